@@ -2,6 +2,7 @@ use proc_macro::TokenStream;
 
 mod generate_tuples;
 mod resource;
+mod system_param;
 mod vulkan_resource;
 
 #[proc_macro_derive(Resource)]
@@ -9,6 +10,13 @@ pub fn derive_resource(input: TokenStream) -> TokenStream {
     resource::impl_derive_resource(input)
 }
 
+/// Implements `SystemParam` for a struct of params so they can be taken by a system as one, the
+/// struct can have one lifetime for the resource bank borrow.
+#[proc_macro_derive(SystemParam)]
+pub fn derive_system_param(input: TokenStream) -> TokenStream {
+    system_param::impl_derive_system_param(input)
+}
+
 #[proc_macro_derive(VulkanResource)]
 pub fn derive_vulkan_resource(input: TokenStream) -> TokenStream {
     vulkan_resource::impl_derive_vulkan_resource(input)
@@ -0,0 +1,84 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, GenericArgument, GenericParam,
+    Lifetime,
+};
+
+pub fn impl_derive_system_param(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let name = &ast.ident;
+    let Data::Struct(data) = &ast.data else {
+        return Error::new(ast.span(), "SystemParam can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+
+    // The struct's lifetime is the resource bank borrow, replaced by the item's lifetime.
+    let lifetimes = ast.generics.lifetimes().collect::<Vec<_>>();
+    if lifetimes.len() > 1 {
+        return Error::new(
+            ast.generics.span(),
+            "SystemParam structs can have at most one lifetime",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let item_lifetime = Lifetime::new("'__rb", proc_macro2::Span::call_site());
+    let item_args = ast
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(_) => GenericArgument::Lifetime(item_lifetime.clone()),
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                GenericArgument::Type(syn::parse_quote!(#ident))
+            }
+            GenericParam::Const(param) => {
+                let ident = &param.ident;
+                GenericArgument::Const(syn::parse_quote!(#ident))
+            }
+        })
+        .collect::<Vec<_>>();
+    let item_args = if item_args.is_empty() {
+        quote! {}
+    } else {
+        quote! { <#(#item_args),*> }
+    };
+
+    let members = data.fields.members().collect::<Vec<_>>();
+    let types = data
+        .fields
+        .iter()
+        .map(|field| &field.ty)
+        .collect::<Vec<_>>();
+
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let gen = quote! {
+        impl #impl_generics crate::engine::system::SystemParam for #name #ty_generics #where_clause {
+            type Item<#item_lifetime> = #name #item_args;
+
+            fn from_resource_bank(
+                resource_bank: &crate::engine::resource::ResourceBank,
+                ticks: crate::engine::change::SystemTicks,
+            ) -> Self::Item<'_> {
+                #name {
+                    #(#members: <#types as crate::engine::system::SystemParam>::from_resource_bank(
+                        resource_bank,
+                        ticks,
+                    ),)*
+                }
+            }
+
+            fn access(access: &mut crate::engine::system::SystemAccess) {
+                #(<#types as crate::engine::system::SystemParam>::access(access);)*
+            }
+        }
+    };
+
+    gen.into()
+}
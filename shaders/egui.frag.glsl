@@ -0,0 +1,48 @@
+DECL_BUFFER(4) EguiTexture {
+  // Premultiplied sRGBA pixels, row by row.
+  uint32_t pixels[];
+};
+
+DECL_PUSH_CONSTANTS {
+  ResourceId vertex_buffer_id;
+  ResourceId index_buffer_id;
+  ResourceId texture_buffer_id;
+  uint32_t first_index;
+  uint32_t vertex_offset;
+  uint32_t texture_width;
+  uint32_t texture_height;
+  uint32_t texture_linear;
+  float screen_width;
+  float screen_height;
+} push_constants;
+
+layout(location = 0) in vec2 in_uv;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 o_color;
+
+vec4 texel(ivec2 coord) {
+  ivec2 size = ivec2(push_constants.texture_width, push_constants.texture_height);
+  coord = clamp(coord, ivec2(0), size - 1);
+  EguiTexture egui_texture = get_buffer(push_constants.texture_buffer_id, EguiTexture);
+  return unpackUnorm4x8(egui_texture.pixels[coord.y * size.x + coord.x]);
+}
+
+// The texture is a buffer so filtering is done by hand.
+vec4 sample_texture(vec2 uv) {
+  vec2 position = uv * vec2(push_constants.texture_width, push_constants.texture_height);
+  if (push_constants.texture_linear == 0) {
+    return texel(ivec2(floor(position)));
+  }
+
+  position -= 0.5;
+  ivec2 coord = ivec2(floor(position));
+  vec2 t = fract(position);
+  vec4 top = mix(texel(coord), texel(coord + ivec2(1, 0)), t.x);
+  vec4 bottom = mix(texel(coord + ivec2(0, 1)), texel(coord + ivec2(1, 1)), t.x);
+  return mix(top, bottom, t.y);
+}
+
+void main() {
+  o_color = in_color * sample_texture(in_uv);
+}
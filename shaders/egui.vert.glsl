@@ -0,0 +1,40 @@
+DECL_BUFFER(4) EguiVertices {
+  // Per vertex the position and uv in points followed by the color's bits.
+  float data[];
+};
+
+DECL_BUFFER(4) EguiIndices {
+  uint32_t indices[];
+};
+
+DECL_PUSH_CONSTANTS {
+  ResourceId vertex_buffer_id;
+  ResourceId index_buffer_id;
+  ResourceId texture_buffer_id;
+  uint32_t first_index;
+  uint32_t vertex_offset;
+  uint32_t texture_width;
+  uint32_t texture_height;
+  uint32_t texture_linear;
+  float screen_width;
+  float screen_height;
+} push_constants;
+
+const uint32_t VERTEX_FLOATS = 5;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+void main() {
+  EguiIndices indices = get_buffer(push_constants.index_buffer_id, EguiIndices);
+  EguiVertices vertices = get_buffer(push_constants.vertex_buffer_id, EguiVertices);
+
+  uint32_t index = indices.indices[push_constants.first_index + gl_VertexIndex];
+  uint32_t base = (push_constants.vertex_offset + index) * VERTEX_FLOATS;
+  vec2 position = vec2(vertices.data[base], vertices.data[base + 1]);
+
+  out_uv = vec2(vertices.data[base + 2], vertices.data[base + 3]);
+  // Premultiplied sRGBA, blended as is since the backbuffer isn't sRGB either.
+  out_color = unpackUnorm4x8(floatBitsToUint(vertices.data[base + 4]));
+  gl_Position = vec4(2.0 * position / vec2(push_constants.screen_width, push_constants.screen_height) - 1.0, 0.0, 1.0);
+}
@@ -36,13 +36,14 @@ DECL_BUFFER(16) Camera {
   float fov;
 };
 
-DECL_BUFFER(4) ChunkOccupancyGrid {
-  uint8_t grid[];
+DECL_BUFFER(4) ChunkIndicesGrid {
+  // First two most signifigant bits are status flag, the rest is the brick index block.
+  uint32_t grid[];
 };
 
-DECL_BUFFER_COHERENT(4) BrickIndicesGrid {
+DECL_BUFFER_COHERENT(4) BrickIndexPool {
   // First two most signifigant bits are status flag.
-  uint32_t grid[];
+  uint32_t indices[];
 };
 
 struct BrickData {
@@ -79,8 +80,8 @@ DECL_BUFFER(16) VoxelWorldInfo {
   i32vec3 chunk_center;
   u32vec3 chunk_translation;
   ResourceId super_chunk_occupancy_grid_buffer;
-  ResourceId chunk_occupancy_grid_buffer;
-  ResourceId brick_indices_grid_buffer;
+  ResourceId chunk_indices_grid_buffer;
+  ResourceId brick_index_pool_buffer;
   ResourceId brick_data_buffer;
   ResourceId brick_palette_list_buffer;
   ResourceId brick_palette_indices_list_buffer;
@@ -88,6 +89,32 @@ DECL_BUFFER(16) VoxelWorldInfo {
 
   uint32_t chunk_side_length;
   uint32_t chunk_half_length;
+
+  ResourceId voxel_object_list_buffer;
+  ResourceId voxel_object_bvh_buffer;
+  uint32_t voxel_object_count;
+};
+
+struct VoxelObject {
+  mat4 world_to_object;
+  uint32_t block_index;
+};
+
+DECL_BUFFER(16) VoxelObjectList {
+  VoxelObject objects[];
+};
+
+struct BvhNode {
+  vec3 min;
+  // Left child index for interior nodes, right child is the next node, first object for leaves.
+  uint32_t left_first;
+  vec3 max;
+  // The # of objects in the leaf, 0 for interior nodes.
+  uint32_t count;
+};
+
+DECL_BUFFER(4) VoxelObjectBvh {
+  BvhNode nodes[];
 };
 
 struct VoxelMaterial {
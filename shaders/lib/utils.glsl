@@ -132,3 +132,17 @@ u32vec3 morton_decode_3(uint32_t morton) {
   return u32vec3(morton_compact_by_2(morton), morton_compact_by_2(morton >> 1), morton_compact_by_2(morton >> 2));
 }
 
+
+// Linear index of a chunk in the dynamic world's chunk grid.
+uint32_t dyn_chunk_index(u32vec3 chunk_pos, uint32_t side_length) {
+  return chunk_pos.x + (chunk_pos.y + chunk_pos.z * side_length) * side_length;
+}
+
+u32vec3 dyn_chunk_position(uint32_t chunk_index, uint32_t side_length) {
+  return u32vec3(chunk_index % side_length, (chunk_index / side_length) % side_length, chunk_index / (side_length * side_length));
+}
+
+// Each loaded chunk owns a morton ordered block of CHUNK_VOLUME brick indices in the brick index pool.
+uint32_t brick_pool_index(uint32_t block_index, u32vec3 brick_local) {
+  return block_index * CHUNK_VOLUME + morton_encode_3(brick_local.x, brick_local.y, brick_local.z);
+}
@@ -30,7 +30,8 @@ uint32_t triple32(uint32_t x) {
 
 bool check_voxel_occupancy(i32vec3 i_voxel_position) {
   VoxelWorldInfo info = get_buffer(push_constants.voxel_world_info_id, VoxelWorldInfo);
-  BrickIndicesGrid brick_indices_grid = get_buffer(info.brick_indices_grid_buffer, BrickIndicesGrid);
+  ChunkIndicesGrid chunk_indices_grid = get_buffer(info.chunk_indices_grid_buffer, ChunkIndicesGrid);
+  BrickIndexPool brick_pool = get_buffer(info.brick_index_pool_buffer, BrickIndexPool);
   BrickDataList brick_data_list = get_buffer(info.brick_data_buffer, BrickDataList);
 
   if (i_voxel_position.x < 0 || i_voxel_position.y < 0 || i_voxel_position.z < 0) {
@@ -44,10 +45,17 @@ bool check_voxel_occupancy(i32vec3 i_voxel_position) {
 
   u32vec3 brick_position = voxel_position / BRICK_LENGTH;
   u32vec3 voxel_local = voxel_position % BRICK_LENGTH;
-  uint32_t brick_morton = morton_encode_3(brick_position.x, brick_position.y, brick_position.z);
   uint32_t voxel_morton = morton_encode_3(voxel_local.x, voxel_local.y, voxel_local.z);
 
-  uint32_t brick_index = brick_indices_grid.grid[brick_morton];
+  uint32_t chunk_indices = chunk_indices_grid.grid[dyn_chunk_index(brick_position / CHUNK_LENGTH, info.chunk_side_length)];
+  uint32_t chunk_status = chunk_indices >> 30;
+  if(chunk_status == 3) {
+    return false;
+  }
+  if(chunk_status == 0 || chunk_status == 1) {
+    return true;
+  }
+  uint32_t brick_index = brick_pool.indices[brick_pool_index(chunk_indices & 0x3FFFFFFF, brick_position % CHUNK_LENGTH)];
   uint32_t brick_status = brick_index >> 30;
   if(brick_status == 3) {
     return false;
@@ -56,7 +64,7 @@ bool check_voxel_occupancy(i32vec3 i_voxel_position) {
     return true;
   }
   uint32_t brick_data_index = brick_index & 0x3FFFFFFF;
-  BrickData brick_data = brick_data_list.data[brick_index];
+  BrickData brick_data = brick_data_list.data[brick_data_index];
   uint32_t voxel_status = brick_data.voxel_mask[voxel_morton >> 3] & (1 << (voxel_morton & 7));
   if (voxel_status != 0) {
     // debugPrintfEXT("check brick inde xdata %d\n", brick_data_index);
@@ -68,23 +76,29 @@ bool check_voxel_occupancy(i32vec3 i_voxel_position) {
 
 void main() {
   VoxelWorldInfo info = get_buffer(push_constants.voxel_world_info_id, VoxelWorldInfo);
-  BrickIndicesGrid brick_indices = get_buffer(info.brick_indices_grid_buffer, BrickIndicesGrid);
+  ChunkIndicesGrid chunk_indices_grid = get_buffer(info.chunk_indices_grid_buffer, ChunkIndicesGrid);
+  BrickIndexPool brick_pool = get_buffer(info.brick_index_pool_buffer, BrickIndexPool);
   BrickDataList brick_data_list = get_buffer(info.brick_data_buffer, BrickDataList);
   BrickPaletteIndicesList brick_palette_indices_list = get_buffer(info.brick_palette_indices_list_buffer, BrickPaletteIndicesList);
   BrickPaletteListVolatile palette_list = get_buffer(info.brick_palette_list_buffer, BrickPaletteListVolatile);
 
   BrickProcessList to_process = get_buffer(push_constants.to_process_bricks, BrickProcessList);
   uint32_t to_process_index = gl_GlobalInvocationID.x / 512;
-  uint32_t current_brick_morton = to_process.bricks[to_process_index];
-  uint32_t brick_data_index = brick_indices.grid[current_brick_morton] & 0x3FFFFFFF;
+  // The dyn brick index, tiled by chunk so the chunk index and local brick morton can be split off.
+  uint32_t current_dyn_brick_index = to_process.bricks[to_process_index];
+  uint32_t current_chunk_index = current_dyn_brick_index / CHUNK_VOLUME;
+  uint32_t current_brick_morton = current_dyn_brick_index % CHUNK_VOLUME;
+  uint32_t block_index = chunk_indices_grid.grid[current_chunk_index] & 0x3FFFFFFF;
+  uint32_t brick_data_index = brick_pool.indices[block_index * CHUNK_VOLUME + current_brick_morton] & 0x3FFFFFFF;
   BrickData current_brick_data = brick_data_list.data[brick_data_index];
   uint32_t current_voxel_morton = gl_GlobalInvocationID.x % 512;
   uint32_t voxel_status = current_brick_data.voxel_mask[current_voxel_morton >> 3] & (1 << (current_voxel_morton & 7));
   // dont process empty voxels
-  // debugPrintfEXT("current brick index %d\n", current_brick_morton);
+  // debugPrintfEXT("current brick index %d\n", current_dyn_brick_index);
   uint32_t palette_index = current_brick_data.palette_index & 0x3FFFFFFF;
   u32vec3 voxel_local_position = morton_decode_3(current_voxel_morton);
-  u32vec3 brick_local_position = morton_decode_3(current_brick_morton);
+  u32vec3 brick_local_position = dyn_chunk_position(current_chunk_index, info.chunk_side_length) * CHUNK_LENGTH
+    + morton_decode_3(current_brick_morton);
   // debugPrintfEXT("processing brick %d, %d, %d\n", brick_local_position.x, brick_local_position.y, brick_local_position.z);
   u32vec3 voxel_world_position = brick_local_position * BRICK_LENGTH + voxel_local_position;
   if(voxel_status == 0) {
@@ -14,21 +14,32 @@ DECL_PUSH_CONSTANTS {
 struct TraceWorldOut {
   vec3 color;
   bool hit;
+  // The distance along the ray to the hit.
+  float t;
 };
 
+const float FAR_T = 1.0e30;
+
 TraceWorldOut trace_world_out_miss() {
-  return TraceWorldOut(vec3(0, 0, 0), false);
+  return TraceWorldOut(vec3(0, 0, 0), false, FAR_T);
 }
 
 TraceWorldOut trace_world_out_hit(vec3 color) {
-  return TraceWorldOut(color, true);
+  return TraceWorldOut(color, true, FAR_T);
+}
+
+TraceWorldOut trace_world_out_hit_at(vec3 color, float t) {
+  return TraceWorldOut(color, true, t);
 }
 
 const float EPSILON = 0.000001;
+const uint32_t BVH_STACK_SIZE = 32;
 const vec3 LIGHT_DIR = normalize(vec3(-0.5,-1,1));
 const vec3 LIGHT_POS = vec3(100, 100, 0);
 
-TraceWorldOut trace_brick(Ray ray, uint32_t data_index, vec3 normal, in VoxelWorldInfo info, vec3 brick_world_pos) {
+// The space ray is the ray in the same space as brick_world_pos, world space for the terrain and
+// object space for voxel objects.
+TraceWorldOut trace_brick(Ray ray, uint32_t data_index, vec3 normal, in VoxelWorldInfo info, vec3 brick_world_pos, in Ray space_ray) {
   if(data_index >= 500000) {
     return trace_world_out_hit(vec3(0.6, 0.1, 0.1));
   }
@@ -64,7 +75,8 @@ TraceWorldOut trace_brick(Ray ray, uint32_t data_index, vec3 normal, in VoxelWor
       // half lambert
       float dotl = max(dot(mat.normal, -LIGHT_DIR), 0.1);
       float diff = pow(dotl * 0.5 + 0.5, 5.0);
-      return trace_world_out_hit(mat.albedo * dotl);
+      float t = dot(voxel_world_pos + 0.5 * VOXEL_WORLD_LENGTH - space_ray.origin, space_ray.dir);
+      return trace_world_out_hit_at(mat.albedo * dotl, t);
     }
 
     bvec3 mask = lessThanEqual(curr_t.xyz, min(curr_t.yzx, curr_t.zxy));
@@ -76,8 +88,8 @@ TraceWorldOut trace_brick(Ray ray, uint32_t data_index, vec3 normal, in VoxelWor
   return trace_world_out_miss();
 }
 
-TraceWorldOut trace_chunk(Ray ray, i32vec3 chunk_local, u32vec3 chunk_translated_local, vec3 normal, in VoxelWorldInfo info, vec3 dyn_world_min) { 
-  BrickIndicesGrid brick_grid = get_buffer(info.brick_indices_grid_buffer, BrickIndicesGrid);
+TraceWorldOut trace_chunk(Ray ray, vec3 chunk_world_pos, uint32_t block_index, vec3 normal, in VoxelWorldInfo info, in Ray space_ray) { 
+  BrickIndexPool brick_pool = get_buffer(info.brick_index_pool_buffer, BrickIndexPool);
 
   i32vec3 map_pos = i32vec3(floor(ray.origin));
   i32vec3 step_axes = i32vec3(sign(ray.dir));
@@ -89,9 +101,7 @@ TraceWorldOut trace_chunk(Ray ray, i32vec3 chunk_local, u32vec3 chunk_translated
 
   while(map_pos.x >= 0 && map_pos.y >= 0 && map_pos.z >= 0 && 
     map_pos.x < CHUNK_LENGTH && map_pos.y < CHUNK_LENGTH && map_pos.z < CHUNK_LENGTH) {
-    u32vec3 translated_brick_pos = chunk_translated_local * CHUNK_LENGTH + u32vec3(map_pos);
-    uint32_t brick_morton = morton_encode_3(translated_brick_pos.x, translated_brick_pos.y, translated_brick_pos.z);
-    uint32_t brick_index = brick_grid.grid[brick_morton];
+    uint32_t brick_index = brick_pool.indices[brick_pool_index(block_index, u32vec3(map_pos))];
     uint32_t brick_status = brick_index >> 30;
     if (brick_status == 2) {
       uint32_t data_index = brick_index & 0x3FFFFFFF;
@@ -101,9 +111,8 @@ TraceWorldOut trace_chunk(Ray ray, i32vec3 chunk_local, u32vec3 chunk_translated
 
       vec3 brick_enter_pos = ray.origin + ray.dir * (min(min(last_t.x, last_t.y), last_t.z));
       Ray brick_local_ray = Ray((clamp(brick_enter_pos - map_pos, EPSILON, 1.0 - EPSILON)) * BRICK_LENGTH, ray.dir, ray.inv_dir);
-      vec3 chunk_world_pos = dyn_world_min + vec3(chunk_local) * CHUNK_WORLD_LENGTH;
       vec3 brick_world_pos = chunk_world_pos + vec3(map_pos) * BRICK_WORLD_LENGTH;
-      TraceWorldOut brick_result = trace_brick(brick_local_ray, data_index, normal, info, brick_world_pos);
+      TraceWorldOut brick_result = trace_brick(brick_local_ray, data_index, normal, info, brick_world_pos, space_ray);
       if(brick_result.hit) {
         return brick_result;
       }
@@ -119,13 +128,13 @@ TraceWorldOut trace_chunk(Ray ray, i32vec3 chunk_local, u32vec3 chunk_translated
 
 TraceWorldOut trace_vox_world(Ray ray) {
   VoxelWorldInfo info = get_buffer(push_constants.voxel_world_info_id, VoxelWorldInfo);
-  ChunkOccupancyGrid chunk_occupancy_grid = get_buffer(info.chunk_occupancy_grid_buffer, ChunkOccupancyGrid);
+  ChunkIndicesGrid chunk_indices_grid = get_buffer(info.chunk_indices_grid_buffer, ChunkIndicesGrid);
 
+  Ray world_ray = ray;
   float dyn_world_world_side_length = info.chunk_side_length * CHUNK_WORLD_LENGTH;
-  float dyn_world_world_half_length = dyn_world_world_side_length / 2;
 
-  vec3 dyn_world_center = info.chunk_center * CHUNK_WORLD_LENGTH;
-  AABB dyn_world_aabb = AABB(dyn_world_center - dyn_world_world_half_length, dyn_world_center + dyn_world_world_half_length);
+  vec3 dyn_world_min = (info.chunk_center - i32vec3(info.chunk_half_length)) * CHUNK_WORLD_LENGTH;
+  AABB dyn_world_aabb = AABB(dyn_world_min, dyn_world_min + dyn_world_world_side_length);
 
   RayAABBIntersection intersection = ray_aabb_intersection(ray, dyn_world_aabb);
   if(!intersection.hit) {
@@ -133,7 +142,7 @@ TraceWorldOut trace_vox_world(Ray ray) {
   }
 
   vec3 enter_pos = ray.origin + ray.dir * (intersection.tenter);
-  vec3 normalized_world_pos = ((enter_pos - dyn_world_center) + dyn_world_world_half_length) / dyn_world_world_side_length;
+  vec3 normalized_world_pos = (enter_pos - dyn_world_min) / dyn_world_world_side_length;
 
   // Transform the ray to world-chunk space
   ray = Ray(normalized_world_pos * info.chunk_side_length, ray.dir, ray.inv_dir);
@@ -150,13 +159,16 @@ TraceWorldOut trace_vox_world(Ray ray) {
   while(map_pos.x >= 0 && map_pos.y >= 0 && map_pos.z >= 0 && map_pos.x < info.chunk_side_length && map_pos.y < info.chunk_side_length && map_pos.z < info.chunk_side_length) {
 
     u32vec3 translated_map_pos = u32vec3((map_pos + i32vec3(info.chunk_side_length) + info.chunk_translation) % info.chunk_side_length);
-    uint32_t chunk_morton = morton_encode_3(translated_map_pos.x, translated_map_pos.y, translated_map_pos.z);
-    uint32_t chunk_status = ((chunk_occupancy_grid.grid[chunk_morton >> 3] >> (chunk_morton & 7)) & 1);
-    if(chunk_status == 1) {
+    uint32_t chunk_index = dyn_chunk_index(translated_map_pos, info.chunk_side_length);
+    uint32_t chunk_indices = chunk_indices_grid.grid[chunk_index];
+    uint32_t chunk_status = chunk_indices >> 30;
+    if(chunk_status == 2) {
+      uint32_t block_index = chunk_indices & 0x3FFFFFFF;
       vec3 chunk_enter_pos = ray.origin + ray.dir * (min(min(last_t.x, last_t.y), last_t.z));
       Ray brick_local_ray = Ray((clamp(chunk_enter_pos - map_pos, EPSILON, 1.0 - EPSILON)) * CHUNK_LENGTH, ray.dir, ray.inv_dir);
       vec3 normal = vec3(lessThanEqual(last_t.xyz, min(last_t.yzx, last_t.zxy))) * -step_axes;
-      TraceWorldOut chunk_result = trace_chunk(brick_local_ray, map_pos, translated_map_pos, normal, info, dyn_world_aabb.min);
+      vec3 chunk_world_pos = dyn_world_min + vec3(map_pos) * CHUNK_WORLD_LENGTH;
+      TraceWorldOut chunk_result = trace_chunk(brick_local_ray, chunk_world_pos, block_index, normal, info, world_ray);
       if(chunk_result.hit) {
         return chunk_result;
       }
@@ -171,6 +183,61 @@ TraceWorldOut trace_vox_world(Ray ray) {
   return trace_world_out_hit(vec3(0,0.5,0.8));
 }
 
+// Traces the object's chunk sized volume in object space, the transform is rigid so t is the
+// same as in world space.
+TraceWorldOut trace_object(Ray world_ray, VoxelObject object, in VoxelWorldInfo info) {
+  vec3 origin = (object.world_to_object * vec4(world_ray.origin, 1.0)).xyz;
+  vec3 dir = mat3(object.world_to_object) * world_ray.dir;
+  Ray object_ray = Ray(origin, dir, 1.0 / dir);
+
+  AABB object_aabb = AABB(vec3(0.0), vec3(CHUNK_WORLD_LENGTH));
+  RayAABBIntersection intersection = ray_aabb_intersection(object_ray, object_aabb);
+  if(!intersection.hit) {
+    return trace_world_out_miss();
+  }
+
+  vec3 enter_pos = object_ray.origin + object_ray.dir * intersection.tenter;
+  Ray brick_local_ray = Ray(clamp(enter_pos / CHUNK_WORLD_LENGTH, EPSILON, 1.0 - EPSILON) * CHUNK_LENGTH, dir, object_ray.inv_dir);
+  vec3 normal = vec3(greaterThanEqual(intersection.tmin.xyz, max(intersection.tmin.yzx, intersection.tmin.zxy))) * -sign(dir);
+  return trace_chunk(brick_local_ray, vec3(0.0), object.block_index, normal, info, object_ray);
+}
+
+// Walks the object BVH and returns the closest object hit.
+TraceWorldOut trace_objects(Ray world_ray, in VoxelWorldInfo info) {
+  TraceWorldOut closest = trace_world_out_miss();
+  if(info.voxel_object_count == 0) {
+    return closest;
+  }
+
+  VoxelObjectBvh bvh = get_buffer(info.voxel_object_bvh_buffer, VoxelObjectBvh);
+  VoxelObjectList object_list = get_buffer(info.voxel_object_list_buffer, VoxelObjectList);
+
+  uint32_t stack[BVH_STACK_SIZE];
+  uint32_t stack_size = 0;
+  stack[stack_size++] = 0;
+  while(stack_size > 0) {
+    BvhNode node = bvh.nodes[stack[--stack_size]];
+    RayAABBIntersection intersection = ray_aabb_intersection(world_ray, AABB(node.min, node.max));
+    if(!intersection.hit || intersection.tenter > closest.t) {
+      continue;
+    }
+
+    if(node.count > 0) {
+      for(uint32_t i = node.left_first; i < node.left_first + node.count; i++) {
+        TraceWorldOut object_result = trace_object(world_ray, object_list.objects[i], info);
+        if(object_result.hit && object_result.t < closest.t) {
+          closest = object_result;
+        }
+      }
+    } else if(stack_size + 2 <= BVH_STACK_SIZE) {
+      stack[stack_size++] = node.left_first;
+      stack[stack_size++] = node.left_first + 1;
+    }
+  }
+
+  return closest;
+}
+
 void main() {
   Camera camera = get_buffer(push_constants.camera_id, Camera);
 
@@ -198,6 +265,11 @@ void main() {
   Ray ray = Ray(ro, rd, 1.0 / rd);
 
   TraceWorldOut trace_world_out = trace_vox_world(ray);
+  VoxelWorldInfo info = get_buffer(push_constants.voxel_world_info_id, VoxelWorldInfo);
+  TraceWorldOut trace_objects_out = trace_objects(ray, info);
+  if(trace_objects_out.hit && trace_objects_out.t < trace_world_out.t) {
+    trace_world_out = trace_objects_out;
+  }
 
   vec3 color = vec3(0.0);
   if(trace_world_out.hit) {
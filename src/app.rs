@@ -1,27 +1,58 @@
+use std::collections::HashSet;
+
 use winit::event_loop::EventLoop;
 
 use crate::engine::{
+    event::Events,
     graphics::{device::DeviceResource, swapchain::SwapchainResource},
-    input::Input,
-    resource::ResourceBank,
+    input::{mouse, Input, InputEvent},
+    plugin::{Plugin, PluginId},
+    resource::{Resource, ResourceBank},
+    schedule::{IntoSystemConfig, Schedule, Stage},
+    ui::egui::Egui,
+    window::window::Window,
 };
-use winit::event::{Event as WinitEvent, WindowEvent as WinitWindowEvent};
+use winit::event::{ElementState, Event as WinitEvent, WindowEvent as WinitWindowEvent};
 
 pub struct App {
+    /// None for headless apps and once the app is running.
     event_loop: Option<EventLoop<()>>,
+    headless: bool,
     resource_bank: ResourceBank,
+    schedule: Schedule,
+    plugins: HashSet<PluginId>,
 }
 
 impl App {
     pub fn new() -> Self {
         Self {
             event_loop: Some(EventLoop::new().expect("Failed to create event loop")),
+            headless: false,
+            resource_bank: ResourceBank::new(),
+            schedule: Schedule::new(),
+            plugins: HashSet::new(),
+        }
+    }
+
+    /// An app without an event loop, so no window or GPU, driven by `run_ticks` instead of `run`.
+    pub fn new_headless() -> Self {
+        Self {
+            event_loop: None,
+            headless: true,
             resource_bank: ResourceBank::new(),
+            schedule: Schedule::new(),
+            plugins: HashSet::new(),
         }
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
     pub fn event_loop(&self) -> &EventLoop<()> {
-        self.event_loop.as_ref().unwrap()
+        self.event_loop
+            .as_ref()
+            .expect("Headless apps don't have an event loop to create windows with")
     }
 
     pub fn resource_bank(&self) -> &ResourceBank {
@@ -32,39 +63,128 @@ impl App {
         &mut self.resource_bank
     }
 
-    pub fn run(mut self) {
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> &mut Self {
+        self.resource_bank.insert(resource);
+        self
+    }
+
+    /// Builds the plugin, panics if it was already added or a plugin it depends on wasn't.
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        let id = PluginId::of::<P>();
+        if self.plugins.contains(&id) {
+            panic!("Plugin {} was added twice", plugin.name());
+        }
+        for dependency in plugin.dependencies() {
+            if !self.plugins.contains(&dependency) {
+                panic!(
+                    "Plugin {} depends on {} which has to be added first",
+                    plugin.name(),
+                    dependency.name()
+                );
+            }
+        }
+
+        plugin.build(self);
+        self.plugins.insert(id);
+        self
+    }
+
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        self.plugins.contains(&PluginId::of::<P>())
+    }
+
+    pub fn schedule_mut(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+
+    pub fn add_system<M>(&mut self, stage: Stage, system: impl IntoSystemConfig<M>) -> &mut Self {
+        self.schedule.add_system(stage, system);
+        self
+    }
+
+    /// Inserts the event queue and swaps its buffers at the end of each frame.
+    pub fn add_event<E: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.resource_bank.insert(Events::<E>::new());
+        self.schedule
+            .add_system(Stage::Cleanup, Events::<E>::update);
+        self
+    }
+
+    /// Runs the schedule the given # of times back to back.
+    pub fn run_ticks(&mut self, ticks: u32) {
+        self.schedule
+            .build()
+            .unwrap_or_else(|err| panic!("Failed to build schedule: {}", err));
+
+        for _ in 0..ticks {
+            self.schedule.run(&mut self.resource_bank);
+        }
+    }
+
+    /// Runs until the window is closed, the app is kept so its resources can be used afterwards.
+    pub fn run(&mut self) {
+        assert!(!self.headless, "Headless apps are run with run_ticks");
+        self.schedule
+            .build()
+            .unwrap_or_else(|err| panic!("Failed to build schedule: {}", err));
+
+        // Later changes arrive as window events.
+        let scale_factor = self
+            .resource_bank
+            .get_resource::<Window>()
+            .handle()
+            .scale_factor();
+        self.resource_bank
+            .get_resource_mut::<Input>()
+            .submit(InputEvent::Mouse(mouse::SubmitInput::ScaleFactor(
+                scale_factor as f32,
+            )));
+
         let event_loop = self.event_loop.take().unwrap();
         event_loop
             .run(move |event, window| {
                 window.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
                 match event {
-                    WinitEvent::WindowEvent { event, .. } => match event {
-                        WinitWindowEvent::CloseRequested => {
-                            window.exit();
-                        }
-                        WinitWindowEvent::Resized(new_size) => {
-                            self.resource_bank()
-                                .get_resource_mut::<SwapchainResource>()
-                                .resize(
-                                    &mut self.resource_bank().get_resource_mut::<DeviceResource>(),
-                                    new_size.width,
-                                    new_size.height,
-                                );
-                        }
-                        event => {
-                            self.resource_bank_mut()
-                                .get_resource_mut::<Input>()
-                                .handle_winit_window_event(event);
+                    WinitEvent::WindowEvent { event, .. } => {
+                        let consumed = self
+                            .resource_bank()
+                            .get_resource_mut::<Egui>()
+                            .handle_window_event(
+                                &self.resource_bank().get_resource::<Window>(),
+                                &event,
+                            );
+
+                        match event {
+                            WinitWindowEvent::CloseRequested => {
+                                window.exit();
+                            }
+                            WinitWindowEvent::Resized(new_size) => {
+                                self.resource_bank()
+                                    .get_resource_mut::<SwapchainResource>()
+                                    .resize(
+                                        &mut self
+                                            .resource_bank()
+                                            .get_resource_mut::<DeviceResource>(),
+                                        new_size.width,
+                                        new_size.height,
+                                    );
+                            }
+                            event if !consumed || is_release(&event) => {
+                                self.resource_bank_mut()
+                                    .get_resource_mut::<Input>()
+                                    .handle_winit_window_event(event);
+                            }
+                            _ => {}
                         }
-                    },
+                    }
                     WinitEvent::DeviceEvent { device_id, event } => {
                         self.resource_bank_mut()
                             .get_resource_mut::<Input>()
                             .handle_winit_device_event(device_id, event);
                     }
                     WinitEvent::AboutToWait => {
-                        crate::game_loop::game_loop(&mut self);
+                        self.schedule.run(&mut self.resource_bank);
                     }
                     _ => {}
                 }
@@ -72,3 +192,78 @@ impl App {
             .expect("Failed to run event loop");
     }
 }
+
+/// Releases reach `Input` even when egui consumes them, otherwise a key pressed before a text
+/// field took focus would stay down.
+fn is_release(event: &WinitWindowEvent) -> bool {
+    match event {
+        WinitWindowEvent::KeyboardInput { event, .. } => event.state == ElementState::Released,
+        WinitWindowEvent::MouseInput { state, .. } => *state == ElementState::Released,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use voxei_macros::Resource;
+
+    use super::*;
+    use crate::{
+        engine::{
+            common::time::Time,
+            plugin::{CorePlugin, HeadlessPlugin},
+            resource::ResMut,
+            voxel::{
+                dynamic_world::SpatialStatus,
+                vox_world::{VoxelWorld, WorldChunkPos},
+            },
+        },
+        setup,
+    };
+
+    #[derive(Resource)]
+    struct FixedUpdates(u32);
+
+    #[test]
+    fn test_headless_ticks_run_one_fixed_update() {
+        let mut app = App::new_headless();
+        app.add_plugin(CorePlugin)
+            .add_plugin(HeadlessPlugin)
+            .insert_resource(FixedUpdates(0))
+            .add_system(Stage::FixedUpdate, |mut updates: ResMut<FixedUpdates>| {
+                updates.0 += 1;
+            });
+
+        app.run_ticks(10);
+        assert_eq!(app.resource_bank().get_resource::<FixedUpdates>().0, 10);
+        let time = app.resource_bank().get_resource::<Time>();
+        assert_eq!(time.total_duration(), time.fixed_delta_time() * 10);
+    }
+
+    #[test]
+    fn test_headless_world_streams_chunks() {
+        let mut app = App::new_headless();
+        setup::add_headless_plugins(&mut app);
+
+        let chunk_status = |app: &App| {
+            let vox_world = app.resource_bank().get_resource::<VoxelWorld>();
+            let dyn_pos = WorldChunkPos::new(0, 0, 0).to_dyn_pos(&vox_world).unwrap();
+            vox_world.dyn_world().chunk_status(dyn_pos)
+        };
+        // Chunks generate on another thread so give it a few seconds.
+        for _ in 0..500 {
+            app.run_ticks(1);
+            let status = chunk_status(&app);
+            if status == SpatialStatus::Loaded || status == SpatialStatus::LoadedEmpty {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!(
+            "The center chunk never loaded, status: {:?}",
+            chunk_status(&app)
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod asset;
 pub mod loader;
+pub mod plugin;
 pub mod watched_shaders;
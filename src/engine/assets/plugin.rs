@@ -0,0 +1,26 @@
+use crate::{
+    app::App,
+    engine::{plugin::Plugin, schedule::Stage},
+};
+
+use super::{
+    asset::Assets,
+    loader::{obj::ObjLoader, spirv::SpirVLoader},
+    watched_shaders::WatchedShaders,
+};
+
+pub struct AssetsPlugin;
+
+impl Plugin for AssetsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut assets = Assets::new();
+        assets.add_loader::<SpirVLoader>();
+        assets.add_loader::<ObjLoader>();
+
+        app.insert_resource(assets)
+            .insert_resource(WatchedShaders::new());
+
+        app.add_system(Stage::PreUpdate, Assets::update)
+            .add_system(Stage::PreUpdate, WatchedShaders::update);
+    }
+}
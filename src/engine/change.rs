@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{
+    resource::ResourceBank,
+    system::{SystemAccess, SystemParam},
+};
+
+/// Shared by every resource bank and ECS world, only the order of ticks matters so one counter
+/// for the whole process is enough.
+static CHANGE_TICK: AtomicU64 = AtomicU64::new(1);
+
+/// Advances the change tick and returns the new one, later ticks are always greater.
+pub fn next_change_tick() -> u64 {
+    CHANGE_TICK.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// The ticks of a system's previous and current run, anything changed after the previous run
+/// started counts as changed, including the system's own writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemTicks {
+    pub last_run: u64,
+    pub this_run: u64,
+}
+
+impl SystemTicks {
+    pub fn new(last_run: u64, this_run: u64) -> Self {
+        Self { last_run, this_run }
+    }
+
+    /// For borrows outside of a system, where everything counts as changed.
+    pub fn always_changed() -> Self {
+        Self::default()
+    }
+
+    pub fn is_changed(&self, changed_tick: u64) -> bool {
+        changed_tick > self.last_run
+    }
+}
+
+impl SystemParam for SystemTicks {
+    type Item<'rb> = SystemTicks;
+
+    fn from_resource_bank(_resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        ticks
+    }
+
+    fn access(_access: &mut SystemAccess) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use voxei_macros::Resource;
+
+    use super::*;
+    use crate::engine::{
+        common::time::Time,
+        resource::{Res, ResMut},
+        schedule::{IntoSystemConfig, Schedule, Stage},
+    };
+
+    #[derive(Resource)]
+    struct Counter(u32);
+
+    #[derive(Resource)]
+    struct Write(bool);
+
+    #[derive(Resource)]
+    struct Seen(Vec<bool>);
+
+    #[test]
+    fn test_systems_see_resource_changes_since_their_last_run() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Time::new());
+        resource_bank.insert(Counter(0));
+        resource_bank.insert(Write(false));
+        resource_bank.insert(Seen(Vec::new()));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(
+                Stage::Update,
+                (|write: Res<Write>, mut counter: ResMut<Counter>| {
+                    if write.0 {
+                        counter.0 += 1;
+                    }
+                })
+                .label("writer"),
+            )
+            .add_system(
+                Stage::Update,
+                (|counter: Res<Counter>, mut seen: ResMut<Seen>| seen.0.push(counter.is_changed()))
+                    .after("writer"),
+            );
+
+        // Inserting counts as a change on the first run.
+        schedule.run(&mut resource_bank);
+        schedule.run(&mut resource_bank);
+        resource_bank.get_resource_mut::<Write>().0 = true;
+        schedule.run(&mut resource_bank);
+        resource_bank.get_resource_mut::<Write>().0 = false;
+        schedule.run(&mut resource_bank);
+        // Writes from outside the schedule count too.
+        resource_bank.get_resource_mut::<Counter>().0 = 5;
+        schedule.run(&mut resource_bank);
+
+        assert_eq!(
+            resource_bank.get_resource::<Seen>().0,
+            vec![true, false, true, false, true]
+        );
+    }
+}
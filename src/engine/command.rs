@@ -0,0 +1,126 @@
+use hecs::{DynamicBundle, Entity};
+use parking_lot::Mutex;
+use voxei_macros::Resource;
+
+use super::{
+    change::SystemTicks,
+    ecs::ecs_world::ECSWorld,
+    resource::{Res, ResMut, Resource, ResourceBank},
+    system::{SystemAccess, SystemParam},
+};
+
+type Command = Box<dyn FnOnce(&mut ResourceBank) + Send>;
+
+/// The commands queued by systems, applied by the schedule at the end of each stage.
+#[derive(Resource)]
+pub struct CommandQueue {
+    commands: Mutex<Vec<Command>>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        Self {
+            commands: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Applies every queued command in the order they were queued.
+    pub fn apply(resource_bank: &mut ResourceBank) {
+        let commands = match resource_bank.try_get::<CommandQueue>() {
+            Some(queue) => std::mem::take(&mut *queue.commands.lock()),
+            None => return,
+        };
+        for command in commands {
+            command(resource_bank);
+        }
+    }
+}
+
+/// Queues changes that need the whole `ResourceBank` or would conflict with other running
+/// systems. Commands only take shared access so systems using them still run in parallel, the
+/// order between systems' commands follows the order they ran in.
+pub struct Commands<'rb> {
+    queue: Res<'rb, CommandQueue>,
+}
+
+impl Commands<'_> {
+    pub fn add(&mut self, command: impl FnOnce(&mut ResourceBank) + Send + 'static) {
+        self.queue.commands.lock().push(Box::new(command));
+    }
+
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) {
+        self.add(move |resource_bank| resource_bank.insert(resource));
+    }
+
+    pub fn remove_resource<R: Resource>(&mut self) {
+        self.add(|resource_bank| {
+            resource_bank.remove::<R>();
+        });
+    }
+
+    pub fn spawn(&mut self, components: impl DynamicBundle + Send + 'static) {
+        self.add(move |resource_bank| {
+            resource_bank
+                .get_resource_mut::<ECSWorld>()
+                .spawn(components);
+        });
+    }
+
+    /// Despawns the entity if it still exists when the command is applied.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |resource_bank| {
+            let _ = resource_bank.get_resource_mut::<ECSWorld>().despawn(entity);
+        });
+    }
+}
+
+impl SystemParam for Commands<'_> {
+    type Item<'rb> = Commands<'rb>;
+
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        Commands {
+            queue: resource_bank.get_resource_with_ticks::<CommandQueue>(ticks),
+        }
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_read::<CommandQueue>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        common::time::Time,
+        schedule::{Schedule, Stage},
+    };
+
+    #[derive(Resource)]
+    struct Counter(u32);
+
+    #[test]
+    fn test_commands_apply_between_stages() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Time::new());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::PreUpdate, |mut commands: Commands| {
+                commands.insert_resource(Counter(0));
+            })
+            .add_system(Stage::Update, |counter: Option<ResMut<Counter>>| {
+                counter.unwrap().0 += 1;
+            })
+            .add_system(Stage::PostUpdate, |mut commands: Commands| {
+                commands.remove_resource::<Counter>();
+            })
+            .add_system(Stage::Cleanup, |counter: Option<Res<Counter>>| {
+                assert!(counter.is_none());
+            });
+
+        schedule.run(&mut resource_bank);
+        assert!(resource_bank.try_get::<Counter>().is_none());
+        assert!(resource_bank.try_get::<CommandQueue>().is_some());
+    }
+}
@@ -4,7 +4,9 @@ use paya::common::AccessFlags;
 use paya::device::Device;
 use paya::gpu_resources::BufferId;
 
+use crate::engine::change::SystemTicks;
 use crate::engine::ecs::ecs_world::ECSWorld;
+use crate::engine::ecs::tracked::Tracked;
 use crate::engine::graphics::device::{
     create_device_buffer_typed, stage_buffer_copy, DeviceResource,
 };
@@ -32,6 +34,8 @@ pub struct Camera {
     resolution: (u32, u32),
     aspect_ratio: f32,
     fov: f32,
+    /// Set when the matrices change and cleared once they're copied to the buffer.
+    dirty: bool,
 
     buffer: BufferId,
 }
@@ -46,12 +50,15 @@ impl Camera {
             resolution: (0, 0),
             aspect_ratio: 0.0,
             fov: 0.0,
+            dirty: true,
 
             buffer: create_device_buffer_typed::<CameraBuffer>(device, "camera_buffer"),
         }
     }
 
+    /// Recomputes the matrices of the cameras whose transform, resolution or fov changed.
     pub fn update_cameras(
+        ticks: SystemTicks,
         mut ecs_world: ResMut<ECSWorld>,
         render_manager: Res<RenderManager>,
         device: Res<DeviceResource>,
@@ -59,7 +66,18 @@ impl Camera {
     ) {
         if let Some(backbuffer_id) = render_manager.backbuffer() {
             let backbuffer_extent = device.get_image(backbuffer_id).info.extent;
-            for (_, (camera, transform)) in ecs_world.query_mut::<(&mut Camera, &Transform)>() {
+            let resolution = (backbuffer_extent.width, backbuffer_extent.height);
+            let settings_changed = settings.is_changed();
+            for (_, (camera, transform)) in
+                ecs_world.query_mut::<(&mut Camera, &Tracked<Transform>)>()
+            {
+                if !transform.is_changed(ticks)
+                    && !settings_changed
+                    && camera.resolution == resolution
+                {
+                    continue;
+                }
+
                 let eye = transform.isometry.translation.vector;
                 let target = eye + transform.isometry * Vector3::z();
                 let aspect_ratio = backbuffer_extent.width as f32 / backbuffer_extent.height as f32;
@@ -71,18 +89,25 @@ impl Camera {
                     nalgebra::Perspective3::new(aspect_ratio, settings.camera_fov, 0.1, 1000.0)
                         .into();
                 camera.proj_view = camera.projection * camera.view;
-                camera.resolution = (backbuffer_extent.width, backbuffer_extent.height);
+                camera.resolution = resolution;
                 camera.aspect_ratio = aspect_ratio;
                 camera.fov = settings.camera_fov;
+                camera.dirty = true;
             }
         }
     }
 
+    /// Copies the matrices to the camera's buffer if they changed since the last copy.
     pub fn record_copy_commands(
-        &self,
+        &mut self,
         device: &mut Device,
         command_recorder: &mut CommandRecorder,
     ) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
         stage_buffer_copy(
             device,
             command_recorder,
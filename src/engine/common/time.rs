@@ -2,7 +2,14 @@ use std::time::{Duration, Instant};
 
 use voxei_macros::Resource;
 
-use crate::engine::resource::ResMut;
+use crate::{
+    engine::resource::{Res, ResMut},
+    settings::Settings,
+};
+
+/// Sleeping overshoots by up to about a millisecond on most platforms so the end of the frame
+/// limiter's wait is spun instead.
+const FRAME_LIMIT_SPIN_DURATION: Duration = Duration::from_millis(1);
 
 #[derive(Resource)]
 pub struct Time {
@@ -10,6 +17,15 @@ pub struct Time {
     time: Instant,
     last_time: Instant,
     start_time: Instant,
+
+    fixed_delta_time: Duration,
+    /// Caps the fixed steps per frame so a slow frame doesn't make the next one slower.
+    max_fixed_steps: u32,
+    fixed_accumulator: Duration,
+    fixed_steps: u32,
+
+    /// Replaces the wall clock's frame time when set.
+    manual_delta_time: Option<Duration>,
 }
 
 impl Time {
@@ -21,21 +37,65 @@ impl Time {
             time,
             last_time: time,
             start_time: time,
+
+            fixed_delta_time: Duration::from_secs_f64(1.0 / 60.0),
+            max_fixed_steps: 8,
+            fixed_accumulator: Duration::ZERO,
+            fixed_steps: 0,
+
+            manual_delta_time: None,
         }
     }
 
     pub fn update(mut time: ResMut<Time>) {
         time.last_time = time.time;
-        time.time = Instant::now();
+        time.time = match time.manual_delta_time {
+            Some(delta_time) => time.last_time + delta_time,
+            None => Instant::now(),
+        };
         time.delta_time = time.time.duration_since(time.last_time);
-        // println!(
-        //     "Delta in ms: {}",
-        //     time.delta_time.as_micros() as f32 / 1000.0
-        // );
-        // println!(
-        //     "FPS: {}",
-        //     1.0 / (time.delta_time.as_micros() as f32 / 1000000.0)
-        // );
+        let delta_time = time.delta_time;
+        time.fixed_steps = time.accumulate_fixed_steps(delta_time);
+    }
+
+    /// Adds the frame's time and returns how many fixed steps should run, dropping any time past
+    /// the max steps.
+    fn accumulate_fixed_steps(&mut self, delta_time: Duration) -> u32 {
+        self.fixed_accumulator += delta_time;
+
+        let mut steps = 0;
+        while self.fixed_accumulator >= self.fixed_delta_time {
+            if steps == self.max_fixed_steps {
+                self.fixed_accumulator = Duration::ZERO;
+                break;
+            }
+            self.fixed_accumulator -= self.fixed_delta_time;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    /// Waits out the rest of the frame when there is an fps limit, run last in the frame.
+    pub fn limit_frame_rate(time: Res<Time>, settings: Res<Settings>) {
+        let Some(fps_limit) = settings.fps_limit else {
+            return;
+        };
+
+        let frame_end = time.time + Duration::from_secs_f64(1.0 / fps_limit as f64);
+        loop {
+            let now = Instant::now();
+            if now >= frame_end {
+                break;
+            }
+
+            let remaining = frame_end - now;
+            if remaining > FRAME_LIMIT_SPIN_DURATION {
+                std::thread::sleep(remaining - FRAME_LIMIT_SPIN_DURATION);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
     }
 
     pub fn delta_time(&self) -> Duration {
@@ -50,7 +110,61 @@ impl Time {
         self.last_time
     }
 
+    /// The time from the start to this frame.
     pub fn total_duration(&self) -> Duration {
-        self.start_time.elapsed()
+        self.time.duration_since(self.start_time)
+    }
+
+    /// The time simulated by each fixed update.
+    pub fn fixed_delta_time(&self) -> Duration {
+        self.fixed_delta_time
+    }
+
+    pub fn set_fixed_delta_time(&mut self, fixed_delta_time: Duration) {
+        self.fixed_delta_time = fixed_delta_time;
+    }
+
+    pub fn manual_delta_time(&self) -> Option<Duration> {
+        self.manual_delta_time
+    }
+
+    /// Advances each frame by the given time instead of the wall clock's, so headless runs and
+    /// tests simulate the same no matter how fast frames actually run.
+    pub fn set_manual_delta_time(&mut self, delta_time: Option<Duration>) {
+        self.manual_delta_time = delta_time;
+    }
+
+    /// The # of fixed updates to run this frame.
+    pub fn fixed_steps(&self) -> u32 {
+        self.fixed_steps
+    }
+
+    /// How far the frame is between the last fixed update and the next one from 0 to 1, used to
+    /// interpolate fixed update state when rendering.
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.fixed_accumulator.as_secs_f32() / self.fixed_delta_time.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_steps_are_decoupled_from_frames() {
+        let mut time = Time::new();
+        time.set_fixed_delta_time(Duration::from_millis(10));
+
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_millis(4)), 0);
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_millis(4)), 0);
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_millis(4)), 1);
+        assert!((time.interpolation_alpha() - 0.2).abs() < 0.0001);
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_millis(25)), 2);
+        assert!((time.interpolation_alpha() - 0.7).abs() < 0.0001);
+
+        // Past the max steps the backlog is dropped.
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_secs(1)), 8);
+        assert_eq!(time.interpolation_alpha(), 0.0);
+        assert_eq!(time.accumulate_fixed_steps(Duration::from_millis(5)), 0);
     }
 }
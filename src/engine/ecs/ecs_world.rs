@@ -1,11 +1,13 @@
-use hecs::{Query, QueryBorrow, With};
+use hecs::{Component, Query, QueryBorrow, With};
 use voxei_macros::Resource;
 
 use crate::{
-    engine::system::SystemParam,
+    engine::{change::SystemTicks, system::SystemParam},
     game::player::player::{PlayerQuery, PlayerTag},
 };
 
+use super::tracked::{Changed, Tracked};
+
 #[derive(Resource)]
 pub struct ECSWorld {
     world: hecs::World,
@@ -27,6 +29,14 @@ impl ECSWorld {
             self.query::<Q>().with::<&'a PlayerTag>() as QueryBorrow<'a, With<Q, &'a PlayerTag>>
         )
     }
+
+    /// Queries the entities whose `Tracked<T>` changed since the system with the ticks last ran.
+    pub fn query_changed<'a, T: Component, Q: Query>(
+        &'a self,
+        ticks: SystemTicks,
+    ) -> Changed<T, Q> {
+        Changed::new(self.query::<(&'a Tracked<T>, Q)>(), ticks)
+    }
 }
 
 impl std::ops::Deref for ECSWorld {
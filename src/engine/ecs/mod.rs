@@ -1 +1,2 @@
 pub mod ecs_world;
+pub mod tracked;
@@ -0,0 +1,70 @@
+use std::ops::{Deref, DerefMut};
+
+use hecs::{Component, Entity, Query, QueryBorrow};
+
+use crate::engine::change::{next_change_tick, SystemTicks};
+
+/// A component that records when it was last mutably dereferenced so systems can skip entities
+/// that haven't changed, see `ECSWorld::query_changed`.
+pub struct Tracked<T> {
+    value: T,
+    changed_tick: u64,
+}
+
+impl<T> Tracked<T> {
+    /// Spawning counts as a change.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            changed_tick: next_change_tick(),
+        }
+    }
+
+    pub fn is_changed(&self, ticks: SystemTicks) -> bool {
+        ticks.is_changed(self.changed_tick)
+    }
+
+    /// Mutably borrows the value without marking it as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.changed_tick = next_change_tick();
+        &mut self.value
+    }
+}
+
+/// A query filtered to the entities whose `Tracked<T>` changed since the system last ran.
+pub struct Changed<'a, T: Component, Q: Query> {
+    query: QueryBorrow<'a, (&'a Tracked<T>, Q)>,
+    ticks: SystemTicks,
+}
+
+impl<'a, T: Component, Q: Query> Changed<'a, T, Q> {
+    pub fn new(query: QueryBorrow<'a, (&'a Tracked<T>, Q)>, ticks: SystemTicks) -> Self {
+        Self { query, ticks }
+    }
+
+    pub fn iter(&mut self) -> impl Iterator<Item = (Entity, Q::Item<'_>)> + '_ {
+        let ticks = self.ticks;
+        self.query
+            .iter()
+            .filter(move |(_, (tracked, _))| tracked.is_changed(ticks))
+            .map(|(entity, (_, item))| (entity, item))
+    }
+}
@@ -0,0 +1,149 @@
+use super::{
+    change::SystemTicks,
+    resource::{Res, ResMut, Resource, ResourceBank},
+    system::{SystemAccess, SystemParam},
+};
+
+/// A double buffered event queue, events sent during a frame are read during the next one so
+/// every reader sees them once no matter what order the systems run in.
+pub struct Events<E> {
+    /// The events sent last frame, what readers see.
+    sent: Vec<E>,
+    /// The events sent this frame.
+    sending: Vec<E>,
+}
+
+impl<E: Send + Sync + 'static> Resource for Events<E> {}
+
+impl<E: Send + Sync + 'static> Events<E> {
+    pub fn new() -> Self {
+        Self {
+            sent: Vec::new(),
+            sending: Vec::new(),
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.sending.push(event);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<E> {
+        self.sent.iter()
+    }
+
+    /// Swaps the buffers, dropping the events readers have seen. Run once at the end of the
+    /// frame, like `Input::clear_inputs`.
+    pub fn update(mut events: ResMut<Events<E>>) {
+        let events = &mut *events;
+        std::mem::swap(&mut events.sent, &mut events.sending);
+        events.sending.clear();
+    }
+}
+
+/// Reads the events sent last frame. A reader in a stage that runs more than once a frame sees
+/// the same events each time.
+pub struct EventReader<'rb, E: Send + Sync + 'static> {
+    events: Res<'rb, Events<E>>,
+}
+
+impl<E: Send + Sync + 'static> EventReader<'_, E> {
+    pub fn iter(&self) -> std::slice::Iter<E> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.sent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.sent.is_empty()
+    }
+}
+
+impl<E: Send + Sync + 'static> SystemParam for EventReader<'_, E> {
+    type Item<'rb> = EventReader<'rb, E>;
+
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        EventReader {
+            events: resource_bank.get_resource_with_ticks::<Events<E>>(ticks),
+        }
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_read::<Events<E>>();
+    }
+}
+
+/// Sends events to be read next frame.
+pub struct EventWriter<'rb, E: Send + Sync + 'static> {
+    events: ResMut<'rb, Events<E>>,
+}
+
+impl<E: Send + Sync + 'static> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.sending.extend(events);
+    }
+}
+
+impl<E: Send + Sync + 'static> SystemParam for EventWriter<'_, E> {
+    type Item<'rb> = EventWriter<'rb, E>;
+
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        EventWriter {
+            events: resource_bank.get_resource_mut_with_ticks::<Events<E>>(ticks),
+        }
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_write::<Events<E>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        common::time::Time,
+        resource::ResMut,
+        schedule::{IntoSystemConfig, Schedule, Stage},
+    };
+    use voxei_macros::Resource;
+
+    #[derive(Resource)]
+    struct Received(Vec<u32>);
+
+    #[test]
+    fn test_events_are_read_the_frame_after_being_sent() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Time::new());
+        resource_bank.insert(Received(Vec::new()));
+        resource_bank.insert(Events::<u32>::new());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(
+                Stage::Update,
+                (|reader: EventReader<u32>, mut received: ResMut<Received>| {
+                    received.0.extend(reader.iter());
+                })
+                .label("reader"),
+            )
+            // Runs after the reader but the reader only sees last frame's events anyway.
+            .add_system(
+                Stage::Update,
+                (|mut writer: EventWriter<u32>| writer.send_batch([1, 2])).after("reader"),
+            )
+            .add_system(Stage::Cleanup, Events::<u32>::update);
+
+        schedule.run(&mut resource_bank);
+        assert!(resource_bank.get_resource::<Received>().0.is_empty());
+        schedule.run(&mut resource_bank);
+        assert_eq!(resource_bank.get_resource::<Received>().0, vec![1, 2]);
+        schedule.run(&mut resource_bank);
+        assert_eq!(resource_bank.get_resource::<Received>().0, vec![1, 2, 1, 2]);
+    }
+}
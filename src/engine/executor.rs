@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc,
+};
+
+use super::{resource::ResourceBank, schedule::StageSystems, system::SystemAccess};
+
+/// How the systems in a stage are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutorKind {
+    /// One after another on the calling thread.
+    Serial,
+    /// On the rayon thread pool, any systems that don't borrow the same resources mutably and
+    /// aren't ordered against each other run at the same time.
+    Parallel,
+}
+
+/// The access of the systems currently running, checked before starting another so a bad
+/// schedule panics with the systems involved instead of blocking on a resource's lock.
+#[derive(Default)]
+struct RunningAccess {
+    running: HashMap<usize, (&'static str, SystemAccess)>,
+}
+
+impl RunningAccess {
+    fn start(&mut self, system: usize, name: &'static str, access: &SystemAccess) {
+        for (other_name, other_access) in self.running.values() {
+            let resources = access.conflicts_with(other_access);
+            if !resources.is_empty() {
+                panic!(
+                    "Deadlock: system {} would wait on {} held by running system {}",
+                    name,
+                    resources.join(", "),
+                    other_name
+                );
+            }
+        }
+        self.running.insert(system, (name, access.clone()));
+    }
+
+    fn finish(&mut self, system: usize) {
+        self.running.remove(&system);
+    }
+}
+
+/// Runs a built stage's systems on the rayon thread pool, starting each system once every system
+/// it depends on has finished.
+pub(crate) fn run_parallel(stage_systems: &mut StageSystems, resource_bank: &ResourceBank) {
+    let StageSystems {
+        systems,
+        order,
+        dependents,
+    } = stage_systems;
+    let system_count = systems.len();
+
+    let mut remaining_dependencies = vec![0; system_count];
+    for dependent in dependents.iter().flatten() {
+        remaining_dependencies[*dependent] += 1;
+    }
+    let mut ready = order
+        .iter()
+        .copied()
+        .filter(|i| remaining_dependencies[*i] == 0)
+        .collect::<Vec<_>>();
+
+    let mut idle_systems = systems.iter_mut().map(Some).collect::<Vec<_>>();
+    let mut running_access = RunningAccess::default();
+    let (finished_sender, finished_receiver) = mpsc::channel();
+    rayon::in_place_scope(|scope| {
+        let mut running = 0;
+        let mut finished = 0;
+        loop {
+            for i in ready.drain(..) {
+                let system = idle_systems[i].take().unwrap();
+                running_access.start(i, system.name(), system.access());
+
+                let finished_sender = finished_sender.clone();
+                scope.spawn(move |_| {
+                    let result =
+                        panic::catch_unwind(AssertUnwindSafe(|| system.run(resource_bank)));
+                    finished_sender.send((i, result)).unwrap();
+                });
+                running += 1;
+            }
+
+            if running == 0 {
+                break;
+            }
+            let (i, result) = finished_receiver.recv().unwrap();
+            if let Err(panic) = result {
+                panic::resume_unwind(panic);
+            }
+            running -= 1;
+            finished += 1;
+            running_access.finish(i);
+
+            for &dependent in &dependents[i] {
+                remaining_dependencies[dependent] -= 1;
+                if remaining_dependencies[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+
+        // Only reachable with a cycle, which `Schedule::build` rejects.
+        if finished < system_count {
+            let stuck = (0..system_count)
+                .filter(|i| remaining_dependencies[*i] > 0)
+                .map(|i| idle_systems[i].as_ref().unwrap().name())
+                .collect::<Vec<_>>();
+            panic!("Deadlock: systems never became ready: {}", stuck.join(", "));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use voxei_macros::Resource;
+
+    use super::*;
+    use crate::engine::{
+        common::time::Time,
+        resource::{Res, ResMut},
+        schedule::{IntoSystemConfig, Schedule, Stage},
+    };
+
+    #[derive(Resource)]
+    struct A;
+
+    #[derive(Resource)]
+    struct B;
+
+    /// Counts how many systems overlap, each system holds its resources for a while so any that
+    /// can run together do.
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static MAX_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+    fn hold() {
+        let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+        MAX_RUNNING.fetch_max(running, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn max_running(schedule: &mut Schedule) -> usize {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(A);
+        resource_bank.insert(B);
+        resource_bank.insert(Time::new());
+
+        MAX_RUNNING.store(0, Ordering::SeqCst);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        pool.install(|| schedule.run(&mut resource_bank));
+        MAX_RUNNING.load(Ordering::SeqCst)
+    }
+
+    // The cases share the counters so they can't be separate tests.
+    #[test]
+    fn test_parallel_execution() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, |_: Res<A>| hold())
+            .add_system(Stage::Update, |_: Res<A>| hold())
+            .add_system(Stage::Update, |_: ResMut<B>| hold());
+        assert_eq!(max_running(&mut schedule), 3);
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, (|_: ResMut<A>| hold()).label("a"))
+            .add_system(Stage::Update, |_: Res<A>| hold())
+            .add_system(Stage::Update, |_: Res<B>| hold())
+            .add_system(Stage::Update, (|| hold()).after("a"));
+        assert_eq!(max_running(&mut schedule), 2);
+
+        schedule.set_executor(ExecutorKind::Serial);
+        assert_eq!(max_running(&mut schedule), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadlock")]
+    fn test_running_conflicts_panic() {
+        let mut write_a = SystemAccess::default();
+        write_a.add_write::<A>();
+        let mut read_a = SystemAccess::default();
+        read_a.add_read::<A>();
+
+        let mut running_access = RunningAccess::default();
+        running_access.start(0, "write_a", &write_a);
+        running_access.start(1, "read_a", &read_a);
+    }
+}
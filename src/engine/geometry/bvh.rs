@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+use nalgebra::Point3;
+
+use super::{ray::Ray, shapes::aabb::AABB};
+
+const MAX_LEAF_SIZE: usize = 2;
+
+/// Laid out so the node list can be uploaded to the gpu as is.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    /// The left child index for interior nodes, the right child is always the next node. For
+    /// leaves it's the first index into `Bvh::indices`.
+    pub left_first: u32,
+    pub max: [f32; 3],
+    /// The # of primitives in the leaf, 0 for interior nodes.
+    pub count: u32,
+}
+
+impl BvhNode {
+    fn new_leaf(first: usize, count: usize) -> Self {
+        Self {
+            min: [0.0; 3],
+            left_first: first as u32,
+            max: [0.0; 3],
+            count: count as u32,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+
+    pub fn aabb(&self) -> AABB {
+        AABB::new_min_max(self.min.into(), self.max.into())
+    }
+}
+
+/// A bounding volume hierarchy over a list of AABBs, split at the median of the longest axis.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn build(aabbs: &[AABB]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: (0..aabbs.len() as u32).collect(),
+        };
+        if aabbs.is_empty() {
+            return bvh;
+        }
+
+        bvh.nodes.push(BvhNode::new_leaf(0, aabbs.len()));
+        bvh.subdivide(0, aabbs);
+
+        bvh
+    }
+
+    fn subdivide(&mut self, node_index: usize, aabbs: &[AABB]) {
+        let first = self.nodes[node_index].left_first as usize;
+        let count = self.nodes[node_index].count as usize;
+        let primitives = first..(first + count);
+
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        let mut centroid_min = min;
+        let mut centroid_max = max;
+        for i in &self.indices[primitives.clone()] {
+            let aabb = &aabbs[*i as usize];
+            min = min.inf(&aabb.min());
+            max = max.sup(&aabb.max());
+            centroid_min = centroid_min.inf(&aabb.center());
+            centroid_max = centroid_max.sup(&aabb.center());
+        }
+        let node = &mut self.nodes[node_index];
+        node.min = min.into();
+        node.max = max.into();
+
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let axis = (centroid_max - centroid_min).imax();
+        self.indices[primitives].sort_by(|a, b| {
+            let a = aabbs[*a as usize].center()[axis];
+            let b = aabbs[*b as usize].center()[axis];
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        });
+
+        let left_count = count / 2;
+        let left_index = self.nodes.len();
+        self.nodes.push(BvhNode::new_leaf(first, left_count));
+        self.nodes
+            .push(BvhNode::new_leaf(first + left_count, count - left_count));
+        self.nodes[node_index].left_first = left_index as u32;
+        self.nodes[node_index].count = 0;
+
+        self.subdivide(left_index, aabbs);
+        self.subdivide(left_index + 1, aabbs);
+    }
+
+    pub fn nodes(&self) -> &[BvhNode] {
+        &self.nodes
+    }
+
+    /// The primitive indices ordered so each leaf covers a contiguous range.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Returns the primitives in every leaf the ray passes through.
+    pub fn ray_candidates(&self, ray: &Ray) -> Vec<u32> {
+        self.candidates(|node| ray.intersect_aabb(&node.aabb()).is_some())
+    }
+
+    /// Returns the primitives in every leaf overlapping the AABB.
+    pub fn aabb_candidates(&self, aabb: &AABB) -> Vec<u32> {
+        let min = aabb.min();
+        let max = aabb.max();
+        self.candidates(|node| (0..3).all(|i| node.min[i] <= max[i] && node.max[i] >= min[i]))
+    }
+
+    fn candidates(&self, mut overlaps: impl FnMut(&BvhNode) -> bool) -> Vec<u32> {
+        let mut candidates = Vec::new();
+        if self.nodes.is_empty() {
+            return candidates;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !overlaps(node) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let first = node.left_first as usize;
+                candidates.extend_from_slice(&self.indices[first..(first + node.count as usize)]);
+            } else {
+                stack.push(node.left_first as usize);
+                stack.push(node.left_first as usize + 1);
+            }
+        }
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+
+    fn unit_aabb(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new_min_max(Point3::new(x, y, z), Point3::new(x + 1.0, y + 1.0, z + 1.0))
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray::new(Point3::origin(), Vector3::x());
+
+        assert!(bvh.nodes().is_empty());
+        assert!(bvh.ray_candidates(&ray).is_empty());
+    }
+
+    #[test]
+    fn test_root_bounds_everything() {
+        let aabbs: Vec<AABB> = (0..9)
+            .map(|i| unit_aabb(i as f32 * 3.0, 0.0, 0.0))
+            .collect();
+        let bvh = Bvh::build(&aabbs);
+
+        let root = bvh.nodes()[0];
+        assert_eq!(root.min, [0.0, 0.0, 0.0]);
+        assert_eq!(root.max, [25.0, 1.0, 1.0]);
+
+        let mut leaf_primitives: Vec<u32> = bvh
+            .nodes()
+            .iter()
+            .filter(|node| node.is_leaf())
+            .flat_map(|node| {
+                let first = node.left_first as usize;
+                bvh.indices()[first..(first + node.count as usize)].to_vec()
+            })
+            .collect();
+        leaf_primitives.sort();
+        assert_eq!(leaf_primitives, (0..9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ray_candidates() {
+        let aabbs: Vec<AABB> = (0..16)
+            .map(|i| unit_aabb((i % 4) as f32 * 4.0, (i / 4) as f32 * 4.0, 0.0))
+            .collect();
+        let bvh = Bvh::build(&aabbs);
+
+        // Along the x axis through the second row.
+        let ray = Ray::new(Point3::new(-1.0, 4.5, 0.5), Vector3::x());
+        let mut candidates = bvh.ray_candidates(&ray);
+        assert!(candidates.len() < aabbs.len());
+        candidates.retain(|i| ray.intersect_aabb(&aabbs[*i as usize]).is_some());
+        candidates.sort();
+        assert_eq!(candidates, vec![4, 5, 6, 7]);
+
+        let ray = Ray::new(Point3::new(-1.0, 2.5, 2.5), Vector3::x());
+        assert!(bvh.ray_candidates(&ray).is_empty());
+    }
+
+    #[test]
+    fn test_aabb_candidates() {
+        let aabbs: Vec<AABB> = (0..8)
+            .map(|i| unit_aabb(i as f32 * 2.0, 0.0, 0.0))
+            .collect();
+        let bvh = Bvh::build(&aabbs);
+
+        let query = AABB::new_min_max(Point3::new(3.5, 0.0, 0.0), Point3::new(6.5, 1.0, 1.0));
+        let mut candidates = bvh.aabb_candidates(&query);
+        candidates.sort();
+        assert_eq!(candidates, vec![2, 3]);
+    }
+}
@@ -1,2 +1,3 @@
+pub mod bvh;
 pub mod ray;
 pub mod shapes;
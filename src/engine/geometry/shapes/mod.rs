@@ -1,6 +1,7 @@
 use nalgebra::Vector3;
 
 pub mod aabb;
+pub mod oriented_box;
 pub mod triangle;
 
 pub type Vertex = Vector3<f32>;
@@ -29,6 +30,13 @@ impl Face {
         }
         axes
     }
+
+    /// The direction of each edge going around the face.
+    pub fn edge_directions(&self) -> Vec<Vector3<f32>> {
+        (0..self.vertices.len())
+            .map(|i| self.vertices[(i + 1) % self.vertices.len()] - self.vertices[i])
+            .collect()
+    }
 }
 
 pub struct Projection {
@@ -40,6 +48,19 @@ impl Projection {
     pub fn overlap(&self, other: &Projection) -> bool {
         self.min <= other.max && self.max >= other.min
     }
+
+    /// How far the projections overlap, negative if they're apart.
+    pub fn overlap_depth(&self, other: &Projection) -> f32 {
+        self.max.min(other.max) - self.min.max(other.min)
+    }
+}
+
+/// The smallest translation that separates two overlapping shapes.
+#[derive(Debug, Clone, Copy)]
+pub struct Penetration {
+    /// Points from the first shape towards the second.
+    pub normal: Vector3<f32>,
+    pub depth: f32,
 }
 
 pub trait Shape {
@@ -78,6 +99,59 @@ pub trait Shape {
 
         return true;
     }
+
+    fn center(&self) -> Vertex {
+        let vertices = self.collect_vertices();
+        vertices.iter().sum::<Vertex>() / vertices.len() as f32
+    }
+
+    /// Finds the axis of least overlap with the separating axis theorem, None if the shapes are
+    /// apart or only touching. Unlike `test_intersection` this also tests the cross products of
+    /// every edge pair so boxes meeting edge to edge aren't reported as overlapping.
+    fn penetration(&self, other: &dyn Shape) -> Option<Penetration> {
+        let faces = self.collect_faces();
+        let other_faces = other.collect_faces();
+
+        let mut axes = faces
+            .iter()
+            .chain(other_faces.iter())
+            .flat_map(|face| face.calculate_sat_axes())
+            .collect::<Vec<_>>();
+        let other_edges = other_faces
+            .iter()
+            .flat_map(|face| face.edge_directions())
+            .collect::<Vec<_>>();
+        for edge in faces.iter().flat_map(|face| face.edge_directions()) {
+            for other_edge in &other_edges {
+                let axis = edge.cross(other_edge);
+                if axis.norm_squared() > 1.0e-8 {
+                    axes.push(axis.normalize());
+                }
+            }
+        }
+
+        let mut penetration: Option<Penetration> = None;
+        for axis in axes {
+            let depth = self.project(&axis).overlap_depth(&other.project(&axis));
+            if depth <= 0.0 {
+                return None;
+            }
+
+            if !penetration.is_some_and(|p| depth >= p.depth) {
+                penetration = Some(Penetration {
+                    normal: axis,
+                    depth,
+                });
+            }
+        }
+
+        penetration.map(|mut p| {
+            if p.normal.dot(&(other.center() - self.center())) < 0.0 {
+                p.normal = -p.normal;
+            }
+            p
+        })
+    }
 }
 
 #[cfg(test)]
@@ -130,4 +204,45 @@ mod tests {
 
         assert!(aabb.test_intersection(&t));
     }
+
+    #[test]
+    fn test_aabb_penetration() {
+        let aabb1 = aabb::AABB::new_center_half_extent(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        let aabb2 = aabb::AABB::new_center_half_extent(
+            Point3::new(1.5, 0.2, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        let penetration = aabb1.penetration(&aabb2).unwrap();
+        assert!((penetration.depth - 0.5).abs() < 0.0001);
+        assert!((penetration.normal - Vector3::x()).norm() < 0.0001);
+
+        let aabb3 = aabb::AABB::new_center_half_extent(
+            Point3::new(2.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        assert!(aabb1.penetration(&aabb3).is_none());
+    }
+
+    #[test]
+    fn test_oriented_box_edge_separation() {
+        // Rotated so their edges cross with a small gap, only the edge cross product axis
+        // separates them.
+        let box1 = oriented_box::OrientedBox::new(
+            nalgebra::Isometry3::new(Vector3::zeros(), Vector3::z() * std::f32::consts::FRAC_PI_4),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        let box2 = oriented_box::OrientedBox::new(
+            nalgebra::Isometry3::new(
+                Vector3::new(2.0 * std::f32::consts::SQRT_2 + 0.1, 0.0, 0.0),
+                Vector3::y() * std::f32::consts::FRAC_PI_4,
+            ),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+
+        assert!(box1.test_intersection(&box2));
+        assert!(box1.penetration(&box2).is_none());
+    }
 }
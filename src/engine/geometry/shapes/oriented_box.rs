@@ -0,0 +1,68 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+
+use super::{aabb::AABB, Face, Shape, Vertex};
+
+/// A box rotated by its isometry, the isometry's translation is the box's center.
+#[derive(Debug, Clone, Copy)]
+pub struct OrientedBox {
+    pub isometry: Isometry3<f32>,
+    pub half_extents: Vector3<f32>,
+}
+
+impl OrientedBox {
+    pub fn new(isometry: Isometry3<f32>, half_extents: Vector3<f32>) -> Self {
+        Self {
+            isometry,
+            half_extents,
+        }
+    }
+
+    /// The corners in world space, bit 0 of the index picks +x, bit 1 +y and bit 2 +z.
+    fn corners(&self) -> [Vertex; 8] {
+        std::array::from_fn(|i| {
+            let local_corner = Point3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            let local_corner = Point3::from(local_corner.coords.component_mul(&self.half_extents));
+            (self.isometry * local_corner).coords
+        })
+    }
+
+    /// The world AABB enclosing the box.
+    pub fn aabb(&self) -> AABB {
+        let corners = self.corners();
+        let min = corners.iter().fold(corners[0], |min, c| min.inf(c));
+        let max = corners.iter().fold(corners[0], |max, c| max.sup(c));
+        AABB::new_min_max(min.into(), max.into())
+    }
+}
+
+impl Shape for OrientedBox {
+    fn collect_vertices(&self) -> Vec<Vertex> {
+        self.corners().to_vec()
+    }
+
+    fn collect_faces(&self) -> Vec<Face> {
+        let c = self.corners();
+        vec![
+            // Bottom
+            Face::new(vec![c[0], c[1], c[5], c[4]]),
+            // Top
+            Face::new(vec![c[2], c[3], c[7], c[6]]),
+            // Front
+            Face::new(vec![c[0], c[1], c[3], c[2]]),
+            // Back
+            Face::new(vec![c[4], c[5], c[7], c[6]]),
+            // Left
+            Face::new(vec![c[0], c[4], c[6], c[2]]),
+            // Right
+            Face::new(vec![c[1], c[5], c[7], c[3]]),
+        ]
+    }
+
+    fn center(&self) -> Vertex {
+        self.isometry.translation.vector
+    }
+}
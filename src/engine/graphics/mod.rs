@@ -2,5 +2,6 @@ pub mod common;
 pub mod device;
 pub mod pass;
 pub mod pipeline_manager;
+pub mod plugin;
 pub mod render_manager;
 pub mod swapchain;
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use paya::{
+    allocator::MemoryLocation,
+    command_recorder::{CommandRecorder, CopyRegion, LoadOp, RenderingAttachment, RenderingInfo},
+    common::{
+        AccessFlags, BlendMode, BufferTransition, BufferUsageFlags, Extent2D, Format, ImageLayout,
+        ImageTransition, PolygonMode, Topology,
+    },
+    device::Device,
+    gpu_resources::{BufferId, BufferInfo, ImageId, PackedGpuResourceId},
+    pipeline::{RasterPipeline, RasterPipelineInfo},
+    shader::ShaderInfo,
+};
+use voxei_macros::Resource;
+
+use crate::engine::{
+    assets::{
+        asset::Assets,
+        watched_shaders::{ShaderDependencySignal, WatchedShaders},
+    },
+    graphics::device::{create_device_buffer, stage_buffer_copy, DeviceResource},
+    resource::{Res, ResMut},
+    ui::egui::Egui,
+};
+
+const VERTEX_SHADER_NAME: &str = "egui_vertex";
+const VERTEX_PATH: &str = "shaders/egui.vert.glsl";
+const FRAGMENT_SHADER_NAME: &str = "egui_fragment";
+const FRAGMENT_PATH: &str = "shaders/egui.frag.glsl";
+
+/// The vertices are read as floats in the shader: position, uv, then the color's bits.
+const VERTEX_FLOATS: usize = 5;
+
+#[repr(C)]
+struct EguiPushConstants {
+    vertex_buffer: PackedGpuResourceId,
+    index_buffer: PackedGpuResourceId,
+    texture_buffer: PackedGpuResourceId,
+    first_index: u32,
+    vertex_offset: u32,
+    texture_width: u32,
+    texture_height: u32,
+    /// 1 to filter the texture linearly, 0 for nearest.
+    texture_linear: u32,
+    /// The screen's size in points, egui's coordinates.
+    screen_width: f32,
+    screen_height: f32,
+}
+
+/// An egui texture as a storage buffer of premultiplied sRGBA pixels, sampled by the fragment
+/// shader.
+struct EguiTexture {
+    buffer: BufferId,
+    width: u32,
+    height: u32,
+    options: egui::TextureOptions,
+}
+
+/// Draws egui's meshes over the backbuffer and keeps its textures, the font atlas and any loaded
+/// with `egui::Context::load_texture`, on the GPU.
+#[derive(Resource)]
+pub struct EguiPass {
+    raster_pipeline: Option<RasterPipeline>,
+    shader_signal: ShaderDependencySignal,
+    textures: HashMap<egui::TextureId, EguiTexture>,
+}
+
+impl EguiPass {
+    pub fn new(assets: &mut Assets, watched_shaders: &mut WatchedShaders) -> Self {
+        let shader_signal = watched_shaders.create_dependency_signal();
+        watched_shaders.load_shader(assets, VERTEX_PATH, VERTEX_SHADER_NAME, &shader_signal);
+        watched_shaders.load_shader(assets, FRAGMENT_PATH, FRAGMENT_SHADER_NAME, &shader_signal);
+
+        Self {
+            raster_pipeline: None,
+            shader_signal,
+            textures: HashMap::new(),
+        }
+    }
+
+    pub fn update(
+        mut egui_pass: ResMut<EguiPass>,
+        device: Res<DeviceResource>,
+        watched_shaders: Res<WatchedShaders>,
+    ) {
+        if !watched_shaders.is_dependency_signaled(&egui_pass.shader_signal) {
+            return;
+        }
+
+        let vertex_shader = watched_shaders.get_shader(VERTEX_SHADER_NAME).unwrap();
+        let fragment_shader = watched_shaders.get_shader(FRAGMENT_SHADER_NAME).unwrap();
+        egui_pass.raster_pipeline = Some(device.create_raster_pipeline(RasterPipelineInfo {
+            vertex_shader: ShaderInfo {
+                byte_code: vertex_shader,
+                entry_point: "main".to_string(),
+            },
+            fragment_shader: ShaderInfo {
+                byte_code: fragment_shader,
+                entry_point: "main".to_string(),
+            },
+            push_constant_size: std::mem::size_of::<EguiPushConstants>() as u32,
+            // Vertices are pulled from the vertex buffer by index.
+            vertex_attributes: Vec::new(),
+            polygon_mode: PolygonMode::Fill,
+            color_attachments: vec![Format::R8G8B8A8Unorm],
+            topology: Topology::TriangleList,
+            primitive_restart_enable: false,
+            line_width: 1.0,
+            blend_mode: BlendMode::PremultipliedAlpha,
+        }));
+    }
+
+    /// Uploads egui's texture changes and draws its last frame over the backbuffer, which is left
+    /// in `ImageLayout::ColorAttachmentOptimal`.
+    pub fn record_commands(
+        &mut self,
+        device: &mut Device,
+        command_recorder: &mut CommandRecorder,
+        egui: &mut Egui,
+        backbuffer_image: ImageId,
+        backbuffer_extent: Extent2D,
+        backbuffer_src_layout: ImageLayout,
+        backbuffer_src_access: AccessFlags,
+    ) {
+        let textures_delta = egui.take_textures_delta();
+        for (texture_id, image_delta) in &textures_delta.set {
+            self.upload_texture(device, command_recorder, *texture_id, image_delta);
+        }
+
+        command_recorder.pipeline_barrier_image_transition(
+            device,
+            ImageTransition {
+                image: backbuffer_image,
+                src_layout: backbuffer_src_layout,
+                src_access: backbuffer_src_access,
+                dst_layout: ImageLayout::ColorAttachmentOptimal,
+                dst_access: AccessFlags::COLOR_ATTACHMENT_READ
+                    | AccessFlags::COLOR_ATTACHMENT_WRITE,
+            },
+        );
+
+        if let Some(pipeline) = &self.raster_pipeline {
+            Self::record_draw_commands(
+                device,
+                command_recorder,
+                pipeline,
+                &self.textures,
+                egui,
+                backbuffer_image,
+                backbuffer_extent,
+            );
+        }
+
+        // Freed textures may still be used by the frame just recorded.
+        for texture_id in &textures_delta.free {
+            if let Some(texture) = self.textures.remove(texture_id) {
+                command_recorder.destroy_buffer_deferred(texture.buffer);
+            }
+        }
+    }
+
+    fn upload_texture(
+        &mut self,
+        device: &mut Device,
+        command_recorder: &mut CommandRecorder,
+        texture_id: egui::TextureId,
+        image_delta: &egui::epaint::ImageDelta,
+    ) {
+        let pixels = match &image_delta.image {
+            egui::ImageData::Color(image) => image
+                .pixels
+                .iter()
+                .map(|color| u32::from_le_bytes(color.to_array()))
+                .collect::<Vec<_>>(),
+            egui::ImageData::Font(image) => image
+                .srgba_pixels(None)
+                .map(|color| u32::from_le_bytes(color.to_array()))
+                .collect::<Vec<_>>(),
+        };
+        let [width, height] = image_delta.image.size().map(|size| size as u32);
+
+        let Some([x, y]) = image_delta.pos else {
+            // A whole new image, which can change the texture's size.
+            if let Some(texture) = self.textures.remove(&texture_id) {
+                command_recorder.destroy_buffer_deferred(texture.buffer);
+            }
+            let buffer = create_device_buffer(
+                device,
+                format!("egui_texture_{:?}", texture_id),
+                pixels.len() as u64 * std::mem::size_of::<u32>() as u64,
+            );
+            stage_buffer_copy::<u32>(
+                device,
+                command_recorder,
+                buffer,
+                AccessFlags::SHADER_READ,
+                |ptr| unsafe { ptr.copy_from_nonoverlapping(pixels.as_ptr(), pixels.len()) },
+            );
+            self.textures.insert(
+                texture_id,
+                EguiTexture {
+                    buffer,
+                    width,
+                    height,
+                    options: image_delta.options,
+                },
+            );
+            return;
+        };
+
+        let texture = self
+            .textures
+            .get(&texture_id)
+            .unwrap_or_else(|| panic!("egui updated texture {:?} before creating it", texture_id));
+        let pixel_size = std::mem::size_of::<u32>() as u64;
+        let staging_buffer = device.create_buffer(BufferInfo {
+            name: format!("egui_texture_{:?}_staging_buffer", texture_id),
+            size: pixels.len() as u64 * pixel_size,
+            memory_location: MemoryLocation::CpuToGpu,
+            usage: BufferUsageFlags::TRANSFER_SRC,
+        });
+        let ptr = device.map_buffer_typed::<u32>(staging_buffer);
+        unsafe { ptr.copy_from_nonoverlapping(pixels.as_ptr(), pixels.len()) };
+
+        // One copy per row since the rows aren't contiguous in the texture.
+        let copies = (0..height as u64)
+            .map(|row| CopyRegion {
+                src_offset: row * width as u64 * pixel_size,
+                dst_offset: ((y as u64 + row) * texture.width as u64 + x as u64) * pixel_size,
+                size: width as u64 * pixel_size,
+            })
+            .collect::<Vec<_>>();
+        // The last frame's draw may still be reading the texture.
+        command_recorder.pipeline_barrier_buffer_transition(
+            device,
+            BufferTransition {
+                buffer: texture.buffer,
+                src_access: AccessFlags::SHADER_READ,
+                dst_access: AccessFlags::TRANSFER_WRITE,
+            },
+        );
+        command_recorder.copy_buffer_to_buffer_multiple(
+            device,
+            staging_buffer,
+            texture.buffer,
+            copies,
+        );
+        command_recorder.destroy_buffer_deferred(staging_buffer);
+
+        command_recorder.pipeline_barrier_buffer_transition(
+            device,
+            BufferTransition {
+                buffer: texture.buffer,
+                src_access: AccessFlags::TRANSFER_WRITE,
+                dst_access: AccessFlags::SHADER_READ,
+            },
+        );
+    }
+
+    fn record_draw_commands(
+        device: &mut Device,
+        command_recorder: &mut CommandRecorder,
+        pipeline: &RasterPipeline,
+        textures: &HashMap<egui::TextureId, EguiTexture>,
+        egui: &Egui,
+        backbuffer_image: ImageId,
+        backbuffer_extent: Extent2D,
+    ) {
+        let meshes = egui
+            .primitives()
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                egui::epaint::Primitive::Mesh(mesh) if !mesh.indices.is_empty() => {
+                    Some((primitive.clip_rect, mesh))
+                }
+                // There aren't any paint callbacks in this engine.
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if meshes.is_empty() {
+            return;
+        }
+
+        let vertex_count = meshes
+            .iter()
+            .map(|(_, mesh)| mesh.vertices.len())
+            .sum::<usize>();
+        let index_count = meshes
+            .iter()
+            .map(|(_, mesh)| mesh.indices.len())
+            .sum::<usize>();
+        let vertex_buffer = Self::create_frame_buffer(
+            device,
+            "egui_vertex_buffer",
+            vertex_count * VERTEX_FLOATS * std::mem::size_of::<f32>(),
+        );
+        let index_buffer = Self::create_frame_buffer(
+            device,
+            "egui_index_buffer",
+            index_count * std::mem::size_of::<u32>(),
+        );
+
+        let vertex_ptr = device.map_buffer_typed::<f32>(vertex_buffer);
+        let index_ptr = device.map_buffer_typed::<u32>(index_buffer);
+        let mut vertex_offset = 0;
+        let mut index_offset = 0;
+        let mut draws = Vec::new();
+        for (clip_rect, mesh) in meshes {
+            for (i, vertex) in mesh.vertices.iter().enumerate() {
+                let floats = [
+                    vertex.pos.x,
+                    vertex.pos.y,
+                    vertex.uv.x,
+                    vertex.uv.y,
+                    f32::from_bits(u32::from_le_bytes(vertex.color.to_array())),
+                ];
+                unsafe {
+                    vertex_ptr
+                        .add((vertex_offset + i) * VERTEX_FLOATS)
+                        .copy_from_nonoverlapping(floats.as_ptr(), VERTEX_FLOATS);
+                }
+            }
+            unsafe {
+                index_ptr
+                    .add(index_offset)
+                    .copy_from_nonoverlapping(mesh.indices.as_ptr(), mesh.indices.len());
+            }
+
+            draws.push((clip_rect, mesh, vertex_offset, index_offset));
+            vertex_offset += mesh.vertices.len();
+            index_offset += mesh.indices.len();
+        }
+
+        let pixels_per_point = egui.pixels_per_point();
+        command_recorder.begin_rendering(
+            device,
+            RenderingInfo {
+                color_attachments: vec![RenderingAttachment {
+                    image: backbuffer_image,
+                    load_op: LoadOp::Load,
+                }],
+                extent: backbuffer_extent,
+            },
+        );
+        command_recorder.bind_raster_pipeline(device, pipeline);
+
+        for (clip_rect, mesh, vertex_offset, index_offset) in draws {
+            let Some(texture) = textures.get(&mesh.texture_id) else {
+                continue;
+            };
+
+            // The clip rect is in points, the scissor in pixels clamped to the backbuffer.
+            let min_x = (clip_rect.min.x * pixels_per_point).round().max(0.0) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().max(0.0) as u32;
+            let max_x =
+                ((clip_rect.max.x * pixels_per_point).round() as u32).min(backbuffer_extent.width);
+            let max_y =
+                ((clip_rect.max.y * pixels_per_point).round() as u32).min(backbuffer_extent.height);
+            if max_x <= min_x || max_y <= min_y {
+                continue;
+            }
+            command_recorder.set_scissor(device, min_x, min_y, max_x - min_x, max_y - min_y);
+
+            command_recorder.upload_push_constants(
+                device,
+                pipeline,
+                &EguiPushConstants {
+                    vertex_buffer: vertex_buffer.pack(),
+                    index_buffer: index_buffer.pack(),
+                    texture_buffer: texture.buffer.pack(),
+                    first_index: index_offset as u32,
+                    vertex_offset: vertex_offset as u32,
+                    texture_width: texture.width,
+                    texture_height: texture.height,
+                    texture_linear: (texture.options.magnification == egui::TextureFilter::Linear)
+                        as u32,
+                    screen_width: backbuffer_extent.width as f32 / pixels_per_point,
+                    screen_height: backbuffer_extent.height as f32 / pixels_per_point,
+                },
+            );
+            command_recorder.draw(device, mesh.indices.len() as u32, 1, 0, 0);
+        }
+
+        command_recorder.end_rendering(device);
+
+        command_recorder.destroy_buffer_deferred(vertex_buffer);
+        command_recorder.destroy_buffer_deferred(index_buffer);
+    }
+
+    /// A buffer the shaders read straight from host memory, for data rewritten every frame.
+    fn create_frame_buffer(device: &mut Device, name: &str, size: usize) -> BufferId {
+        device.create_buffer(BufferInfo {
+            name: name.to_owned(),
+            size: size as u64,
+            memory_location: MemoryLocation::CpuToGpu,
+            usage: BufferUsageFlags::STORAGE,
+        })
+    }
+}
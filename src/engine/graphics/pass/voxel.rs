@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     time::Instant,
 };
 
@@ -23,6 +23,7 @@ use crate::{
             asset::Assets,
             watched_shaders::{ShaderDependencySignal, WatchedShaders},
         },
+        geometry::bvh::{Bvh, BvhNode},
         graphics::{
             device::{
                 create_device_buffer, create_device_buffer_typed, stage_buffer_copy, DeviceResource,
@@ -32,11 +33,13 @@ use crate::{
         resource::{Res, ResMut},
         voxel::{
             dynamic_world::{
-                BrickChange, BrickData, BrickIndex, BrickPalette, DynVoxelWorld,
-                PackedVoxelMaterial, SpatialStatus,
+                BrickChange, BrickData, BrickIndex, BrickNormalUpdate, BrickPalette, ChunkIndex,
+                DynVoxelWorld, DynWorldResize, PackedVoxelMaterial, SpatialStatus,
+                PALETTE_SIZE_CLASSES,
             },
+            object::{VoxelObjectInstance, VoxelObjects},
             util::Morton,
-            vox_constants::BRICK_VOLUME,
+            vox_constants::{BRICK_VOLUME, CHUNK_VOLUME},
             vox_world::{DynBrickPos, VoxelWorld},
         },
     },
@@ -46,6 +49,11 @@ use crate::{
 const RAY_MARCH_PATH: &str = "shaders/ray_march.comp.glsl";
 const NORMAL_CALC_PATH: &str = "shaders/normal_calc.comp.glsl";
 
+/// The brick index block holding the brick slot, see `BrickIndexPool::block_slot_min`.
+fn brick_block_index(brick_slot: u64) -> u32 {
+    (brick_slot / CHUNK_VOLUME as u64) as u32
+}
+
 #[repr(C)]
 struct WorldInfo {
     chunk_center: Vector3<i32>,
@@ -53,8 +61,8 @@ struct WorldInfo {
     dyn_chunk_translation: Vector3<u32>,
 
     super_chunk_bit_mask_buffer: PackedGpuResourceId,
-    chunk_occupancy_mask_buffer: PackedGpuResourceId,
-    brick_indices_grid_buffer: PackedGpuResourceId,
+    chunk_indices_grid_buffer: PackedGpuResourceId,
+    brick_index_pool_buffer: PackedGpuResourceId,
     brick_data_buffer: PackedGpuResourceId,
     brick_palette_list_buffer: PackedGpuResourceId,
     brick_palette_indices_buffer: PackedGpuResourceId,
@@ -62,6 +70,18 @@ struct WorldInfo {
 
     dyn_chunk_side_length: u32,
     dyn_chunk_half_length: u32,
+
+    voxel_object_list_buffer: PackedGpuResourceId,
+    voxel_object_bvh_buffer: PackedGpuResourceId,
+    voxel_object_count: u32,
+}
+
+#[repr(C)]
+struct GpuVoxelObject {
+    /// Column major.
+    world_to_object: [f32; 16],
+    block_index: u32,
+    _padding: [u32; 3],
 }
 
 #[repr(C)]
@@ -102,8 +122,8 @@ pub struct VoxelPipeline {
 
     voxel_world_info_buffer: BufferId,
     super_chunk_occupancy_grid_buffer: BufferId,
-    chunk_occupancy_grid_buffer: BufferId,
-    brick_indices_grid_buffer: BufferId,
+    chunk_indices_grid_buffer: BufferId,
+    brick_index_pool_buffer: BufferId,
     brick_data_buffer: BufferId,
     brick_palette_data_buffer: BufferId,
     brick_palette_indices_buffer: BufferId,
@@ -111,11 +131,23 @@ pub struct VoxelPipeline {
     brick_normal_process_list_buffer: BufferId,
     current_frame_brick_process_count: u32,
 
+    voxel_object_list_buffer: BufferId,
+    voxel_object_bvh_buffer: BufferId,
+
     brick_request_staging_buffers: Vec<BufferId>,
     brick_request_list_buffer: BufferId,
 
     queued_brick_updates: VecDeque<BrickChange>,
-    queued_brick_normal_updates: VecDeque<BrickChange>,
+    queued_brick_normal_updates: VecDeque<BrickNormalUpdate>,
+    /// The # of queued brick updates in each brick index block.
+    queued_block_updates: HashMap<u32, u32>,
+    /// Blocks allocated since their brick updates were last all uploaded. Their chunks are
+    /// uploaded as loading until then so the gpu never traces the slots a block's previous chunk
+    /// left behind.
+    unsynced_blocks: HashSet<u32>,
+
+    /// Buffers replaced by a resize, destroyed once the next command recorder finishes.
+    retired_buffers: Vec<BufferId>,
 }
 
 impl VoxelPipeline {
@@ -131,10 +163,9 @@ impl VoxelPipeline {
             create_device_buffer_typed::<WorldInfo>(device, "voxel_world_info_buffer");
         let super_chunk_occupancy_grid_buffer =
             Self::create_super_chunk_occupancy_grid_buffer(device, vox_world.dyn_world());
-        let chunk_occupancy_grid_buffer =
-            Self::create_chunk_occupancy_grid_buffer(device, vox_world.dyn_world());
-        let brick_indices_grid_buffer =
-            Self::create_brick_indices_grid_buffer(device, vox_world.dyn_world());
+        let chunk_indices_grid_buffer =
+            Self::create_chunk_indices_grid_buffer(device, vox_world.dyn_world());
+        let brick_index_pool_buffer = Self::create_brick_index_pool_buffer(device, settings);
 
         let brick_data_buffer = Self::create_brick_data_buffer(device, settings);
         let brick_palette_data_buffer = Self::create_palette_data_buffer(device, settings);
@@ -146,6 +177,8 @@ impl VoxelPipeline {
         let brick_request_list_buffer = Self::create_brick_request_list_buffer(device, settings);
         let brick_normal_process_list_buffer =
             Self::create_brick_normal_process_list_buffer(device, settings);
+        let voxel_object_list_buffer = Self::create_voxel_object_list_buffer(device, settings);
+        let voxel_object_bvh_buffer = Self::create_voxel_object_bvh_buffer(device, settings);
 
         Self {
             ray_march_pipeline: pipeline_manager.create_compute_pipeline::<RayMarchPushConstants>(
@@ -162,43 +195,98 @@ impl VoxelPipeline {
 
             voxel_world_info_buffer,
             super_chunk_occupancy_grid_buffer,
-            chunk_occupancy_grid_buffer,
-            brick_indices_grid_buffer,
+            chunk_indices_grid_buffer,
+            brick_index_pool_buffer,
             brick_data_buffer,
             brick_palette_data_buffer,
             brick_palette_indices_buffer,
             brick_normal_process_list_buffer,
             current_frame_brick_process_count: 0,
 
+            voxel_object_list_buffer,
+            voxel_object_bvh_buffer,
+
             brick_request_staging_buffers,
             brick_request_list_buffer,
 
             queued_brick_updates: VecDeque::new(),
             queued_brick_normal_updates: VecDeque::new(),
+            queued_block_updates: HashMap::new(),
+            unsynced_blocks: HashSet::new(),
+
+            retired_buffers: Vec::new(),
         }
     }
 
     pub fn update_world_changes(
         mut vox_world: ResMut<VoxelWorld>,
         mut vox_pipeline: ResMut<VoxelPipeline>,
+        mut device: ResMut<DeviceResource>,
     ) {
+        let resizes = vox_world.dyn_world_mut().collect_resizes();
+        if !resizes.is_empty() {
+            vox_pipeline.resize_world_buffers(&mut device, vox_world.dyn_world(), &resizes);
+        }
+
+        let vox_pipeline = &mut *vox_pipeline;
         vox_pipeline
-            .queued_brick_updates
-            .extend(vox_world.dyn_world_mut().collect_brick_changes());
+            .unsynced_blocks
+            .extend(vox_world.dyn_world_mut().collect_allocated_blocks());
+        for brick_change in vox_world.dyn_world_mut().collect_brick_changes() {
+            *vox_pipeline
+                .queued_block_updates
+                .entry(brick_block_index(brick_change.brick_index))
+                .or_default() += 1;
+            vox_pipeline.queued_brick_updates.push_back(brick_change);
+        }
         vox_pipeline
             .queued_brick_normal_updates
             .extend(vox_world.dyn_world_mut().collect_brick_normal_updates());
     }
 
+    /// Recreates the grid buffers that scale with the render distance and moves the queued brick
+    /// normal updates to the new layout, the brick index pool doesn't move so it is kept.
+    fn resize_world_buffers(
+        &mut self,
+        device: &mut Device,
+        dyn_world: &DynVoxelWorld,
+        resizes: &[DynWorldResize],
+    ) {
+        self.retired_buffers.extend([
+            self.super_chunk_occupancy_grid_buffer,
+            self.chunk_indices_grid_buffer,
+        ]);
+        self.super_chunk_occupancy_grid_buffer =
+            Self::create_super_chunk_occupancy_grid_buffer(device, dyn_world);
+        self.chunk_indices_grid_buffer = Self::create_chunk_indices_grid_buffer(device, dyn_world);
+
+        for resize in resizes {
+            self.queued_brick_normal_updates = resize
+                .remap_brick_normal_updates(self.queued_brick_normal_updates.drain(..))
+                .into();
+        }
+    }
+
     pub fn record_copy_commands(
         &mut self,
         vox_world: &mut VoxelWorld,
+        voxel_objects: &VoxelObjects,
         device: &mut Device,
         command_recorder: &mut CommandRecorder,
         settings: &Settings,
     ) {
         let cpu_frame_index = device.cpu_frame_index();
 
+        for buffer in self.retired_buffers.drain(..) {
+            command_recorder.destroy_buffer_deferred(buffer);
+        }
+
+        let synced_voxel_objects = self.synced_voxel_objects(voxel_objects);
+        let (object_instances, object_bvh) = match &synced_voxel_objects {
+            Some((instances, bvh)) => (instances.as_slice(), bvh),
+            None => (voxel_objects.instances(), voxel_objects.bvh()),
+        };
+
         // Upload entire world info buffer
         stage_buffer_copy(
             device,
@@ -209,8 +297,8 @@ impl VoxelPipeline {
                 ptr.write(WorldInfo {
                     chunk_center: vox_world.chunk_center().vector,
                     super_chunk_bit_mask_buffer: self.super_chunk_occupancy_grid_buffer.pack(),
-                    chunk_occupancy_mask_buffer: self.chunk_occupancy_grid_buffer.pack(),
-                    brick_indices_grid_buffer: self.brick_indices_grid_buffer.pack(),
+                    chunk_indices_grid_buffer: self.chunk_indices_grid_buffer.pack(),
+                    brick_index_pool_buffer: self.brick_index_pool_buffer.pack(),
                     brick_data_buffer: self.brick_data_buffer.pack(),
                     brick_palette_list_buffer: self.brick_palette_data_buffer.pack(),
                     brick_palette_indices_buffer: self.brick_palette_indices_buffer.pack(),
@@ -219,13 +307,17 @@ impl VoxelPipeline {
                     dyn_chunk_side_length: vox_world
                         .dyn_world()
                         .chunk_render_distance()
-                        .pow2_side_length(),
+                        .side_length(),
                     dyn_chunk_half_length: vox_world
                         .dyn_world()
                         .chunk_render_distance()
-                        .pow2_half_side_length(),
+                        .half_side_length(),
                     dyn_chunk_translation: vox_world.dyn_world().chunk_translation(),
 
+                    voxel_object_list_buffer: self.voxel_object_list_buffer.pack(),
+                    voxel_object_bvh_buffer: self.voxel_object_bvh_buffer.pack(),
+                    voxel_object_count: object_instances.len() as u32,
+
                     _padding0: 0,
                 })
             },
@@ -242,16 +334,15 @@ impl VoxelPipeline {
                 ptr.copy_from_nonoverlapping(grid_slice.as_ptr(), grid_slice.len());
             },
         );
-        stage_buffer_copy(
-            device,
-            command_recorder,
-            self.chunk_occupancy_grid_buffer,
-            AccessFlags::SHADER_READ,
-            |ptr: *mut u8| unsafe {
-                let grid_slice = vox_world.dyn_world().chunk_bit_grid().as_slice();
-                ptr.copy_from_nonoverlapping(grid_slice.as_ptr(), grid_slice.len());
-            },
-        );
+
+        if !object_instances.is_empty() {
+            self.record_voxel_object_copy_commands(
+                object_instances,
+                object_bvh,
+                device,
+                command_recorder,
+            );
+        }
 
         let brick_change_upload_size =
             self.queued_brick_updates
@@ -273,7 +364,7 @@ impl VoxelPipeline {
             let brick_palette_staging_buffer = device.create_buffer(BufferInfo {
                 name: format!("brick_material_data_staging_buffer_{}", cpu_frame_index).to_owned(),
                 size: std::mem::size_of::<PackedVoxelMaterial>() as u64
-                    * 256
+                    * *PALETTE_SIZE_CLASSES.last().unwrap() as u64
                     * brick_change_upload_size,
                 memory_location: MemoryLocation::CpuToGpu,
                 usage: BufferUsageFlags::TRANSFER_SRC,
@@ -312,9 +403,34 @@ impl VoxelPipeline {
                 (self.queued_brick_updates.len() as isize - brick_change_upload_size as isize)
                     .max(0) as usize..,
             ) {
-                let brick_morton = *brick_update.brick_morton;
-                let brick_index =
-                    vox_world.dyn_world().brick_indices_grid().as_slice()[brick_morton as usize];
+                let brick_slot = brick_update.brick_index;
+                let block_index = brick_block_index(brick_slot);
+                let queued_updates = self.queued_block_updates.get_mut(&block_index).unwrap();
+                *queued_updates -= 1;
+                if *queued_updates == 0 {
+                    self.queued_block_updates.remove(&block_index);
+                    self.unsynced_blocks.remove(&block_index);
+                }
+
+                if brick_slot >= settings.brick_index_block_max_size as u64 * CHUNK_VOLUME as u64 {
+                    vox_world.dyn_world_mut().overflow_mut().gpu_brick_indices += 1;
+                    continue;
+                }
+                let mut brick_index = vox_world.dyn_world().brick_index_pool().get(brick_slot);
+                if brick_index.status() == SpatialStatus::Loaded {
+                    // Checked before anything is staged so a brick is either uploaded whole or
+                    // left empty.
+                    let brick_data = vox_world.dyn_world().brick_data().get(brick_index.index());
+                    let brick_palette_end = brick_data.palette_index() + brick_data.palette_size();
+                    let overflow = vox_world.dyn_world_mut().overflow_mut();
+                    if brick_index.index() >= settings.brick_data_max_size {
+                        overflow.gpu_brick_data += 1;
+                        brick_index = BrickIndex::new_loaded_empty();
+                    } else if brick_palette_end > settings.brick_palette_max_size * 256 {
+                        overflow.gpu_brick_palettes += 1;
+                        brick_index = BrickIndex::new_loaded_empty();
+                    }
+                }
 
                 // Set brick indices element
                 let brick_indices_stage_index = brick_indices_copies.len();
@@ -324,23 +440,13 @@ impl VoxelPipeline {
                 brick_indices_copies.push(CopyRegion {
                     src_offset: brick_indices_stage_index as u64
                         * std::mem::size_of::<BrickIndex>() as u64,
-                    dst_offset: brick_morton * std::mem::size_of::<BrickIndex>() as u64,
+                    dst_offset: brick_slot * std::mem::size_of::<BrickIndex>() as u64,
                     size: std::mem::size_of::<BrickIndex>() as u64,
                 });
-                if brick_morton
-                    >= vox_world.dyn_world().brick_indices_grid().buffer_size() as u64 / 4
-                {
-                    // println!("Brick indices buffer on gpu is too small, can't add new bricks.");
-                }
 
                 if brick_index.status() == SpatialStatus::Loaded {
                     // Set brick data element
                     let brick_index = brick_index.index();
-                    if brick_index >= settings.brick_data_max_size {
-                        // println!("Brick data buffer on gpu is too small, can't add new bricks.");
-                        continue;
-                    }
-
                     let brick_data_stage_index = brick_data_copies.len();
                     let brick_data_offset_ptr =
                         unsafe { brick_data_staging_ptr.add(brick_data_stage_index) };
@@ -355,11 +461,6 @@ impl VoxelPipeline {
 
                     // Set brick palette data
                     let brick_palette_index = brick_data.palette_index();
-                    if brick_palette_index >= settings.brick_palette_max_size * 256 {
-                        // println!("Brick palette buffer on gpu is too small, can't add new bricks palettes.");
-                        continue;
-                    }
-
                     let brick_palette = vox_world
                         .dyn_world()
                         .brick_palette_list()
@@ -401,12 +502,14 @@ impl VoxelPipeline {
             }
 
             // println!("Time to copy bricks: {:?}", time.elapsed());
-            command_recorder.copy_buffer_to_buffer_multiple(
-                device,
-                brick_indices_staging_buffer,
-                self.brick_indices_grid_buffer,
-                brick_indices_copies,
-            );
+            if brick_indices_copies.len() > 0 {
+                command_recorder.copy_buffer_to_buffer_multiple(
+                    device,
+                    brick_indices_staging_buffer,
+                    self.brick_index_pool_buffer,
+                    brick_indices_copies,
+                );
+            }
             if brick_data_copies.len() > 0 {
                 command_recorder.copy_buffer_to_buffer_multiple(
                     device,
@@ -430,6 +533,28 @@ impl VoxelPipeline {
             // println!("Time to upload bricks: {:?}", time.elapsed());
         }
 
+        // Uploaded after the brick updates so chunks only point to blocks whose slots are
+        // uploaded.
+        stage_buffer_copy(
+            device,
+            command_recorder,
+            self.chunk_indices_grid_buffer,
+            AccessFlags::SHADER_READ,
+            |ptr: *mut ChunkIndex| unsafe {
+                let grid_slice = vox_world.dyn_world().chunk_indices_grid().as_slice();
+                for (i, chunk_index) in grid_slice.iter().enumerate() {
+                    let chunk_index = if chunk_index.status() == SpatialStatus::Loaded
+                        && self.unsynced_blocks.contains(&chunk_index.block_index())
+                    {
+                        ChunkIndex::new(SpatialStatus::Loading)
+                    } else {
+                        *chunk_index
+                    };
+                    ptr.add(i).write(chunk_index);
+                }
+            },
+        );
+
         let brick_normal_update_size =
             self.queued_brick_normal_updates
                 .len()
@@ -441,10 +566,10 @@ impl VoxelPipeline {
                     - brick_normal_update_size as isize)
                     .max(0) as usize..,
             ) {
-                let brick_index = vox_world.dyn_world().brick_indices_grid().as_slice()
-                    [*brick_normal_update.brick_morton as usize];
+                let dyn_brick_index = brick_normal_update.dyn_brick_index;
+                let brick_index = vox_world.dyn_world().brick_index(dyn_brick_index);
                 if brick_index.status() == SpatialStatus::Loaded {
-                    brick_normal_updates.push(*brick_normal_update.brick_morton as u32);
+                    brick_normal_updates.push(dyn_brick_index as u32);
                 }
             }
 
@@ -475,6 +600,67 @@ impl VoxelPipeline {
         );
     }
 
+    /// Uploads the objects in BVH leaf order so the leaves index the object list directly.
+    /// The objects whose block has been uploaded with a BVH over them, None if that's every
+    /// object. Like chunks, objects aren't traced until their block's slots are uploaded.
+    fn synced_voxel_objects(
+        &self,
+        voxel_objects: &VoxelObjects,
+    ) -> Option<(Vec<VoxelObjectInstance>, Bvh)> {
+        let is_synced =
+            |instance: &VoxelObjectInstance| !self.unsynced_blocks.contains(&instance.block_index);
+        if voxel_objects.instances().iter().all(is_synced) {
+            return None;
+        }
+
+        let instances = voxel_objects
+            .instances()
+            .iter()
+            .filter(|instance| is_synced(instance))
+            .copied()
+            .collect::<Vec<_>>();
+        let aabbs = instances.iter().map(|i| i.aabb).collect::<Vec<_>>();
+        Some((instances, Bvh::build(&aabbs)))
+    }
+
+    fn record_voxel_object_copy_commands(
+        &self,
+        instances: &[VoxelObjectInstance],
+        bvh: &Bvh,
+        device: &mut Device,
+        command_recorder: &mut CommandRecorder,
+    ) {
+        stage_buffer_copy(
+            device,
+            command_recorder,
+            self.voxel_object_list_buffer,
+            AccessFlags::SHADER_READ,
+            |ptr: *mut GpuVoxelObject| {
+                for (i, instance_index) in bvh.indices().iter().enumerate() {
+                    let instance = &instances[*instance_index as usize];
+                    let world_to_object = instance.world_to_object.to_homogeneous();
+                    unsafe {
+                        ptr.add(i).write(GpuVoxelObject {
+                            world_to_object: world_to_object.as_slice().try_into().unwrap(),
+                            block_index: instance.block_index,
+                            _padding: [0; 3],
+                        })
+                    };
+                }
+            },
+        );
+        stage_buffer_copy(
+            device,
+            command_recorder,
+            self.voxel_object_bvh_buffer,
+            AccessFlags::SHADER_READ,
+            |ptr: *mut BvhNode| unsafe {
+                let nodes = bvh.nodes();
+                ptr.copy_from_nonoverlapping(nodes.as_ptr(), nodes.len());
+            },
+        );
+    }
+
     pub fn record_ray_march_commands(
         &mut self,
         device: &mut Device,
@@ -572,6 +758,15 @@ impl VoxelPipeline {
         self.normal_calc_pipeline
     }
 
+    /// Brick changes waiting for a frame with upload space.
+    pub fn queued_brick_update_count(&self) -> usize {
+        self.queued_brick_updates.len()
+    }
+
+    pub fn queued_brick_normal_update_count(&self) -> usize {
+        self.queued_brick_normal_updates.len()
+    }
+
     pub fn compile_brick_requests(
         &self,
         device: &Device,
@@ -603,25 +798,41 @@ impl VoxelPipeline {
         )
     }
 
-    fn create_chunk_occupancy_grid_buffer(
+    fn create_chunk_indices_grid_buffer(
         device: &mut Device,
         vox_world: &DynVoxelWorld,
     ) -> BufferId {
         create_device_buffer(
             device,
-            "chunk_occupancy_grid_buffer",
-            vox_world.chunk_bit_grid().buffer_size() as u64,
+            "chunk_indices_grid_buffer",
+            vox_world.chunk_indices_grid().buffer_size() as u64,
         )
     }
 
-    fn create_brick_indices_grid_buffer(
-        device: &mut Device,
-        vox_world: &DynVoxelWorld,
-    ) -> BufferId {
+    fn create_brick_index_pool_buffer(device: &mut Device, settings: &Settings) -> BufferId {
+        create_device_buffer(
+            device,
+            "brick_index_pool_buffer",
+            std::mem::size_of::<BrickIndex>() as u64
+                * settings.brick_index_block_max_size as u64
+                * CHUNK_VOLUME as u64,
+        )
+    }
+
+    fn create_voxel_object_list_buffer(device: &mut Device, settings: &Settings) -> BufferId {
+        create_device_buffer(
+            device,
+            "voxel_object_list_buffer",
+            std::mem::size_of::<GpuVoxelObject>() as u64 * settings.voxel_object_max_count as u64,
+        )
+    }
+
+    fn create_voxel_object_bvh_buffer(device: &mut Device, settings: &Settings) -> BufferId {
+        // A BVH with n leaves has at most 2n - 1 nodes.
         create_device_buffer(
             device,
-            "brick_indices_grid_buffer",
-            vox_world.brick_indices_grid().buffer_size() as u64,
+            "voxel_object_bvh_buffer",
+            std::mem::size_of::<BvhNode>() as u64 * settings.voxel_object_max_count as u64 * 2,
         )
     }
 
@@ -0,0 +1,91 @@
+use crate::{
+    app::App,
+    constants,
+    engine::{
+        assets::{asset::Assets, plugin::AssetsPlugin, watched_shaders::WatchedShaders},
+        common::camera::Camera,
+        input::plugin::InputPlugin,
+        plugin::{CorePlugin, Plugin, PluginId},
+        schedule::Stage,
+        ui::egui::Egui,
+        voxel::{plugin::VoxelWorldPlugin, vox_world::VoxelWorld},
+        window::window::{Window, WindowConfig},
+    },
+    settings::Settings,
+};
+
+use super::{
+    device::DeviceResource,
+    pass::{egui::EguiPass, voxel::VoxelPipeline},
+    pipeline_manager::PipelineManager,
+    render_manager::RenderManager,
+    swapchain::SwapchainResource,
+};
+
+/// The window, Vulkan device, voxel renderer and egui.
+pub struct RenderPlugin;
+
+impl Plugin for RenderPlugin {
+    fn build(&self, app: &mut App) {
+        let window = Window::new(
+            &WindowConfig {
+                title: constants::WINDOW_TITLE.to_owned(),
+                ..Default::default()
+            },
+            app.event_loop(),
+        );
+        let mut device_resource = DeviceResource::new(&window);
+        let swapchain_resource = SwapchainResource::new(&mut device_resource, &window);
+        let mut pipeline_manager = PipelineManager::new();
+        let (voxel_pipeline, egui_pass) = {
+            let resource_bank = app.resource_bank();
+            let mut assets = resource_bank.get_resource_mut::<Assets>();
+            let mut watched_shaders = resource_bank.get_resource_mut::<WatchedShaders>();
+            let voxel_pipeline = VoxelPipeline::new(
+                &mut assets,
+                &mut watched_shaders,
+                &mut pipeline_manager,
+                &mut device_resource,
+                &resource_bank.get_resource::<Settings>(),
+                &resource_bank.get_resource::<VoxelWorld>(),
+            );
+            let egui_pass = EguiPass::new(&mut assets, &mut watched_shaders);
+            (voxel_pipeline, egui_pass)
+        };
+        let egui = Egui::new(&window);
+
+        app.insert_resource(window)
+            .insert_resource(device_resource)
+            .insert_resource(swapchain_resource)
+            .insert_resource(RenderManager::new())
+            .insert_resource(pipeline_manager)
+            .insert_resource(voxel_pipeline)
+            .insert_resource(egui_pass)
+            .insert_resource(egui);
+
+        app.add_system(Stage::PreUpdate, Egui::begin_frame)
+            .add_system(Stage::Update, VoxelWorld::update_brick_requests)
+            .add_system(Stage::PostUpdate, Egui::end_frame);
+
+        // Update GPU non-buffer resources
+        app.add_system(Stage::PostUpdate, PipelineManager::update)
+            .add_system(Stage::PostUpdate, EguiPass::update)
+            .add_system(Stage::PostUpdate, RenderManager::update);
+
+        // Update render resources
+        app.add_system(Stage::PostUpdate, Camera::update_cameras)
+            .add_system(Stage::PostUpdate, VoxelPipeline::update_world_changes);
+
+        // Render
+        app.add_system(Stage::Render, RenderManager::render);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<CorePlugin>(),
+            PluginId::of::<AssetsPlugin>(),
+            PluginId::of::<InputPlugin>(),
+            PluginId::of::<VoxelWorldPlugin>(),
+        ]
+    }
+}
@@ -7,7 +7,7 @@ use paya::{
     device::{ImageInfo, PresentInfo, SubmitInfo},
     gpu_resources::ImageId,
 };
-use voxei_macros::Resource;
+use voxei_macros::{Resource, SystemParam};
 
 use crate::{
     engine::{
@@ -15,7 +15,8 @@ use crate::{
         common::{camera::Camera, time::Time},
         ecs::ecs_world::ECSWorld,
         resource::{Res, ResMut},
-        voxel::vox_world::VoxelWorld,
+        ui::egui::Egui,
+        voxel::{object::VoxelObjects, vox_world::VoxelWorld},
         window::window::Window,
     },
     game::player::player::PlayerTag,
@@ -24,11 +25,23 @@ use crate::{
 
 use super::{
     device::DeviceResource,
-    pass::voxel::{RayMarchPushConstants, VoxelPipeline},
+    pass::{
+        egui::EguiPass,
+        voxel::{RayMarchPushConstants, VoxelPipeline},
+    },
     pipeline_manager::PipelineManager,
     swapchain::SwapchainResource,
 };
 
+/// The window and device resources a frame is recorded and presented with.
+#[derive(SystemParam)]
+pub struct RenderContext<'rb> {
+    pub device: ResMut<'rb, DeviceResource>,
+    pub swapchain: ResMut<'rb, SwapchainResource>,
+    pub pipeline_manager: Res<'rb, PipelineManager>,
+    pub window: Res<'rb, Window>,
+}
+
 #[derive(Resource)]
 pub struct RenderManager {
     backbuffer: Option<ImageId>,
@@ -70,16 +83,23 @@ impl RenderManager {
 
     pub fn render(
         render_manager: ResMut<RenderManager>,
+        context: RenderContext,
         mut voxel_pipeline: ResMut<VoxelPipeline>,
+        mut egui_pass: ResMut<EguiPass>,
+        mut egui: ResMut<Egui>,
         mut vox_world: ResMut<VoxelWorld>,
-        pipeline_manager: Res<PipelineManager>,
-        mut device: ResMut<DeviceResource>,
-        mut swapchain: ResMut<SwapchainResource>,
-        window: Res<Window>,
-        ecs_world: Res<ECSWorld>,
+        voxel_objects: Res<VoxelObjects>,
+        mut ecs_world: ResMut<ECSWorld>,
         time: Res<Time>,
         settings: Res<Settings>,
     ) {
+        let RenderContext {
+            mut device,
+            mut swapchain,
+            pipeline_manager,
+            window,
+        } = context;
+
         let Some(image_index) = swapchain.acquire_next_image() else {
             return;
         };
@@ -117,12 +137,13 @@ impl RenderManager {
         let copy_time = Instant::now();
         voxel_pipeline.record_copy_commands(
             &mut vox_world,
+            &voxel_objects,
             &mut device,
             &mut command_recorder,
             &settings,
         );
         let copy_time = Instant::now();
-        for (_, camera) in ecs_world.query::<&Camera>().iter() {
+        for (_, camera) in ecs_world.query_mut::<&mut Camera>() {
             camera.record_copy_commands(&mut device, &mut command_recorder);
         }
 
@@ -139,12 +160,22 @@ impl RenderManager {
             cpu_frame_index,
         );
 
+        egui_pass.record_commands(
+            &mut device,
+            &mut command_recorder,
+            &mut egui,
+            backbuffer_index,
+            backbuffer_info.extent.into(),
+            ImageLayout::General,
+            AccessFlags::SHADER_WRITE,
+        );
+
         command_recorder.pipeline_barrier_image_transition(
             &device,
             ImageTransition {
                 image: backbuffer_index,
-                src_layout: ImageLayout::General,
-                src_access: AccessFlags::SHADER_WRITE,
+                src_layout: ImageLayout::ColorAttachmentOptimal,
+                src_access: AccessFlags::COLOR_ATTACHMENT_WRITE,
                 dst_layout: ImageLayout::TransferSrcOptimal,
                 dst_access: AccessFlags::TRANSFER_READ,
             },
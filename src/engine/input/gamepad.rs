@@ -0,0 +1,501 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread::spawn,
+};
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GamepadId(pub usize);
+
+/// Buttons named by their position on the pad so layouts from different vendors map the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    LeftStick,
+    RightStick,
+    Start,
+    Select,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Stick axes range from -1 to 1 with up and right positive, triggers range from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    fn axes(&self) -> (GamepadAxis, GamepadAxis) {
+        match self {
+            Stick::Left => (GamepadAxis::LeftStickX, GamepadAxis::LeftStickY),
+            Stick::Right => (GamepadAxis::RightStickX, GamepadAxis::RightStickY),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadConnection {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SubmitInput {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Pressed(GamepadId, GamepadButton),
+    Released(GamepadId, GamepadButton),
+    Axis(GamepadId, GamepadAxis, f32),
+}
+
+/// Where gamepad input comes from, polled once a frame by `Input::poll_gamepads`.
+pub trait GamepadBackend: Send + Sync {
+    /// Returns the input received since the last poll in the order it happened.
+    fn poll(&mut self) -> Vec<SubmitInput>;
+}
+
+/// A backend fed by hand, clones share the same queue so a test can keep one to send input
+/// after giving the other to `Input`.
+#[derive(Clone, Default)]
+pub struct FakeGamepadBackend {
+    queue: Arc<Mutex<Vec<SubmitInput>>>,
+}
+
+impl FakeGamepadBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, input: SubmitInput) {
+        self.queue.lock().push(input);
+    }
+}
+
+impl GamepadBackend for FakeGamepadBackend {
+    fn poll(&mut self) -> Vec<SubmitInput> {
+        std::mem::take(&mut *self.queue.lock())
+    }
+}
+
+/// Reads physical gamepads through gilrs. Gilrs isn't `Sync` so it lives on its own thread and
+/// sends its input over a channel, the thread blocks on gilrs for as long as the program runs.
+pub struct GilrsGamepadBackend {
+    input_recv: Mutex<Receiver<SubmitInput>>,
+}
+
+impl GilrsGamepadBackend {
+    pub fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (ready_send, ready_recv) = channel();
+        let (input_send, input_recv) = channel();
+        spawn(move || {
+            let mut gilrs = match Gilrs::new() {
+                Ok(gilrs) => gilrs,
+                // Unsupported platforms get a context that never connects a gamepad.
+                Err(gilrs::Error::NotImplemented(gilrs)) => gilrs,
+                Err(err) => {
+                    let _ = ready_send.send(Err(err.to_string()));
+                    return;
+                }
+            };
+            let _ = ready_send.send(Ok(()));
+
+            while let Some(event) = gilrs.next_event_blocking(None) {
+                let Some(input) = gilrs_submit_input(event.id.into(), event.event) else {
+                    continue;
+                };
+                if input_send.send(input).is_err() {
+                    return;
+                }
+            }
+        });
+
+        ready_recv
+            .recv()
+            .map_err(|_| "The gamepad thread exited before starting")??;
+        Ok(Self {
+            input_recv: Mutex::new(input_recv),
+        })
+    }
+}
+
+impl GamepadBackend for GilrsGamepadBackend {
+    fn poll(&mut self) -> Vec<SubmitInput> {
+        self.input_recv.lock().try_iter().collect()
+    }
+}
+
+/// Gilrs reports the analog triggers as buttons with a value and the bumpers as its
+/// `LeftTrigger` and `RightTrigger`.
+fn gilrs_submit_input(id: usize, event: EventType) -> Option<SubmitInput> {
+    let id = GamepadId(id);
+    let input = match event {
+        EventType::Connected => SubmitInput::Connected(id),
+        EventType::Disconnected => SubmitInput::Disconnected(id),
+        EventType::ButtonPressed(button, _) => SubmitInput::Pressed(id, gilrs_button(button)?),
+        EventType::ButtonReleased(button, _) => SubmitInput::Released(id, gilrs_button(button)?),
+        EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+            SubmitInput::Axis(id, GamepadAxis::LeftTrigger, value)
+        }
+        EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+            SubmitInput::Axis(id, GamepadAxis::RightTrigger, value)
+        }
+        EventType::AxisChanged(axis, value, _) => {
+            let axis = match axis {
+                Axis::LeftStickX => GamepadAxis::LeftStickX,
+                Axis::LeftStickY => GamepadAxis::LeftStickY,
+                Axis::RightStickX => GamepadAxis::RightStickX,
+                Axis::RightStickY => GamepadAxis::RightStickY,
+                _ => return None,
+            };
+            SubmitInput::Axis(id, axis, value)
+        }
+        _ => return None,
+    };
+
+    Some(input)
+}
+
+fn gilrs_button(button: Button) -> Option<GamepadButton> {
+    let button = match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::West => GamepadButton::West,
+        Button::North => GamepadButton::North,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::Start => GamepadButton::Start,
+        Button::Select => GamepadButton::Select,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    };
+
+    Some(button)
+}
+
+pub struct Gamepad {
+    pressed_buttons: HashSet<GamepadButton>,
+    down_buttons: HashSet<GamepadButton>,
+    released_buttons: HashSet<GamepadButton>,
+    /// The raw axis values, dead zones are applied when read.
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl Gamepad {
+    fn new() -> Self {
+        Self {
+            pressed_buttons: HashSet::new(),
+            down_buttons: HashSet::new(),
+            released_buttons: HashSet::new(),
+            axes: HashMap::new(),
+        }
+    }
+
+    pub fn is_button_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        self.down_buttons.contains(&button)
+    }
+
+    pub fn is_button_released(&self, button: GamepadButton) -> bool {
+        self.released_buttons.contains(&button)
+    }
+
+    fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// Every connected gamepad.
+pub struct Gamepads {
+    gamepads: HashMap<GamepadId, Gamepad>,
+    connections: Vec<GamepadConnection>,
+    /// Stick positions closer to the center than this read as centered.
+    stick_dead_zone: f32,
+    /// Trigger values below this read as released.
+    trigger_dead_zone: f32,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            gamepads: HashMap::new(),
+            connections: Vec::new(),
+            stick_dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+        }
+    }
+
+    pub fn set_dead_zones(&mut self, stick_dead_zone: f32, trigger_dead_zone: f32) {
+        self.stick_dead_zone = stick_dead_zone;
+        self.trigger_dead_zone = trigger_dead_zone;
+    }
+
+    pub fn submit_input(&mut self, input: SubmitInput) {
+        match input {
+            SubmitInput::Connected(id) => {
+                self.gamepads.insert(id, Gamepad::new());
+                self.connections.push(GamepadConnection::Connected(id));
+            }
+            SubmitInput::Disconnected(id) => {
+                if self.gamepads.remove(&id).is_some() {
+                    self.connections.push(GamepadConnection::Disconnected(id));
+                }
+            }
+            SubmitInput::Pressed(id, button) => {
+                if let Some(gamepad) = self.gamepads.get_mut(&id) {
+                    if gamepad.down_buttons.insert(button) {
+                        gamepad.pressed_buttons.insert(button);
+                    }
+                }
+            }
+            SubmitInput::Released(id, button) => {
+                if let Some(gamepad) = self.gamepads.get_mut(&id) {
+                    gamepad.released_buttons.insert(button);
+                    gamepad.down_buttons.remove(&button);
+                }
+            }
+            SubmitInput::Axis(id, axis, value) => {
+                if let Some(gamepad) = self.gamepads.get_mut(&id) {
+                    gamepad.axes.insert(axis, value);
+                }
+            }
+        }
+    }
+
+    pub fn clear_inputs(&mut self) {
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.pressed_buttons.clear();
+            gamepad.released_buttons.clear();
+        }
+        self.connections.clear();
+    }
+
+    pub fn gamepad(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    pub fn connected(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads.keys().copied()
+    }
+
+    /// The gamepads connected and disconnected this frame.
+    pub fn connections(&self) -> &[GamepadConnection] {
+        &self.connections
+    }
+
+    /// The stick's position with the dead zone removed, rescaled so values still start at 0 at
+    /// the edge of the dead zone.
+    pub fn stick(&self, id: GamepadId, stick: Stick) -> (f32, f32) {
+        let Some(gamepad) = self.gamepads.get(&id) else {
+            return (0.0, 0.0);
+        };
+        let (x_axis, y_axis) = stick.axes();
+        let (x, y) = (gamepad.raw_axis(x_axis), gamepad.raw_axis(y_axis));
+
+        // Radial so diagonals aren't cut off like with a dead zone per axis.
+        let length = (x * x + y * y).sqrt();
+        if length <= self.stick_dead_zone {
+            return (0.0, 0.0);
+        }
+        let scale = rescale(length.min(1.0), self.stick_dead_zone) / length;
+        (x * scale, y * scale)
+    }
+
+    pub fn axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        match axis {
+            GamepadAxis::LeftStickX => self.stick(id, Stick::Left).0,
+            GamepadAxis::LeftStickY => self.stick(id, Stick::Left).1,
+            GamepadAxis::RightStickX => self.stick(id, Stick::Right).0,
+            GamepadAxis::RightStickY => self.stick(id, Stick::Right).1,
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => {
+                let value = self
+                    .gamepads
+                    .get(&id)
+                    .map_or(0.0, |gamepad| gamepad.raw_axis(axis));
+                if value <= self.trigger_dead_zone {
+                    0.0
+                } else {
+                    rescale(value.min(1.0), self.trigger_dead_zone)
+                }
+            }
+        }
+    }
+
+    pub fn is_button_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id)
+            .is_some_and(|gamepad| gamepad.is_button_pressed(button))
+    }
+
+    pub fn is_button_down(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id)
+            .is_some_and(|gamepad| gamepad.is_button_down(button))
+    }
+
+    pub fn is_button_released(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id)
+            .is_some_and(|gamepad| gamepad.is_button_released(button))
+    }
+
+    // Across every connected gamepad, used by the input map.
+    pub fn is_any_button_pressed(&self, button: GamepadButton) -> bool {
+        self.connected()
+            .any(|id| self.is_button_pressed(id, button))
+    }
+
+    pub fn is_any_button_down(&self, button: GamepadButton) -> bool {
+        self.connected().any(|id| self.is_button_down(id, button))
+    }
+
+    pub fn is_any_button_released(&self, button: GamepadButton) -> bool {
+        self.connected()
+            .any(|id| self.is_button_released(id, button))
+    }
+
+    /// The axis value furthest from rest across every connected gamepad.
+    pub fn any_axis(&self, axis: GamepadAxis) -> f32 {
+        self.connected()
+            .map(|id| self.axis(id, axis))
+            .fold(
+                0.0f32,
+                |max, value| {
+                    if value.abs() > max.abs() {
+                        value
+                    } else {
+                        max
+                    }
+                },
+            )
+    }
+}
+
+/// Maps a value from the dead zone to 1 onto 0 to 1.
+fn rescale(value: f32, dead_zone: f32) -> f32 {
+    (value - dead_zone) / (1.0 - dead_zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::input::{
+        mapper::{AxisBinding, Binding},
+        Input,
+    };
+
+    const PAD: GamepadId = GamepadId(0);
+
+    #[test]
+    fn test_buttons_and_connections() {
+        let backend = FakeGamepadBackend::new();
+        let mut input = Input::new();
+        input.set_gamepad_backend(Box::new(backend.clone()));
+
+        backend.send(SubmitInput::Pressed(PAD, GamepadButton::South));
+        backend.send(SubmitInput::Connected(PAD));
+        backend.send(SubmitInput::Pressed(PAD, GamepadButton::South));
+        input.update_gamepads();
+        // Input from a pad that isn't connected yet is dropped.
+        assert_eq!(
+            input.gamepads().connections(),
+            [GamepadConnection::Connected(PAD)]
+        );
+        assert!(input
+            .gamepads()
+            .is_button_pressed(PAD, GamepadButton::South));
+        assert!(input.action_pressed("jump"));
+
+        input.gamepads_mut().clear_inputs();
+        input.update_gamepads();
+        assert!(input.gamepads().connections().is_empty());
+        assert!(!input.action_pressed("jump"));
+        assert!(input.action("jump"));
+
+        backend.send(SubmitInput::Released(PAD, GamepadButton::South));
+        backend.send(SubmitInput::Disconnected(PAD));
+        input.update_gamepads();
+        assert_eq!(
+            input.gamepads().connections(),
+            [GamepadConnection::Disconnected(PAD)]
+        );
+        assert!(!input.action("jump"));
+    }
+
+    #[test]
+    fn test_dead_zones() {
+        let mut gamepads = Gamepads::new();
+        gamepads.set_dead_zones(0.2, 0.1);
+        gamepads.submit_input(SubmitInput::Connected(PAD));
+
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::LeftStickX, 0.1));
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::LeftStickY, 0.1));
+        assert_eq!(gamepads.stick(PAD, Stick::Left), (0.0, 0.0));
+
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::LeftStickX, 0.0));
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::LeftStickY, 0.6));
+        assert!((gamepads.axis(PAD, GamepadAxis::LeftStickY) - 0.5).abs() < 1e-6);
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::LeftStickY, 1.0));
+        assert_eq!(gamepads.axis(PAD, GamepadAxis::LeftStickY), 1.0);
+
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::RightTrigger, 0.05));
+        assert_eq!(gamepads.axis(PAD, GamepadAxis::RightTrigger), 0.0);
+        gamepads.submit_input(SubmitInput::Axis(PAD, GamepadAxis::RightTrigger, 0.55));
+        assert!((gamepads.axis(PAD, GamepadAxis::RightTrigger) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mapped_gamepad_axis() {
+        let backend = FakeGamepadBackend::new();
+        let mut input = Input::new();
+        input.set_gamepad_backend(Box::new(backend.clone()));
+        input
+            .input_map_mut()
+            .bind_action("fire", Binding::GamepadButton(GamepadButton::RightBumper))
+            .bind_axis(
+                "throttle",
+                AxisBinding::GamepadAxis(GamepadAxis::RightTrigger),
+            );
+
+        backend.send(SubmitInput::Connected(PAD));
+        backend.send(SubmitInput::Axis(PAD, GamepadAxis::LeftStickY, 1.0));
+        backend.send(SubmitInput::Axis(PAD, GamepadAxis::RightTrigger, 1.0));
+        backend.send(SubmitInput::Pressed(PAD, GamepadButton::RightBumper));
+        input.update_gamepads();
+
+        assert_eq!(input.axis("vertical"), 1.0);
+        assert_eq!(input.axis("throttle"), 1.0);
+        assert!(input.action("fire"));
+    }
+}
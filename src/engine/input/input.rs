@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use voxei_macros::Resource;
 
 use crate::engine::resource::ResMut;
@@ -6,14 +7,32 @@ use winit::event::DeviceEvent as WinitDeviceEvent;
 use winit::event::DeviceId as WinitDeviceId;
 
 use super::{
+    gamepad::{self, GamepadBackend, Gamepads},
     keyboard::{self, Keyboard},
+    mapper::InputMap,
     mouse::{self, Mouse},
 };
 
+/// An input from any device, everything `Input` receives goes through `Input::submit` as one of
+/// these so it can be recorded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Keyboard(keyboard::SubmitInput),
+    Mouse(mouse::SubmitInput),
+    Gamepad(gamepad::SubmitInput),
+}
+
 #[derive(Resource)]
 pub struct Input {
     keyboard: Keyboard,
     mouse: Mouse,
+    gamepads: Gamepads,
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    input_map: InputMap,
+    /// The inputs submitted since the last `take_recorded`, None when not recording.
+    recorded: Option<Vec<InputEvent>>,
+    /// False while a recording is replayed so the window and gamepads can't interfere.
+    live_input_enabled: bool,
 }
 
 impl Input {
@@ -21,35 +40,111 @@ impl Input {
         Self {
             keyboard: Keyboard::new(),
             mouse: Mouse::new(),
+            gamepads: Gamepads::new(),
+            gamepad_backend: None,
+            input_map: InputMap::default(),
+            recorded: None,
+            live_input_enabled: true,
         }
     }
 
     pub fn clear_inputs(mut input: ResMut<Input>) {
         input.keyboard.clear_inputs();
         input.mouse.clear_inputs();
+        input.gamepads.clear_inputs();
     }
 
-    // General Input
-    pub fn horizontal_axis(&self) -> f32 {
-        let mut axis = 0.0;
-        if self.keyboard.is_key_down(keyboard::Key::A) {
-            axis -= 1.0;
+    pub fn poll_gamepads(mut input: ResMut<Input>) {
+        input.update_gamepads();
+    }
+
+    /// Submits the input the gamepad backend received since the last update.
+    pub fn update_gamepads(&mut self) {
+        if !self.live_input_enabled {
+            return;
         }
-        if self.keyboard.is_key_down(keyboard::Key::D) {
-            axis += 1.0;
+        let Some(backend) = &mut self.gamepad_backend else {
+            return;
+        };
+        for input in backend.poll() {
+            self.submit(InputEvent::Gamepad(input));
         }
-        axis
     }
 
-    pub fn vertical_axis(&self) -> f32 {
-        let mut axis = 0.0;
-        if self.keyboard.is_key_down(keyboard::Key::S) {
-            axis -= 1.0;
+    pub fn submit(&mut self, input: InputEvent) {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(input.clone());
         }
-        if self.keyboard.is_key_down(keyboard::Key::W) {
-            axis += 1.0;
+        match input {
+            InputEvent::Keyboard(input) => self.keyboard.submit_input(input),
+            InputEvent::Mouse(input) => self.mouse.submit_input(input),
+            InputEvent::Gamepad(input) => self.gamepads.submit_input(input),
         }
-        axis
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recorded = Some(Vec::new());
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorded = None;
+    }
+
+    /// Takes the inputs submitted since the last call while recording.
+    pub fn take_recorded(&mut self) -> Vec<InputEvent> {
+        self.recorded
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Whether input from the window and gamepad backend is submitted, `submit` always is.
+    pub fn set_live_input_enabled(&mut self, enabled: bool) {
+        self.live_input_enabled = enabled;
+    }
+
+    /// Replaces where gamepad input comes from, without a backend no gamepads ever connect.
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepad_backend = Some(backend);
+    }
+
+    // General Input
+    pub fn horizontal_axis(&self) -> f32 {
+        self.axis("horizontal")
+    }
+
+    pub fn vertical_axis(&self) -> f32 {
+        self.axis("vertical")
+    }
+
+    // Mapped input functions, unbound actions and axes are never active.
+    /// Returns true while any of the action's bindings are held.
+    pub fn action(&self, action: &str) -> bool {
+        self.input_map.is_action_down(action, self)
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.input_map.is_action_pressed(action, self)
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.input_map.is_action_released(action, self)
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.input_map.axis_value(axis, self)
+    }
+
+    pub fn input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    pub fn input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.input_map
+    }
+
+    pub fn set_input_map(&mut self, input_map: InputMap) {
+        self.input_map = input_map;
     }
 
     // Keyboard functions
@@ -95,6 +190,16 @@ impl Input {
         self.keyboard.is_key_released(key)
     }
 
+    /// The text typed this frame, see `Keyboard::text`.
+    pub fn text(&self) -> &str {
+        self.keyboard.text()
+    }
+
+    /// Allows the IME for this frame, see `Keyboard::request_text_input`.
+    pub fn request_text_input(&mut self, area: keyboard::TextInputArea) {
+        self.keyboard.request_text_input(area);
+    }
+
     // Mouse functions
     pub fn is_mouse_button_pressed(&self, button: mouse::Button) -> bool {
         self.mouse.is_mouse_button_pressed(button)
@@ -112,10 +217,26 @@ impl Input {
         self.mouse.mouse_position()
     }
 
+    pub fn logical_mouse_position(&self) -> (f32, f32) {
+        self.mouse.logical_mouse_position()
+    }
+
     pub fn mouse_delta(&self) -> (f32, f32) {
         self.mouse.mouse_delta()
     }
 
+    pub fn scroll_lines(&self) -> (f32, f32) {
+        self.mouse.scroll_lines()
+    }
+
+    pub fn scroll_pixels(&self) -> (f32, f32) {
+        self.mouse.scroll_pixels()
+    }
+
+    pub fn is_cursor_inside(&self) -> bool {
+        self.mouse.is_cursor_inside()
+    }
+
     pub fn keyboard(&self) -> &Keyboard {
         &self.keyboard
     }
@@ -132,33 +253,51 @@ impl Input {
         &mut self.mouse
     }
 
+    pub fn gamepads(&self) -> &Gamepads {
+        &self.gamepads
+    }
+
+    pub fn gamepads_mut(&mut self) -> &mut Gamepads {
+        &mut self.gamepads
+    }
+
     pub fn handle_winit_device_event(&mut self, device_id: WinitDeviceId, event: WinitDeviceEvent) {
+        if !self.live_input_enabled {
+            return;
+        }
         match event {
             WinitDeviceEvent::Key(key_event) => {
                 if let winit::keyboard::PhysicalKey::Code(winit_key_code) = key_event.physical_key {
                     if let Some(key) = keyboard::Key::from_winit_key_code(winit_key_code) {
                         match key_event.state {
                             winit::event::ElementState::Pressed => {
-                                self.keyboard
-                                    .submit_input(keyboard::SubmitInput::Pressed(key));
+                                self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Pressed(
+                                    key,
+                                )));
                             }
                             winit::event::ElementState::Released => {
-                                self.keyboard
-                                    .submit_input(keyboard::SubmitInput::Released(key));
+                                self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Released(
+                                    key,
+                                )));
                             }
                         }
                     }
                 }
             }
             WinitDeviceEvent::MouseMotion { delta } => {
-                self.mouse
-                    .submit_input(mouse::SubmitInput::Delta(delta.0 as f32, delta.1 as f32));
+                self.submit(InputEvent::Mouse(mouse::SubmitInput::Delta(
+                    delta.0 as f32,
+                    delta.1 as f32,
+                )));
             }
             _ => {}
         }
     }
 
     pub fn handle_winit_window_event(&mut self, event: winit::event::WindowEvent) {
+        if !self.live_input_enabled {
+            return;
+        }
         if let winit::event::WindowEvent::KeyboardInput {
             device_id,
             event,
@@ -170,16 +309,25 @@ impl Input {
                     if let Some(key) = keyboard::Key::from_winit_key_code(winit_key_code) {
                         match event.state {
                             winit::event::ElementState::Pressed => {
-                                self.keyboard
-                                    .submit_input(keyboard::SubmitInput::Pressed(key));
+                                self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Pressed(
+                                    key,
+                                )));
                             }
                             winit::event::ElementState::Released => {
-                                self.keyboard
-                                    .submit_input(keyboard::SubmitInput::Released(key));
+                                self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Released(
+                                    key,
+                                )));
                             }
                         }
                     }
                 }
+                if event.state == winit::event::ElementState::Pressed {
+                    if let Some(text) = &event.text {
+                        self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Text(
+                            text.to_string(),
+                        )));
+                    }
+                }
             }
         }
         if let winit::event::WindowEvent::MouseInput {
@@ -192,14 +340,60 @@ impl Input {
             if let Some(button) = mouse::Button::from_winit_button(button) {
                 match state {
                     winit::event::ElementState::Pressed => {
-                        self.mouse.submit_input(mouse::SubmitInput::Pressed(button));
+                        self.submit(InputEvent::Mouse(mouse::SubmitInput::Pressed(button)));
                     }
                     winit::event::ElementState::Released => {
-                        self.mouse
-                            .submit_input(mouse::SubmitInput::Released(button));
+                        self.submit(InputEvent::Mouse(mouse::SubmitInput::Released(button)));
                     }
                 }
             }
         }
+        match event {
+            winit::event::WindowEvent::CursorMoved { position, .. } => {
+                self.submit(InputEvent::Mouse(mouse::SubmitInput::Position(
+                    position.x as f32,
+                    position.y as f32,
+                )));
+            }
+            winit::event::WindowEvent::CursorEntered { .. } => {
+                self.submit(InputEvent::Mouse(mouse::SubmitInput::CursorEntered));
+            }
+            winit::event::WindowEvent::CursorLeft { .. } => {
+                self.submit(InputEvent::Mouse(mouse::SubmitInput::CursorLeft));
+            }
+            winit::event::WindowEvent::MouseWheel { delta, .. } => match delta {
+                winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                    self.submit(InputEvent::Mouse(mouse::SubmitInput::ScrollLines(x, y)));
+                }
+                winit::event::MouseScrollDelta::PixelDelta(position) => {
+                    self.submit(InputEvent::Mouse(mouse::SubmitInput::ScrollPixels(
+                        position.x as f32,
+                        position.y as f32,
+                    )));
+                }
+            },
+            winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.submit(InputEvent::Mouse(mouse::SubmitInput::ScaleFactor(
+                    scale_factor as f32,
+                )));
+            }
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                self.submit(InputEvent::Keyboard(keyboard::SubmitInput::Modifiers(
+                    keyboard::Modifier::from_winit_modifiers(modifiers.state()),
+                )));
+            }
+            winit::event::WindowEvent::Ime(ime) => {
+                let input = match ime {
+                    winit::event::Ime::Enabled => keyboard::SubmitInput::ImeEnabled,
+                    winit::event::Ime::Preedit(text, cursor) => {
+                        keyboard::SubmitInput::ImePreedit(keyboard::Preedit { text, cursor })
+                    }
+                    winit::event::Ime::Commit(text) => keyboard::SubmitInput::ImeCommit(text),
+                    winit::event::Ime::Disabled => keyboard::SubmitInput::ImeDisabled,
+                };
+                self.submit(InputEvent::Keyboard(input));
+            }
+            _ => {}
+        }
     }
 }
@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
 use winit::{event::ButtonId as WinitButtonId, keyboard::KeyCode as WinitKeyCode};
 
 pub struct Keyboard {
@@ -6,6 +8,33 @@ pub struct Keyboard {
     down_keys: HashSet<Key>,
     repeated_keys: HashSet<Key>,
     released_keys: HashSet<Key>,
+    /// The modifiers held as reported by the OS, which also knows about modifiers pressed
+    /// before the window had focus.
+    modifiers: HashSet<Modifier>,
+    /// The text typed and committed by the IME this frame.
+    text: String,
+    preedit: Option<Preedit>,
+    ime_enabled: bool,
+    /// Where text is being typed this frame, the IME is only allowed while there is one.
+    text_input_area: Option<TextInputArea>,
+}
+
+/// The area of the window text is typed into in physical pixels, the IME places its candidate
+/// window next to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextInputArea {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Text an IME is composing that hasn't been committed yet, shown inline by text fields.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preedit {
+    pub text: String,
+    /// The byte range of the cursor or selection in `text`, None to hide the cursor.
+    pub cursor: Option<(usize, usize)>,
 }
 
 impl Keyboard {
@@ -15,6 +44,11 @@ impl Keyboard {
             down_keys: HashSet::new(),
             repeated_keys: HashSet::new(),
             released_keys: HashSet::new(),
+            modifiers: HashSet::new(),
+            text: String::new(),
+            preedit: None,
+            ime_enabled: false,
+            text_input_area: None,
         }
     }
 
@@ -35,6 +69,23 @@ impl Keyboard {
             SubmitInput::Repeated(key) => {
                 self.repeated_keys.insert(key);
             }
+            SubmitInput::Modifiers(modifiers) => {
+                self.modifiers = modifiers.into_iter().collect();
+            }
+            // Control characters like backspace and enter are left to the key events.
+            SubmitInput::Text(text) | SubmitInput::ImeCommit(text) => {
+                self.text.extend(text.chars().filter(|c| !c.is_control()));
+            }
+            SubmitInput::ImePreedit(preedit) => {
+                self.preedit = Some(preedit).filter(|preedit| !preedit.text.is_empty());
+            }
+            SubmitInput::ImeEnabled => {
+                self.ime_enabled = true;
+            }
+            SubmitInput::ImeDisabled => {
+                self.ime_enabled = false;
+                self.preedit = None;
+            }
         }
     }
 
@@ -42,6 +93,8 @@ impl Keyboard {
         self.pressed_keys.clear();
         self.repeated_keys.clear();
         self.released_keys.clear();
+        self.text.clear();
+        self.text_input_area = None;
     }
 
     pub fn is_key_pressed(&self, key: Key) -> bool {
@@ -73,27 +126,52 @@ impl Keyboard {
     }
 
     pub fn is_modifiers_down(&self, modifiers: &[Modifier]) -> bool {
-        for modifier in modifiers {
-            if !modifier
-                .get_keys()
-                .iter()
-                .any(|k| self.is_key_down(k.clone()))
-            {
-                return false;
-            }
-        }
-        return true;
+        modifiers
+            .iter()
+            .all(|modifier| self.modifiers.contains(modifier))
+    }
+
+    /// The text typed this frame, in order and without control characters.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The IME's in progress composition, kept until it's committed or cancelled.
+    pub fn preedit(&self) -> Option<&Preedit> {
+        self.preedit.as_ref()
+    }
+
+    pub fn is_ime_enabled(&self) -> bool {
+        self.ime_enabled
+    }
+
+    /// Asks for the IME this frame so composed text reaches `text`, call it every frame text is
+    /// expected.
+    pub fn request_text_input(&mut self, area: TextInputArea) {
+        self.text_input_area = Some(area);
+    }
+
+    pub fn text_input_area(&self) -> Option<TextInputArea> {
+        self.text_input_area
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SubmitInput {
     Pressed(Key),
     Repeated(Key),
     Released(Key),
+    /// Every modifier currently held.
+    Modifiers(Vec<Modifier>),
+    Text(String),
+    ImeEnabled,
+    /// Replaces the composition, an empty preedit clears it.
+    ImePreedit(Preedit),
+    ImeCommit(String),
+    ImeDisabled,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Key {
     A,
     B,
@@ -266,19 +344,84 @@ impl Key {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Modifier {
     Shift,
     Control,
     Alt,
+    /// The Windows key or Command on macOS.
+    Super,
 }
 
 impl Modifier {
-    pub(crate) fn get_keys(&self) -> Vec<Key> {
-        match self {
-            Modifier::Shift => vec![Key::LShift, Key::RShift],
-            Modifier::Control => vec![Key::LControl, Key::RControl],
-            Modifier::Alt => vec![Key::LAlt, Key::RAlt],
-        }
+    pub fn from_winit_modifiers(state: winit::keyboard::ModifiersState) -> Vec<Self> {
+        [
+            (state.shift_key(), Modifier::Shift),
+            (state.control_key(), Modifier::Control),
+            (state.alt_key(), Modifier::Alt),
+            (state.super_key(), Modifier::Super),
+        ]
+        .into_iter()
+        .filter(|(held, _)| *held)
+        .map(|(_, modifier)| modifier)
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_and_ime() {
+        let mut keyboard = Keyboard::new();
+        keyboard.submit_input(SubmitInput::Text("a".to_owned()));
+        keyboard.submit_input(SubmitInput::Text("\u{8}".to_owned()));
+        keyboard.submit_input(SubmitInput::ImeEnabled);
+        keyboard.submit_input(SubmitInput::ImePreedit(Preedit {
+            text: "にほ".to_owned(),
+            cursor: Some((6, 6)),
+        }));
+        assert_eq!(keyboard.preedit().unwrap().text, "にほ");
+
+        keyboard.submit_input(SubmitInput::ImePreedit(Preedit {
+            text: String::new(),
+            cursor: None,
+        }));
+        keyboard.submit_input(SubmitInput::ImeCommit("日本".to_owned()));
+        assert_eq!(keyboard.text(), "a日本");
+        assert!(keyboard.preedit().is_none() && keyboard.is_ime_enabled());
+
+        keyboard.clear_inputs();
+        assert_eq!(keyboard.text(), "");
+    }
+
+    #[test]
+    fn test_text_input_is_requested_per_frame() {
+        let mut keyboard = Keyboard::new();
+        let area = TextInputArea {
+            x: 10.0,
+            y: 20.0,
+            width: 200.0,
+            height: 16.0,
+        };
+        keyboard.request_text_input(area);
+        assert_eq!(keyboard.text_input_area(), Some(area));
+
+        keyboard.clear_inputs();
+        assert_eq!(keyboard.text_input_area(), None);
+    }
+
+    #[test]
+    fn test_modifiers_come_from_the_os() {
+        let mut keyboard = Keyboard::new();
+        keyboard.submit_input(SubmitInput::Pressed(Key::S));
+        // Held before the window had focus so there was never a key event for it.
+        keyboard.submit_input(SubmitInput::Modifiers(vec![Modifier::Control]));
+        assert!(keyboard.is_key_down_with_modifiers(Key::S, &[Modifier::Control]));
+        assert!(!keyboard.is_key_down_with_modifiers(Key::S, &[Modifier::Control, Modifier::Shift]));
+
+        keyboard.submit_input(SubmitInput::Modifiers(Vec::new()));
+        assert!(!keyboard.is_key_down_with_modifiers(Key::S, &[Modifier::Control]));
     }
 }
@@ -0,0 +1,338 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    gamepad::{GamepadAxis, GamepadButton},
+    keyboard::{Key, Modifier},
+    mouse, Input,
+};
+
+/// A physical input an action or axis can be bound to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    /// A key, only counted while every modifier is held too.
+    Key {
+        key: Key,
+        modifiers: Vec<Modifier>,
+    },
+    MouseButton(mouse::Button),
+    /// The button on any connected gamepad.
+    GamepadButton(GamepadButton),
+}
+
+impl Binding {
+    pub fn key(key: Key) -> Self {
+        Binding::Key {
+            key,
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn key_with_modifiers(key: Key, modifiers: &[Modifier]) -> Self {
+        Binding::Key {
+            key,
+            modifiers: modifiers.to_vec(),
+        }
+    }
+
+    fn is_down(&self, input: &Input) -> bool {
+        match self {
+            Binding::Key { key, modifiers } => input.is_key_down_with_modifiers(*key, modifiers),
+            Binding::MouseButton(button) => input.is_mouse_button_down(*button),
+            Binding::GamepadButton(button) => input.gamepads().is_any_button_down(*button),
+        }
+    }
+
+    fn is_pressed(&self, input: &Input) -> bool {
+        match self {
+            Binding::Key { key, modifiers } => input.is_key_pressed_with_modifiers(*key, modifiers),
+            Binding::MouseButton(button) => input.is_mouse_button_pressed(*button),
+            Binding::GamepadButton(button) => input.gamepads().is_any_button_pressed(*button),
+        }
+    }
+
+    fn is_released(&self, input: &Input) -> bool {
+        match self {
+            Binding::Key { key, modifiers } => {
+                input.is_key_released_with_modifiers(*key, modifiers)
+            }
+            Binding::MouseButton(button) => input.is_mouse_button_released(*button),
+            Binding::GamepadButton(button) => input.gamepads().is_any_button_released(*button),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseAxis {
+    X,
+    Y,
+}
+
+/// Where an axis reads its value from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    /// -1 while `negative` is held and 1 while `positive` is held, 0 with both or neither.
+    Buttons {
+        negative: Binding,
+        positive: Binding,
+    },
+    /// The mouse's movement this frame in pixels.
+    MouseMotion(MouseAxis),
+    /// The axis on any connected gamepad with its dead zone applied.
+    GamepadAxis(GamepadAxis),
+}
+
+impl AxisBinding {
+    fn value(&self, input: &Input) -> f32 {
+        match self {
+            AxisBinding::Buttons { negative, positive } => {
+                let mut value = 0.0;
+                if negative.is_down(input) {
+                    value -= 1.0;
+                }
+                if positive.is_down(input) {
+                    value += 1.0;
+                }
+                value
+            }
+            AxisBinding::MouseMotion(MouseAxis::X) => input.mouse_delta().0,
+            AxisBinding::MouseMotion(MouseAxis::Y) => input.mouse_delta().1,
+            AxisBinding::GamepadAxis(axis) => input.gamepads().any_axis(*axis),
+        }
+    }
+}
+
+/// An error loading or saving an input map or recording.
+#[derive(Debug)]
+pub enum InputFileError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl Display for InputFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputFileError::Io(err) => write!(f, "Failed to access input file: {}", err),
+            InputFileError::Parse(err) => write!(f, "Failed to parse input file: {}", err),
+            InputFileError::Serialize(err) => write!(f, "Failed to serialize input file: {}", err),
+        }
+    }
+}
+
+impl Error for InputFileError {}
+
+/// Named actions and axes mapped to the inputs that drive them, so gameplay code asks for
+/// `"jump"` instead of a key and players can rebind controls.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    // Sorted maps so saved files don't reorder between saves.
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl InputMap {
+    /// A map without any bindings, see `InputMap::default` for the game's controls.
+    pub fn new() -> Self {
+        Self {
+            actions: BTreeMap::new(),
+            axes: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a binding to the action, an action is active while any of its bindings are.
+    pub fn bind_action(&mut self, action: &str, binding: Binding) -> &mut Self {
+        self.actions
+            .entry(action.to_owned())
+            .or_default()
+            .push(binding);
+        self
+    }
+
+    /// Adds a binding to the axis, the axis is the sum of its bindings.
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(axis.to_owned()).or_default().push(binding);
+        self
+    }
+
+    /// Removes every binding of the action, for rebinding it from scratch.
+    pub fn clear_action(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    pub fn clear_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axes.get(axis).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(String::as_str)
+    }
+
+    pub fn axes(&self) -> impl Iterator<Item = &str> {
+        self.axes.keys().map(String::as_str)
+    }
+
+    pub(crate) fn is_action_down(&self, action: &str, input: &Input) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| binding.is_down(input))
+    }
+
+    pub(crate) fn is_action_pressed(&self, action: &str, input: &Input) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| binding.is_pressed(input))
+    }
+
+    pub(crate) fn is_action_released(&self, action: &str, input: &Input) -> bool {
+        self.action_bindings(action)
+            .iter()
+            .any(|binding| binding.is_released(input))
+    }
+
+    pub(crate) fn axis_value(&self, axis: &str, input: &Input) -> f32 {
+        self.axis_bindings(axis)
+            .iter()
+            .map(|binding| binding.value(input))
+            .sum()
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self, InputFileError> {
+        ron::from_str(ron).map_err(InputFileError::Parse)
+    }
+
+    pub fn to_ron(&self) -> Result<String, InputFileError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(InputFileError::Serialize)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputFileError> {
+        let ron = std::fs::read_to_string(path).map_err(InputFileError::Io)?;
+        Self::from_ron(&ron)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), InputFileError> {
+        std::fs::write(path, self.to_ron()?).map_err(InputFileError::Io)
+    }
+}
+
+impl Default for InputMap {
+    /// The game's default controls.
+    fn default() -> Self {
+        let mut input_map = Self::new();
+        input_map
+            .bind_action("pause", Binding::key(Key::Tab))
+            .bind_action("pause", Binding::GamepadButton(GamepadButton::Start))
+            .bind_action("toggle_movement_mode", Binding::key(Key::V))
+            .bind_action(
+                "toggle_movement_mode",
+                Binding::GamepadButton(GamepadButton::Select),
+            )
+            .bind_action("zoom", Binding::key(Key::C))
+            .bind_action("zoom", Binding::GamepadButton(GamepadButton::LeftBumper))
+            .bind_action("jump", Binding::key(Key::Space))
+            .bind_action("jump", Binding::GamepadButton(GamepadButton::South))
+            .bind_action("ascend", Binding::key(Key::Space))
+            .bind_action("ascend", Binding::GamepadButton(GamepadButton::South))
+            .bind_action("descend", Binding::key(Key::LShift))
+            .bind_action("descend", Binding::GamepadButton(GamepadButton::East))
+            .bind_action("run", Binding::key(Key::LControl))
+            .bind_action("run", Binding::GamepadButton(GamepadButton::LeftStick))
+            .bind_action("toggle_debug_overlay", Binding::key(Key::F3))
+            .bind_axis(
+                "horizontal",
+                AxisBinding::Buttons {
+                    negative: Binding::key(Key::A),
+                    positive: Binding::key(Key::D),
+                },
+            )
+            .bind_axis(
+                "horizontal",
+                AxisBinding::GamepadAxis(GamepadAxis::LeftStickX),
+            )
+            .bind_axis(
+                "vertical",
+                AxisBinding::Buttons {
+                    negative: Binding::key(Key::S),
+                    positive: Binding::key(Key::W),
+                },
+            )
+            .bind_axis(
+                "vertical",
+                AxisBinding::GamepadAxis(GamepadAxis::LeftStickY),
+            )
+            .bind_axis("look_x", AxisBinding::MouseMotion(MouseAxis::X))
+            .bind_axis("look_y", AxisBinding::MouseMotion(MouseAxis::Y));
+        input_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::input::{keyboard, Input};
+
+    #[test]
+    fn test_actions_and_axes() {
+        let mut input = Input::new();
+        input
+            .input_map_mut()
+            .bind_action("fire", Binding::MouseButton(mouse::Button::Left))
+            .bind_action(
+                "save",
+                Binding::key_with_modifiers(Key::S, &[Modifier::Control]),
+            );
+
+        for key in [Key::S, Key::Space] {
+            input
+                .keyboard_mut()
+                .submit_input(keyboard::SubmitInput::Pressed(key));
+        }
+        input
+            .mouse_mut()
+            .submit_input(mouse::SubmitInput::Pressed(mouse::Button::Left));
+
+        assert!(input.action("jump"));
+        assert!(input.action_pressed("fire"));
+        assert!(!input.action("save"));
+        assert!(!input.action("unbound"));
+        assert_eq!(input.axis("vertical"), -1.0);
+
+        input
+            .keyboard_mut()
+            .submit_input(keyboard::SubmitInput::Modifiers(vec![Modifier::Control]));
+        assert!(input.action("save"));
+        input
+            .keyboard_mut()
+            .submit_input(keyboard::SubmitInput::Pressed(Key::W));
+        assert_eq!(input.axis("vertical"), 0.0);
+    }
+
+    #[test]
+    fn test_ron_round_trip() {
+        let mut input_map = InputMap::default();
+        input_map.clear_action("jump");
+        input_map.bind_action("jump", Binding::MouseButton(mouse::Button::Right));
+
+        let ron = input_map.to_ron().unwrap();
+        assert_eq!(InputMap::from_ron(&ron).unwrap(), input_map);
+        assert!(matches!(
+            InputMap::from_ron("(actions: 3)"),
+            Err(InputFileError::Parse(_))
+        ));
+    }
+}
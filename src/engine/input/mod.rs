@@ -1,6 +1,9 @@
 pub mod input;
 pub mod mapper;
+pub mod plugin;
+pub mod recording;
 pub use input::*;
 
+pub mod gamepad;
 pub mod keyboard;
 pub mod mouse;
@@ -1,8 +1,18 @@
 use std::collections::HashSet;
 
+use serde::{Deserialize, Serialize};
+
 pub struct Mouse {
+    /// The cursor's position in physical pixels from the window's top left corner.
     position: (f32, f32),
     delta: (f32, f32),
+    /// The window's scale factor, physical pixels per logical pixel.
+    scale_factor: f32,
+    scroll_lines: (f32, f32),
+    scroll_pixels: (f32, f32),
+    cursor_inside: bool,
+    cursor_entered: bool,
+    cursor_left: bool,
     pressed_buttons: HashSet<Button>,
     down_buttons: HashSet<Button>,
     released_buttons: HashSet<Button>,
@@ -13,6 +23,12 @@ impl Mouse {
         Self {
             position: (0.0, 0.0),
             delta: (0.0, 0.0),
+            scale_factor: 1.0,
+            scroll_lines: (0.0, 0.0),
+            scroll_pixels: (0.0, 0.0),
+            cursor_inside: false,
+            cursor_entered: false,
+            cursor_left: false,
             pressed_buttons: HashSet::new(),
             down_buttons: HashSet::new(),
             released_buttons: HashSet::new(),
@@ -23,6 +39,10 @@ impl Mouse {
         self.pressed_buttons.clear();
         self.released_buttons.clear();
         self.delta = (0.0, 0.0);
+        self.scroll_lines = (0.0, 0.0);
+        self.scroll_pixels = (0.0, 0.0);
+        self.cursor_entered = false;
+        self.cursor_left = false;
     }
 
     pub fn submit_input(&mut self, input: SubmitInput) {
@@ -41,6 +61,23 @@ impl Mouse {
             SubmitInput::Delta(x, y) => {
                 self.delta = (self.delta.0 + x, self.delta.1 + y);
             }
+            SubmitInput::ScrollLines(x, y) => {
+                self.scroll_lines = (self.scroll_lines.0 + x, self.scroll_lines.1 + y);
+            }
+            SubmitInput::ScrollPixels(x, y) => {
+                self.scroll_pixels = (self.scroll_pixels.0 + x, self.scroll_pixels.1 + y);
+            }
+            SubmitInput::CursorEntered => {
+                self.cursor_inside = true;
+                self.cursor_entered = true;
+            }
+            SubmitInput::CursorLeft => {
+                self.cursor_inside = false;
+                self.cursor_left = true;
+            }
+            SubmitInput::ScaleFactor(scale_factor) => {
+                self.scale_factor = scale_factor;
+            }
         }
     }
 
@@ -56,27 +93,75 @@ impl Mouse {
         self.released_buttons.contains(&button)
     }
 
+    /// The cursor's position in physical pixels, the last position inside the window when the
+    /// cursor is outside of it.
     pub fn mouse_position(&self) -> (f32, f32) {
         self.position
     }
 
+    /// The cursor's position in logical pixels, for UI laid out independent of the display's
+    /// scale.
+    pub fn logical_mouse_position(&self) -> (f32, f32) {
+        (
+            self.position.0 / self.scale_factor,
+            self.position.1 / self.scale_factor,
+        )
+    }
+
     pub fn mouse_delta(&self) -> (f32, f32) {
         self.delta
     }
+
+    /// The scroll this frame from wheels that scroll in steps, positive is right and up.
+    pub fn scroll_lines(&self) -> (f32, f32) {
+        self.scroll_lines
+    }
+
+    /// The scroll this frame from touchpads and other devices that scroll smoothly, in physical
+    /// pixels.
+    pub fn scroll_pixels(&self) -> (f32, f32) {
+        self.scroll_pixels
+    }
+
+    pub fn is_cursor_inside(&self) -> bool {
+        self.cursor_inside
+    }
+
+    pub fn cursor_entered(&self) -> bool {
+        self.cursor_entered
+    }
+
+    pub fn cursor_left(&self) -> bool {
+        self.cursor_left
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SubmitInput {
     Pressed(Button),
     Released(Button),
+    /// The cursor's position in physical pixels.
     Position(f32, f32),
     Delta(f32, f32),
+    ScrollLines(f32, f32),
+    ScrollPixels(f32, f32),
+    CursorEntered,
+    CursorLeft,
+    ScaleFactor(f32),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Button {
     Left,
     Right,
     Middle,
+    Back,
+    Forward,
+    Other(u16),
 }
 
 impl Button {
@@ -85,7 +170,37 @@ impl Button {
             winit::event::MouseButton::Left => Some(Self::Left),
             winit::event::MouseButton::Right => Some(Self::Right),
             winit::event::MouseButton::Middle => Some(Self::Middle),
-            _ => None,
+            winit::event::MouseButton::Back => Some(Self::Back),
+            winit::event::MouseButton::Forward => Some(Self::Forward),
+            winit::event::MouseButton::Other(button) => Some(Self::Other(*button)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_scroll_and_cursor_focus() {
+        let mut mouse = Mouse::new();
+        mouse.submit_input(SubmitInput::ScaleFactor(2.0));
+        mouse.submit_input(SubmitInput::CursorEntered);
+        mouse.submit_input(SubmitInput::Position(300.0, 100.0));
+        mouse.submit_input(SubmitInput::ScrollLines(0.0, 1.0));
+        mouse.submit_input(SubmitInput::ScrollLines(0.0, 2.0));
+        mouse.submit_input(SubmitInput::ScrollPixels(4.0, 0.0));
+
+        assert_eq!(mouse.mouse_position(), (300.0, 100.0));
+        assert_eq!(mouse.logical_mouse_position(), (150.0, 50.0));
+        assert_eq!(mouse.scroll_lines(), (0.0, 3.0));
+        assert_eq!(mouse.scroll_pixels(), (4.0, 0.0));
+        assert!(mouse.is_cursor_inside() && mouse.cursor_entered());
+
+        mouse.clear_inputs();
+        mouse.submit_input(SubmitInput::CursorLeft);
+        assert_eq!(mouse.scroll_lines(), (0.0, 0.0));
+        assert!(!mouse.is_cursor_inside() && !mouse.cursor_entered() && mouse.cursor_left());
+        assert_eq!(mouse.mouse_position(), (300.0, 100.0));
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use crate::{
+    app::App,
+    engine::{
+        plugin::{CorePlugin, Plugin, PluginId},
+        schedule::{IntoSystemConfig, Stage},
+    },
+};
+
+use super::{gamepad::GilrsGamepadBackend, mapper::InputMap, recording::InputRecorder, Input};
+
+/// Where rebound controls are loaded from, the default controls are used without it.
+pub const INPUT_MAP_PATH: &str = "input.ron";
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        let mut input = Input::new();
+        if Path::new(INPUT_MAP_PATH).exists() {
+            let input_map = InputMap::load(INPUT_MAP_PATH)
+                .unwrap_or_else(|err| panic!("Failed to load {}: {}", INPUT_MAP_PATH, err));
+            input.set_input_map(input_map);
+        }
+        // Headless runs only get input from replays.
+        if !app.is_headless() {
+            let gamepad_backend = GilrsGamepadBackend::new()
+                .unwrap_or_else(|err| panic!("Failed to start gamepad input: {}", err));
+            input.set_gamepad_backend(Box::new(gamepad_backend));
+        }
+        app.insert_resource(input)
+            .insert_resource(InputRecorder::new());
+
+        app.add_system(Stage::PreUpdate, Input::poll_gamepads)
+            .add_system(Stage::PreUpdate, InputRecorder::replay_frame.before("time"));
+
+        // Post render clear
+        app.add_system(
+            Stage::Cleanup,
+            InputRecorder::record_frame.label("input_recording"),
+        )
+        .add_system(Stage::Cleanup, Input::clear_inputs.after("input_recording"));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<CorePlugin>()]
+    }
+}
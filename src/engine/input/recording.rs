@@ -0,0 +1,251 @@
+use std::{path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use voxei_macros::Resource;
+
+use crate::engine::{
+    common::time::Time,
+    resource::{Res, ResMut},
+};
+
+use super::{mapper::InputFileError, Input, InputEvent};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub delta_time: Duration,
+    /// The inputs in the order they were submitted, before the frame's systems ran.
+    pub inputs: Vec<InputEvent>,
+}
+
+/// The input of every frame of a session, replaying it with the same starting state reproduces
+/// the session.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    pub fn from_ron(ron: &str) -> Result<Self, InputFileError> {
+        ron::from_str(ron).map_err(InputFileError::Parse)
+    }
+
+    pub fn to_ron(&self) -> Result<String, InputFileError> {
+        ron::ser::to_string(self).map_err(InputFileError::Serialize)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputFileError> {
+        let ron = std::fs::read_to_string(path).map_err(InputFileError::Io)?;
+        Self::from_ron(&ron)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), InputFileError> {
+        std::fs::write(path, self.to_ron()?).map_err(InputFileError::Io)
+    }
+}
+
+enum RecorderState {
+    Idle,
+    Recording(InputRecording),
+    Replaying {
+        recording: InputRecording,
+        next_frame: usize,
+        /// `Time`'s manual delta time from before the replay, set on the first replayed frame.
+        previous_delta_time: Option<Option<Duration>>,
+    },
+}
+
+/// Records the input and frame times of a session or replays a recording through `Input`.
+#[derive(Resource)]
+pub struct InputRecorder {
+    state: RecorderState,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            state: RecorderState::Idle,
+        }
+    }
+
+    /// Starts recording from the next frame.
+    pub fn start_recording(&mut self, input: &mut Input) {
+        input.start_recording();
+        self.state = RecorderState::Recording(InputRecording::default());
+    }
+
+    /// Returns the frames recorded so far, None if the recorder wasn't recording.
+    pub fn stop_recording(&mut self, input: &mut Input) -> Option<InputRecording> {
+        match std::mem::replace(&mut self.state, RecorderState::Idle) {
+            RecorderState::Recording(recording) => {
+                input.stop_recording();
+                Some(recording)
+            }
+            state => {
+                self.state = state;
+                None
+            }
+        }
+    }
+
+    /// Replays the recording from the next frame, live input is ignored until it finishes.
+    pub fn start_replay(&mut self, recording: InputRecording, input: &mut Input) {
+        input.stop_recording();
+        input.set_live_input_enabled(false);
+        self.state = RecorderState::Replaying {
+            recording,
+            next_frame: 0,
+            previous_delta_time: None,
+        };
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, RecorderState::Recording(_))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.state, RecorderState::Replaying { .. })
+    }
+
+    /// Stores the frame's input and delta time, runs at the end of the frame.
+    pub fn record_frame(
+        mut recorder: ResMut<InputRecorder>,
+        mut input: ResMut<Input>,
+        time: Res<Time>,
+    ) {
+        if let RecorderState::Recording(recording) = &mut recorder.state {
+            recording.frames.push(RecordedFrame {
+                delta_time: time.delta_time(),
+                inputs: input.take_recorded(),
+            });
+        }
+    }
+
+    /// Submits the next recorded frame's input and steps time by its delta, runs before
+    /// `Time::update`.
+    pub fn replay_frame(
+        mut recorder: ResMut<InputRecorder>,
+        mut input: ResMut<Input>,
+        mut time: ResMut<Time>,
+    ) {
+        let RecorderState::Replaying {
+            recording,
+            next_frame,
+            previous_delta_time,
+        } = &mut recorder.state
+        else {
+            return;
+        };
+        let previous_delta_time = *previous_delta_time.get_or_insert(time.manual_delta_time());
+
+        let Some(frame) = recording.frames.get_mut(*next_frame) else {
+            time.set_manual_delta_time(previous_delta_time);
+            input.set_live_input_enabled(true);
+            recorder.state = RecorderState::Idle;
+            return;
+        };
+        *next_frame += 1;
+
+        time.set_manual_delta_time(Some(frame.delta_time));
+        for event in frame.inputs.drain(..) {
+            input.submit(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        input::keyboard::{Key, SubmitInput},
+        resource::ResourceBank,
+        schedule::{IntoSystemConfig, Schedule, Stage},
+    };
+    use voxei_macros::Resource;
+
+    #[derive(Resource)]
+    struct Jumps(Vec<Duration>);
+
+    fn schedule() -> Schedule {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::PreUpdate, Time::update.label("time"))
+            .add_system(Stage::PreUpdate, InputRecorder::replay_frame.before("time"))
+            .add_system(
+                Stage::Update,
+                |input: Res<Input>, time: Res<Time>, mut jumps: ResMut<Jumps>| {
+                    if input.action_pressed("jump") {
+                        jumps.0.push(time.total_duration());
+                    }
+                },
+            )
+            .add_system(Stage::Cleanup, InputRecorder::record_frame.label("record"))
+            .add_system(Stage::Cleanup, Input::clear_inputs.after("record"));
+        schedule
+    }
+
+    fn resource_bank() -> ResourceBank {
+        let mut resource_bank = ResourceBank::new();
+        let mut time = Time::new();
+        time.set_manual_delta_time(Some(Duration::from_millis(10)));
+        resource_bank.insert(time);
+        resource_bank.insert(Input::new());
+        resource_bank.insert(InputRecorder::new());
+        resource_bank.insert(Jumps(Vec::new()));
+        resource_bank
+    }
+
+    #[test]
+    fn test_replay_reproduces_the_session() {
+        let mut schedule = schedule();
+        let mut resource_bank = resource_bank();
+        resource_bank.resource_scope(|resource_bank, mut recorder: ResMut<InputRecorder>| {
+            recorder.start_recording(&mut resource_bank.get_resource_mut::<Input>());
+        });
+
+        for frame in 0..6 {
+            if frame == 2 {
+                resource_bank
+                    .get_resource_mut::<Time>()
+                    .set_manual_delta_time(Some(Duration::from_millis(30)));
+            }
+            if frame % 2 == 1 {
+                let mut input = resource_bank.get_resource_mut::<Input>();
+                input.submit(InputEvent::Keyboard(SubmitInput::Pressed(Key::Space)));
+                input.submit(InputEvent::Keyboard(SubmitInput::Released(Key::Space)));
+            }
+            schedule.run(&mut resource_bank);
+        }
+        let recording =
+            resource_bank.resource_scope(|resource_bank, mut recorder: ResMut<InputRecorder>| {
+                recorder
+                    .stop_recording(&mut resource_bank.get_resource_mut::<Input>())
+                    .unwrap()
+            });
+        let recorded_jumps = resource_bank.remove::<Jumps>().unwrap().0;
+        assert_eq!(recording.frames.len(), 6);
+        assert_eq!(recorded_jumps.len(), 3);
+
+        let recording = InputRecording::from_ron(&recording.to_ron().unwrap()).unwrap();
+        let mut resource_bank = self::resource_bank();
+        resource_bank.resource_scope(|resource_bank, mut recorder: ResMut<InputRecorder>| {
+            recorder.start_replay(recording, &mut resource_bank.get_resource_mut::<Input>());
+        });
+        for _ in 0..7 {
+            // Live input is ignored while replaying.
+            let mut input = resource_bank.get_resource_mut::<Input>();
+            input.handle_winit_window_event(winit::event::WindowEvent::Ime(
+                winit::event::Ime::Commit("a".to_owned()),
+            ));
+            assert_eq!(input.text(), "");
+            drop(input);
+            schedule.run(&mut resource_bank);
+        }
+
+        assert_eq!(resource_bank.get_resource::<Jumps>().0, recorded_jumps);
+        assert!(!resource_bank.get_resource::<InputRecorder>().is_replaying());
+        assert_eq!(
+            resource_bank.get_resource::<Time>().manual_delta_time(),
+            Some(Duration::from_millis(10))
+        );
+    }
+}
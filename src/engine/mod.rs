@@ -1,12 +1,18 @@
 pub mod assets;
+pub mod change;
+pub mod command;
 pub mod common;
 pub mod ecs;
+pub mod event;
+pub mod executor;
 pub mod geometry;
 pub mod graphics;
 pub mod input;
 pub mod model;
 pub mod physics;
+pub mod plugin;
 pub mod resource;
+pub mod schedule;
 pub mod system;
 pub mod ui;
 pub mod voxel;
@@ -0,0 +1,525 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
+
+use nalgebra::{Isometry3, Vector3};
+use voxei_macros::Resource;
+
+use crate::{
+    engine::{
+        common::{time::Time, transform::Transform},
+        ecs::ecs_world::ECSWorld,
+        resource::{Res, ResMut},
+        voxel::{
+            events::{VoxelEditSource, VoxelEvent, VoxelEventReader, VoxelEvents},
+            object::VoxelObject,
+            vox_constants::{BRICK_LENGTH, VOXEL_WORLD_LENGTH},
+            vox_world::VoxelWorld,
+        },
+    },
+    settings::Settings,
+};
+
+use super::{
+    rigid_body::{Collider, RigidBody},
+    voxel_collision::VoxelOccupancy,
+};
+
+const NEIGHBOUR_OFFSETS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// A group of connected voxels that no longer touches anything outside the searched region.
+pub struct VoxelIsland {
+    pub voxels: Vec<Vector3<i32>>,
+    pub min: Vector3<i32>,
+    pub max: Vector3<i32>,
+}
+
+impl VoxelIsland {
+    /// The size in voxels of the island's bounds.
+    pub fn extent(&self) -> Vector3<u32> {
+        (self.max - self.min).map(|x| x as u32 + 1)
+    }
+}
+
+/// Flood fills from the solid voxels around the edited region, anything that can't reach the
+/// edge of the region expanded by `search_radius` is floating. Fills that grow past
+/// `max_island_voxels` are treated as supported so large structures don't stall the search.
+pub fn find_floating_islands(
+    world: &impl VoxelOccupancy,
+    edited_min: Vector3<i32>,
+    edited_max: Vector3<i32>,
+    search_radius: i32,
+    max_island_voxels: usize,
+) -> Vec<VoxelIsland> {
+    let bounds_min = edited_min.add_scalar(-search_radius);
+    let bounds_max = edited_max.add_scalar(search_radius);
+    let in_bounds =
+        |pos: &Vector3<i32>| (0..3).all(|i| pos[i] >= bounds_min[i] && pos[i] <= bounds_max[i]);
+    let mut empty_bricks = HashMap::new();
+    let mut is_solid = |pos: Vector3<i32>| {
+        let brick_pos = pos.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+        let is_brick_empty = *empty_bricks
+            .entry(brick_pos)
+            .or_insert_with(|| world.is_brick_empty(brick_pos));
+        !is_brick_empty && world.is_voxel_solid(pos)
+    };
+
+    // Voxels a finished fill proved to be connected to support, a fill that stopped early still
+    // reached support so everything it visited is supported too.
+    let mut supported = HashSet::new();
+    let mut floating = HashSet::new();
+    let mut islands = Vec::new();
+    for x in (edited_min.x - 1)..=(edited_max.x + 1) {
+        for y in (edited_min.y - 1)..=(edited_max.y + 1) {
+            for z in (edited_min.z - 1)..=(edited_max.z + 1) {
+                let seed = Vector3::new(x, y, z);
+                if supported.contains(&seed) || floating.contains(&seed) || !is_solid(seed) {
+                    continue;
+                }
+
+                let mut island = vec![seed];
+                let mut visited = HashSet::from([seed]);
+                let mut queue = VecDeque::from([seed]);
+                let mut is_supported = false;
+                'fill: while let Some(pos) = queue.pop_front() {
+                    for offset in NEIGHBOUR_OFFSETS {
+                        let neighbour = pos + Vector3::from(offset);
+                        if visited.contains(&neighbour) || !is_solid(neighbour) {
+                            continue;
+                        }
+                        if supported.contains(&neighbour)
+                            || !in_bounds(&neighbour)
+                            || island.len() >= max_island_voxels
+                        {
+                            is_supported = true;
+                            break 'fill;
+                        }
+
+                        visited.insert(neighbour);
+                        island.push(neighbour);
+                        queue.push_back(neighbour);
+                    }
+                }
+
+                if is_supported {
+                    supported.extend(island);
+                } else {
+                    floating.extend(island.iter().copied());
+                    let min = island.iter().fold(seed, |min, pos| min.inf(pos));
+                    let max = island.iter().fold(seed, |max, pos| max.sup(pos));
+                    islands.push(VoxelIsland {
+                        voxels: island,
+                        min,
+                        max,
+                    });
+                }
+            }
+        }
+    }
+
+    islands
+}
+
+/// Marks a voxel object spawned by `Destruction`, it's despawned once it expires.
+pub struct Debris {
+    spawned_at: Duration,
+}
+
+/// Turns voxels cut off from the ground by edits into falling voxel objects.
+#[derive(Resource)]
+pub struct Destruction {
+    reader: VoxelEventReader,
+    /// How far in voxels past an edit to look for support.
+    pub search_radius: i32,
+    pub max_island_voxels: usize,
+    /// The mass of a single voxel of debris.
+    pub voxel_mass: f32,
+    /// How long debris lasts before it's despawned.
+    pub debris_lifetime: Duration,
+    /// The most debris alive at once, the oldest is despawned first. Each piece holds a brick
+    /// index block that terrain chunks are also allocated from.
+    pub max_debris: usize,
+}
+
+impl Destruction {
+    pub fn new(events: &mut VoxelEvents) -> Self {
+        Self {
+            reader: events.subscribe(),
+            search_radius: 16,
+            max_island_voxels: 8192,
+            voxel_mass: 1.0,
+            debris_lifetime: Duration::from_secs(30),
+            max_debris: 256,
+        }
+    }
+
+    pub fn update(
+        mut ecs_world: ResMut<ECSWorld>,
+        mut vox_world: ResMut<VoxelWorld>,
+        mut events: ResMut<VoxelEvents>,
+        destruction: Res<Destruction>,
+        time: Res<Time>,
+    ) {
+        for event in events.read(destruction.reader) {
+            let VoxelEvent::RegionEdited { aabb, source } = event else {
+                continue;
+            };
+            // Our own edits only remove islands so they can't cut anything else off.
+            if matches!(
+                source,
+                VoxelEditSource::Generation | VoxelEditSource::Physics
+            ) {
+                continue;
+            }
+
+            let edited_min = (aabb.min().coords / VOXEL_WORLD_LENGTH).map(|x| x.floor() as i32);
+            let edited_max = (aabb.max().coords / VOXEL_WORLD_LENGTH).map(|x| x.ceil() as i32 - 1);
+            let islands = find_floating_islands(
+                vox_world.dyn_world(),
+                edited_min,
+                edited_max,
+                destruction.search_radius,
+                destruction.max_island_voxels,
+            );
+            for island in islands {
+                destruction.spawn_debris(
+                    &mut ecs_world,
+                    &mut vox_world,
+                    island,
+                    time.total_duration(),
+                );
+            }
+        }
+    }
+
+    /// Despawns debris older than `debris_lifetime` and the oldest debris past `max_debris` or
+    /// past what the renderer draws, freeing their voxel objects.
+    pub fn despawn_debris(
+        mut ecs_world: ResMut<ECSWorld>,
+        mut vox_world: ResMut<VoxelWorld>,
+        destruction: Res<Destruction>,
+        time: Res<Time>,
+        settings: Res<Settings>,
+    ) {
+        let mut debris = ecs_world
+            .query::<&Debris>()
+            .iter()
+            .map(|(entity, debris)| (entity, debris.spawned_at))
+            .collect::<Vec<_>>();
+        debris.sort_by_key(|(_, spawned_at)| Reverse(*spawned_at));
+
+        let max_debris = destruction
+            .max_debris
+            .min(settings.voxel_object_max_count as usize);
+        let now = time.total_duration();
+        for (i, (entity, spawned_at)) in debris.into_iter().enumerate() {
+            if i < max_debris && now.saturating_sub(spawned_at) < destruction.debris_lifetime {
+                continue;
+            }
+
+            if let Ok(object) = ecs_world.remove_one::<VoxelObject>(entity) {
+                object.destroy(vox_world.dyn_world_mut());
+            }
+            ecs_world
+                .despawn(entity)
+                .expect("Debris was queried this frame");
+        }
+    }
+
+    /// Moves the island's voxels out of the terrain into a dynamic voxel object. Islands bigger
+    /// than a voxel object are left in place.
+    fn spawn_debris(
+        &self,
+        ecs_world: &mut ECSWorld,
+        vox_world: &mut VoxelWorld,
+        island: VoxelIsland,
+        spawned_at: Duration,
+    ) {
+        let extent = island.extent();
+        if extent.max() > VoxelObject::MAX_EXTENT {
+            return;
+        }
+
+        let dyn_world = vox_world.dyn_world_mut();
+        let albedos = island
+            .voxels
+            .iter()
+            .filter_map(|pos| {
+                let albedo = dyn_world.world_voxel_albedo(*pos)?;
+                Some(((pos - island.min).map(|x| x as u32), albedo))
+            })
+            .collect::<HashMap<_, _>>();
+        let Some(object) = VoxelObject::new(dyn_world, extent, |pos| albedos.get(&pos).copied())
+        else {
+            return;
+        };
+        dyn_world.set_world_voxels(
+            island.voxels.iter().map(|pos| (*pos, None)),
+            VoxelEditSource::Physics,
+        );
+
+        let half_extent = extent.map(|x| x as f32 * VOXEL_WORLD_LENGTH / 2.0);
+        let center = island.min.map(|x| x as f32 * VOXEL_WORLD_LENGTH) + half_extent;
+        let mut transform = Transform::new();
+        transform.isometry = Isometry3::translation(center.x, center.y, center.z);
+
+        ecs_world.spawn((
+            object,
+            transform,
+            RigidBody::new_dynamic(island.voxels.len() as f32 * self.voxel_mass),
+            Collider::new_box(half_extent),
+            Debris { spawned_at },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{
+            common::time::Time,
+            resource::ResourceBank,
+            schedule::{IntoSystemConfig, Schedule, Stage},
+            voxel::{
+                chunk_generator::GeneratedChunk,
+                vox_world::{ChunkRadius, DynChunkPos},
+            },
+        },
+        settings::Settings,
+    };
+
+    struct TestWorld(HashSet<Vector3<i32>>);
+
+    impl TestWorld {
+        /// A ground plane below y = 0 with a pillar on top.
+        fn with_pillar(height: i32) -> Self {
+            let mut voxels = HashSet::new();
+            for x in -20..=20 {
+                for z in -20..=20 {
+                    voxels.insert(Vector3::new(x, -1, z));
+                }
+            }
+            for y in 0..height {
+                voxels.insert(Vector3::new(0, y, 0));
+            }
+            Self(voxels)
+        }
+    }
+
+    impl VoxelOccupancy for TestWorld {
+        fn is_brick_empty(&self, brick_pos: Vector3<i32>) -> bool {
+            !self
+                .0
+                .iter()
+                .any(|pos| pos.map(|x| x.div_euclid(BRICK_LENGTH as i32)) == brick_pos)
+        }
+
+        fn is_voxel_solid(&self, voxel_pos: Vector3<i32>) -> bool {
+            self.0.contains(&voxel_pos)
+        }
+    }
+
+    #[test]
+    fn test_connected_voxels_are_supported() {
+        let world = TestWorld::with_pillar(6);
+        let edited = Vector3::new(1, 3, 0);
+
+        assert!(find_floating_islands(&world, edited, edited, 8, 1000).is_empty());
+    }
+
+    #[test]
+    fn test_cut_pillar_top_floats() {
+        let mut world = TestWorld::with_pillar(6);
+        let edited = Vector3::new(0, 2, 0);
+        world.0.remove(&edited);
+
+        let islands = find_floating_islands(&world, edited, edited, 8, 1000);
+        assert_eq!(islands.len(), 1);
+        assert_eq!(islands[0].voxels.len(), 3);
+        assert_eq!(islands[0].min, Vector3::new(0, 3, 0));
+        assert_eq!(islands[0].extent(), Vector3::new(1, 3, 1));
+    }
+
+    #[test]
+    fn test_islands_reaching_the_search_edge_are_supported() {
+        let mut world = TestWorld::with_pillar(20);
+        let edited = Vector3::new(0, 2, 0);
+        world.0.remove(&edited);
+
+        // The pillar goes past the searched region so it could be held up by anything.
+        assert!(find_floating_islands(&world, edited, edited, 8, 1000).is_empty());
+        assert_eq!(
+            find_floating_islands(&world, edited, edited, 20, 1000).len(),
+            1
+        );
+        // Too big to count as debris.
+        assert!(find_floating_islands(&world, edited, edited, 20, 10).is_empty());
+    }
+
+    #[test]
+    fn test_fills_stopped_early_still_support_their_voxels() {
+        // A ground plane far below a column that holds up a bar over the edited voxel.
+        let mut voxels = HashSet::new();
+        for x in -20..=20 {
+            for z in -20..=20 {
+                voxels.insert(Vector3::new(x, -10, z));
+            }
+        }
+        for y in -9..=1 {
+            voxels.insert(Vector3::new(-1, y, 0));
+        }
+        voxels.insert(Vector3::new(0, 1, 0));
+        voxels.insert(Vector3::new(1, 1, 0));
+        let world = TestWorld(voxels);
+
+        // The column's fill leaves the search region and stops after reaching the bar's first
+        // voxel but before its second, which is seeded later.
+        let edited = Vector3::new(0, 0, 0);
+        assert!(find_floating_islands(&world, edited, edited, 3, 1000).is_empty());
+    }
+
+    /// A voxel world with a floor reaching past the search radius and a pillar on top, with the
+    /// systems that turn edits into debris.
+    fn pillar_world() -> (ResourceBank, Schedule) {
+        let settings = Settings {
+            chunk_render_distance: ChunkRadius::new(1),
+            ..Default::default()
+        };
+        let mut vox_world = VoxelWorld::new(&settings);
+        let dyn_world = vox_world.dyn_world_mut();
+        // Loaded empty so the unloaded chunks, which count as solid, don't hold anything up.
+        for index in 0..settings.chunk_render_distance.volume() {
+            let dyn_pos = DynChunkPos::from_index(index, settings.chunk_render_distance);
+            let chunk_position = dyn_world.world_chunk_pos(dyn_pos);
+            dyn_world.set_generated_chunk(
+                dyn_pos,
+                GeneratedChunk {
+                    chunk_position,
+                    is_empty: true,
+                    voxel_data: None,
+                },
+            );
+        }
+        // A floor reaching past the search radius with a pillar on top.
+        let floor = (-20..=20).flat_map(|x| (-20..=20).map(move |z| Vector3::new(x, 0, z)));
+        let pillar = (1..=8).map(|y| Vector3::new(0, y, 0));
+        dyn_world.set_world_voxels(
+            floor
+                .chain(pillar)
+                .map(|pos| (pos, Some(Vector3::new(0.5, 0.5, 0.5)))),
+            VoxelEditSource::Generation,
+        );
+
+        let mut events = VoxelEvents::new();
+        let destruction = Destruction::new(&mut events);
+        let mut time = Time::new();
+        time.set_manual_delta_time(Some(Duration::from_millis(10)));
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(time);
+        resource_bank.insert(ECSWorld::new());
+        resource_bank.insert(vox_world);
+        resource_bank.insert(events);
+        resource_bank.insert(destruction);
+        resource_bank.insert(settings);
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::PreUpdate, Time::update)
+            .add_system(
+                Stage::Update,
+                VoxelWorld::publish_events.label("voxel_events"),
+            )
+            .add_system(
+                Stage::Update,
+                Destruction::update
+                    .label("destruction")
+                    .after("voxel_events"),
+            )
+            .add_system(
+                Stage::Update,
+                Destruction::despawn_debris.after("destruction"),
+            );
+
+        (resource_bank, schedule)
+    }
+
+    fn used_block_count(resource_bank: &ResourceBank) -> u32 {
+        let vox_world = resource_bank.get_resource::<VoxelWorld>();
+        let pool = vox_world.dyn_world().brick_index_pool();
+        pool.block_count() - pool.free_block_count()
+    }
+
+    fn debris_count(resource_bank: &ResourceBank) -> usize {
+        resource_bank
+            .get_resource::<ECSWorld>()
+            .query::<(&Debris, &VoxelObject, &RigidBody)>()
+            .iter()
+            .count()
+    }
+
+    #[test]
+    fn test_player_edit_detaches_debris() {
+        let (mut resource_bank, mut schedule) = pillar_world();
+        resource_bank
+            .get_resource_mut::<VoxelWorld>()
+            .break_voxels(Vector3::new(0, 4, 0), 1);
+        schedule.run(&mut resource_bank);
+
+        let vox_world = resource_bank.get_resource::<VoxelWorld>();
+        let is_pillar_set = |y| {
+            vox_world
+                .dyn_world()
+                .is_world_voxel_set(Vector3::new(0, y, 0))
+        };
+        assert!(is_pillar_set(1) && is_pillar_set(2));
+        // Broken by the player, then moved into the debris.
+        assert!((3..=8).all(|y| !is_pillar_set(y)));
+
+        assert_eq!(debris_count(&resource_bank), 1);
+    }
+
+    #[test]
+    fn test_expired_debris_frees_its_block() {
+        let (mut resource_bank, mut schedule) = pillar_world();
+        let used_blocks = used_block_count(&resource_bank);
+
+        resource_bank
+            .get_resource_mut::<VoxelWorld>()
+            .break_voxels(Vector3::new(0, 4, 0), 1);
+        schedule.run(&mut resource_bank);
+        assert_eq!(debris_count(&resource_bank), 1);
+        assert_eq!(used_block_count(&resource_bank), used_blocks + 1);
+
+        let debris_lifetime = resource_bank.get_resource::<Destruction>().debris_lifetime;
+        resource_bank
+            .get_resource_mut::<Time>()
+            .set_manual_delta_time(Some(debris_lifetime));
+        schedule.run(&mut resource_bank);
+        assert_eq!(debris_count(&resource_bank), 0);
+        assert_eq!(used_block_count(&resource_bank), used_blocks);
+    }
+
+    #[test]
+    fn test_debris_past_the_max_is_despawned() {
+        let (mut resource_bank, mut schedule) = pillar_world();
+        resource_bank.get_resource_mut::<Destruction>().max_debris = 0;
+        let used_blocks = used_block_count(&resource_bank);
+
+        resource_bank
+            .get_resource_mut::<VoxelWorld>()
+            .break_voxels(Vector3::new(0, 4, 0), 1);
+        schedule.run(&mut resource_bank);
+        assert_eq!(debris_count(&resource_bank), 0);
+        assert_eq!(used_block_count(&resource_bank), used_blocks);
+    }
+}
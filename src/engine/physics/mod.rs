@@ -1 +1,5 @@
+pub mod destruction;
 pub mod phy_world;
+pub mod plugin;
+pub mod rigid_body;
+pub mod voxel_collision;
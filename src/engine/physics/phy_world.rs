@@ -1,4 +1,452 @@
+use std::collections::HashMap;
+
+use hecs::Entity;
+use nalgebra::{Isometry3, Vector3};
 use voxei_macros::Resource;
 
+use crate::engine::{
+    common::{time::Time, transform::Transform},
+    ecs::ecs_world::ECSWorld,
+    geometry::{
+        bvh::Bvh,
+        shapes::{oriented_box::OrientedBox, Penetration, Shape},
+    },
+    resource::{Res, ResMut},
+    voxel::vox_world::VoxelWorld,
+};
+
+use super::{
+    rigid_body::{Collider, RigidBody},
+    voxel_collision::{sweep_aabb, VoxelOccupancy},
+};
+
+/// Overlap allowed between bodies before positional correction kicks in, stops resting bodies
+/// from jittering.
+const PENETRATION_SLOP: f32 = 0.005;
+/// The fraction of the remaining overlap corrected each step.
+const CORRECTION_PERCENT: f32 = 0.8;
+/// Bounces slower than this come to rest instead.
+const REST_SPEED: f32 = 0.1;
+
+/// A copy of a physics entity's state so stepping doesn't hold any ECS borrows.
+#[derive(Clone)]
+pub struct PhysicsBody {
+    pub entity: Entity,
+    pub transform: Transform,
+    pub rigid_body: Option<RigidBody>,
+    pub collider: Option<Collider>,
+}
+
+impl PhysicsBody {
+    fn inverse_mass(&self) -> f32 {
+        self.rigid_body
+            .filter(|rigid_body| rigid_body.is_dynamic())
+            .map_or(0.0, |rigid_body| rigid_body.inverse_mass)
+    }
+
+    fn velocity(&self) -> Vector3<f32> {
+        self.rigid_body
+            .map_or(Vector3::zeros(), |rigid_body| rigid_body.velocity)
+    }
+
+    fn shape(&self) -> Option<OrientedBox> {
+        self.collider
+            .map(|collider| collider.shape(&self.transform))
+    }
+
+    /// Moves the body, dynamic bodies with a collider are stopped by the terrain. Returns the axes
+    /// that hit the terrain.
+    fn translate(
+        &mut self,
+        translation: Vector3<f32>,
+        terrain: &impl VoxelOccupancy,
+    ) -> Vector3<bool> {
+        let is_dynamic = self
+            .rigid_body
+            .is_some_and(|rigid_body| rigid_body.is_dynamic());
+        let (Some(shape), true) = (self.shape(), is_dynamic) else {
+            self.transform.isometry.translation.vector += translation;
+            return Vector3::new(false, false, false);
+        };
+
+        let result = sweep_aabb(terrain, &shape.aabb(), translation);
+        self.transform.isometry.translation.vector += result.motion;
+        result.collided
+    }
+}
+
+/// A contact found between two bodies during the last step.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub a: Entity,
+    pub b: Entity,
+    /// Points from `a` towards `b`.
+    pub normal: Vector3<f32>,
+    pub depth: f32,
+}
+
+/// Steps every entity with a `RigidBody` or `Collider`, runs in the fixed update so it's
+/// independent of the frame's delta time.
 #[derive(Resource)]
-pub struct PhysicsWorld {}
+pub struct PhysicsWorld {
+    gravity: Vector3<f32>,
+    contacts: Vec<Contact>,
+    /// The isometry of each body before the last step, for interpolating between steps.
+    previous_isometries: HashMap<Entity, Isometry3<f32>>,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            contacts: Vec::new(),
+            previous_isometries: HashMap::new(),
+        }
+    }
+
+    pub fn fixed_update(
+        ecs_world: ResMut<ECSWorld>,
+        mut physics_world: ResMut<PhysicsWorld>,
+        time: Res<Time>,
+        vox_world: Res<VoxelWorld>,
+    ) {
+        let mut bodies = ecs_world
+            .query::<(&Transform, Option<&RigidBody>, Option<&Collider>)>()
+            .iter()
+            .filter(|(_, (_, rigid_body, collider))| rigid_body.is_some() || collider.is_some())
+            .map(|(entity, (transform, rigid_body, collider))| PhysicsBody {
+                entity,
+                transform: transform.clone(),
+                rigid_body: rigid_body.copied(),
+                collider: collider.copied(),
+            })
+            .collect::<Vec<_>>();
+
+        physics_world.previous_isometries = bodies
+            .iter()
+            .filter(|body| body.rigid_body.is_some())
+            .map(|body| (body.entity, body.transform.isometry))
+            .collect();
+        physics_world.step(
+            &mut bodies,
+            vox_world.dyn_world(),
+            time.fixed_delta_time().as_secs_f32(),
+        );
+
+        // Static colliders never move so only bodies are written back.
+        for body in bodies {
+            let Some(rigid_body) = body.rigid_body else {
+                continue;
+            };
+            if let Ok(mut transform) = ecs_world.get::<&mut Transform>(body.entity) {
+                *transform = body.transform;
+            }
+            if let Ok(mut ecs_rigid_body) = ecs_world.get::<&mut RigidBody>(body.entity) {
+                *ecs_rigid_body = rigid_body;
+            }
+        }
+    }
+
+    /// Advances the bodies by the timestep in seconds.
+    pub fn step(&mut self, bodies: &mut [PhysicsBody], terrain: &impl VoxelOccupancy, dt: f32) {
+        for body in bodies.iter_mut() {
+            self.integrate(body, terrain, dt);
+        }
+
+        self.contacts.clear();
+        for (a, b, penetration) in Self::find_contacts(bodies) {
+            Self::resolve_contact(bodies, a, b, &penetration, terrain);
+            self.contacts.push(Contact {
+                a: bodies[a].entity,
+                b: bodies[b].entity,
+                normal: penetration.normal,
+                depth: penetration.depth,
+            });
+        }
+    }
+
+    /// Semi-implicit euler, the velocity is updated first then moves the body.
+    fn integrate(&self, body: &mut PhysicsBody, terrain: &impl VoxelOccupancy, dt: f32) {
+        let Some(mut rigid_body) = body.rigid_body else {
+            return;
+        };
+
+        if rigid_body.is_dynamic() {
+            rigid_body.velocity += self.gravity * rigid_body.gravity_scale * dt;
+        }
+
+        let collided = body.translate(rigid_body.velocity * dt, terrain);
+        for axis in 0..3 {
+            if !collided[axis] {
+                continue;
+            }
+
+            let normal_speed = rigid_body.velocity[axis].abs();
+            rigid_body.velocity[axis] *= -rigid_body.restitution;
+            if rigid_body.velocity[axis].abs() < REST_SPEED {
+                rigid_body.velocity[axis] = 0.0;
+            }
+
+            // Coulomb friction, the tangential speed lost is bounded by the normal speed lost.
+            let normal_change = normal_speed + rigid_body.velocity[axis].abs();
+            let mut tangent = rigid_body.velocity;
+            tangent[axis] = 0.0;
+            let tangent_speed = tangent.norm();
+            if tangent_speed > 0.0 {
+                let friction_speed = (rigid_body.friction * normal_change).min(tangent_speed);
+                rigid_body.velocity -= tangent * (friction_speed / tangent_speed);
+            }
+        }
+
+        body.rigid_body = Some(rigid_body);
+    }
+
+    /// Finds the overlapping pairs using a BVH over the collider AABBs then SAT between the
+    /// colliders, pairs where neither body can move are skipped.
+    fn find_contacts(bodies: &[PhysicsBody]) -> Vec<(usize, usize, Penetration)> {
+        let colliders = bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, body)| body.shape().map(|shape| (i, shape)))
+            .collect::<Vec<_>>();
+        let aabbs = colliders
+            .iter()
+            .map(|(_, shape)| shape.aabb())
+            .collect::<Vec<_>>();
+        let bvh = Bvh::build(&aabbs);
+
+        let mut contacts = Vec::new();
+        for (collider_index, (a, shape_a)) in colliders.iter().enumerate() {
+            for other_index in bvh.aabb_candidates(&aabbs[collider_index]) {
+                let (b, shape_b) = &colliders[other_index as usize];
+                if other_index as usize <= collider_index
+                    || bodies[*a].inverse_mass() + bodies[*b].inverse_mass() == 0.0
+                {
+                    continue;
+                }
+
+                if let Some(penetration) = shape_a.penetration(shape_b) {
+                    contacts.push((*a, *b, penetration));
+                }
+            }
+        }
+
+        contacts
+    }
+
+    /// Pushes the bodies apart by their inverse masses then applies the impulses along the
+    /// contact normal and friction along the tangent.
+    fn resolve_contact(
+        bodies: &mut [PhysicsBody],
+        a: usize,
+        b: usize,
+        penetration: &Penetration,
+        terrain: &impl VoxelOccupancy,
+    ) {
+        let inverse_mass_a = bodies[a].inverse_mass();
+        let inverse_mass_b = bodies[b].inverse_mass();
+        let total_inverse_mass = inverse_mass_a + inverse_mass_b;
+        let normal = penetration.normal;
+
+        let correction =
+            normal * (penetration.depth - PENETRATION_SLOP).max(0.0) * CORRECTION_PERCENT
+                / total_inverse_mass;
+        bodies[a].translate(-correction * inverse_mass_a, terrain);
+        bodies[b].translate(correction * inverse_mass_b, terrain);
+
+        let relative_velocity = bodies[b].velocity() - bodies[a].velocity();
+        let normal_speed = relative_velocity.dot(&normal);
+        if normal_speed >= 0.0 {
+            return;
+        }
+
+        let (restitution, friction) = match (bodies[a].rigid_body, bodies[b].rigid_body) {
+            (Some(rigid_a), Some(rigid_b)) => (
+                rigid_a.restitution.min(rigid_b.restitution),
+                (rigid_a.friction * rigid_b.friction).sqrt(),
+            ),
+            (Some(rigid_body), None) | (None, Some(rigid_body)) => {
+                (rigid_body.restitution, rigid_body.friction)
+            }
+            (None, None) => return,
+        };
+
+        let normal_impulse = -(1.0 + restitution) * normal_speed / total_inverse_mass;
+        let mut impulse = normal * normal_impulse;
+
+        let tangent_velocity = relative_velocity - normal * normal_speed;
+        let tangent_speed = tangent_velocity.norm();
+        if tangent_speed > 0.0 {
+            let friction_impulse =
+                (tangent_speed / total_inverse_mass).min(friction * normal_impulse);
+            impulse += tangent_velocity * (-friction_impulse / tangent_speed);
+        }
+
+        if let Some(rigid_body) = bodies[a].rigid_body.as_mut() {
+            rigid_body.velocity -= impulse * inverse_mass_a;
+        }
+        if let Some(rigid_body) = bodies[b].rigid_body.as_mut() {
+            rigid_body.velocity += impulse * inverse_mass_b;
+        }
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    pub fn gravity(&self) -> Vector3<f32> {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
+        self.gravity = gravity;
+    }
+
+    /// Blends the body's isometry from before the last step to its current one by `alpha`, see
+    /// `Time::interpolation_alpha`.
+    pub fn interpolated_isometry(
+        &self,
+        entity: Entity,
+        isometry: &Isometry3<f32>,
+        alpha: f32,
+    ) -> Isometry3<f32> {
+        self.previous_isometries
+            .get(&entity)
+            .map_or(*isometry, |previous| previous.lerp_slerp(isometry, alpha))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Isometry3;
+
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// Solid below y = 0.
+    struct Ground;
+
+    impl VoxelOccupancy for Ground {
+        fn is_brick_empty(&self, brick_pos: Vector3<i32>) -> bool {
+            brick_pos.y >= 0
+        }
+
+        fn is_voxel_solid(&self, voxel_pos: Vector3<i32>) -> bool {
+            voxel_pos.y < 0
+        }
+    }
+
+    /// Nothing is solid.
+    struct Sky;
+
+    impl VoxelOccupancy for Sky {
+        fn is_brick_empty(&self, _brick_pos: Vector3<i32>) -> bool {
+            true
+        }
+
+        fn is_voxel_solid(&self, _voxel_pos: Vector3<i32>) -> bool {
+            false
+        }
+    }
+
+    fn body(
+        position: Vector3<f32>,
+        rigid_body: Option<RigidBody>,
+        collider: Option<Collider>,
+    ) -> PhysicsBody {
+        let mut transform = Transform::new();
+        transform.isometry = Isometry3::translation(position.x, position.y, position.z);
+        PhysicsBody {
+            entity: Entity::DANGLING,
+            transform,
+            rigid_body,
+            collider,
+        }
+    }
+
+    fn unit_box(position: Vector3<f32>, rigid_body: Option<RigidBody>) -> PhysicsBody {
+        body(
+            position,
+            rigid_body,
+            Some(Collider::new_box(Vector3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn position(body: &PhysicsBody) -> Vector3<f32> {
+        body.transform.isometry.translation.vector
+    }
+
+    #[test]
+    fn test_free_fall_uses_semi_implicit_euler() {
+        let mut physics_world = PhysicsWorld::new();
+        let mut bodies = [body(
+            Vector3::new(0.0, 10.0, 0.0),
+            Some(RigidBody::new_dynamic(1.0)),
+            None,
+        )];
+        physics_world.step(&mut bodies, &Sky, DT);
+
+        let velocity = bodies[0].rigid_body.unwrap().velocity;
+        assert!((velocity.y + 9.81 * DT).abs() < 0.0001);
+        assert!((position(&bodies[0]).y - (10.0 + velocity.y * DT)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_body_comes_to_rest_on_terrain() {
+        let mut physics_world = PhysicsWorld::new();
+        let mut bodies = [unit_box(
+            Vector3::new(0.5, 5.0, 0.5),
+            Some(RigidBody::new_dynamic(1.0)),
+        )];
+        for _ in 0..180 {
+            physics_world.step(&mut bodies, &Ground, DT);
+        }
+
+        assert!((position(&bodies[0]).y - 0.5).abs() < 0.001);
+        assert_eq!(bodies[0].rigid_body.unwrap().velocity, Vector3::zeros());
+    }
+
+    #[test]
+    fn test_body_rests_on_static_collider() {
+        let mut physics_world = PhysicsWorld::new();
+        let mut bodies = [
+            unit_box(Vector3::new(0.0, 0.0, 0.0), None),
+            unit_box(
+                Vector3::new(0.0, 3.0, 0.0),
+                Some(RigidBody::new_dynamic(1.0)),
+            ),
+        ];
+        for _ in 0..180 {
+            physics_world.step(&mut bodies, &Sky, DT);
+        }
+
+        assert_eq!(position(&bodies[0]), Vector3::zeros());
+        assert!((position(&bodies[1]).y - 1.0).abs() < 0.05);
+        assert!(bodies[1].rigid_body.unwrap().velocity.y.abs() < 0.5);
+        assert_eq!(physics_world.contacts().len(), 1);
+        assert!((physics_world.contacts()[0].normal - Vector3::y()).norm() < 0.0001);
+    }
+
+    #[test]
+    fn test_elastic_collision_swaps_velocities() {
+        let mut physics_world = PhysicsWorld::new();
+        physics_world.set_gravity(Vector3::zeros());
+
+        let mut moving = RigidBody::new_dynamic(1.0);
+        moving.velocity = Vector3::new(6.0, 0.0, 0.0);
+        moving.restitution = 1.0;
+        let mut resting = RigidBody::new_dynamic(1.0);
+        resting.restitution = 1.0;
+        let mut bodies = [
+            unit_box(Vector3::new(0.0, 0.0, 0.0), Some(moving)),
+            unit_box(Vector3::new(1.05, 0.0, 0.0), Some(resting)),
+        ];
+        for _ in 0..10 {
+            physics_world.step(&mut bodies, &Sky, DT);
+        }
+
+        assert!(bodies[0].rigid_body.unwrap().velocity.norm() < 0.0001);
+        assert!((bodies[1].rigid_body.unwrap().velocity.x - 6.0).abs() < 0.0001);
+    }
+}
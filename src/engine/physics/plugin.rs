@@ -0,0 +1,44 @@
+use crate::{
+    app::App,
+    engine::{
+        plugin::{CorePlugin, Plugin, PluginId},
+        schedule::{IntoSystemConfig, Stage},
+        voxel::{events::VoxelEvents, plugin::VoxelWorldPlugin},
+    },
+};
+
+use super::{destruction::Destruction, phy_world::PhysicsWorld};
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        let destruction =
+            Destruction::new(&mut app.resource_bank().get_resource_mut::<VoxelEvents>());
+
+        app.insert_resource(PhysicsWorld::new())
+            .insert_resource(destruction);
+
+        app.add_system(Stage::FixedUpdate, PhysicsWorld::fixed_update)
+            .add_system(
+                Stage::Update,
+                Destruction::update
+                    .label("destruction")
+                    .after("voxel_events")
+                    .before("voxel_objects"),
+            )
+            .add_system(
+                Stage::Update,
+                Destruction::despawn_debris
+                    .after("destruction")
+                    .before("voxel_objects"),
+            );
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<CorePlugin>(),
+            PluginId::of::<VoxelWorldPlugin>(),
+        ]
+    }
+}
@@ -0,0 +1,70 @@
+use nalgebra::Vector3;
+
+use crate::engine::{common::transform::Transform, geometry::shapes::oriented_box::OrientedBox};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RigidBodyKind {
+    /// Moved by gravity and contacts.
+    Dynamic,
+    /// Moved only by its velocity, pushes dynamic bodies but isn't pushed back.
+    Kinematic,
+}
+
+/// Linear rigid body dynamics, the entity's rotation is left as is. Entities with a `Collider` and
+/// no rigid body are static.
+#[derive(Debug, Clone, Copy)]
+pub struct RigidBody {
+    pub kind: RigidBodyKind,
+    pub velocity: Vector3<f32>,
+    /// Zero for non-dynamic bodies.
+    pub inverse_mass: f32,
+    /// How much of the velocity into a contact bounces back, from 0 to 1.
+    pub restitution: f32,
+    /// The coulomb friction coefficient.
+    pub friction: f32,
+    pub gravity_scale: f32,
+}
+
+impl RigidBody {
+    pub fn new_dynamic(mass: f32) -> Self {
+        Self {
+            kind: RigidBodyKind::Dynamic,
+            velocity: Vector3::zeros(),
+            inverse_mass: 1.0 / mass,
+            restitution: 0.0,
+            friction: 0.5,
+            gravity_scale: 1.0,
+        }
+    }
+
+    pub fn new_kinematic(velocity: Vector3<f32>) -> Self {
+        Self {
+            kind: RigidBodyKind::Kinematic,
+            velocity,
+            inverse_mass: 0.0,
+            restitution: 0.0,
+            friction: 0.5,
+            gravity_scale: 0.0,
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.kind == RigidBodyKind::Dynamic
+    }
+}
+
+/// A box collider centered on the entity's transform, the transform's scale is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    pub half_extents: Vector3<f32>,
+}
+
+impl Collider {
+    pub fn new_box(half_extents: Vector3<f32>) -> Self {
+        Self { half_extents }
+    }
+
+    pub fn shape(&self, transform: &Transform) -> OrientedBox {
+        OrientedBox::new(transform.isometry, self.half_extents)
+    }
+}
@@ -0,0 +1,370 @@
+use nalgebra::{Point3, Vector3};
+
+use crate::engine::{
+    geometry::shapes::aabb::AABB,
+    voxel::{
+        dynamic_world::{DynVoxelWorld, SpatialStatus},
+        vox_constants::{BRICK_LENGTH, VOXEL_WORLD_LENGTH},
+    },
+};
+
+/// How close a box can get to a voxel face before it counts as touching it.
+const CONTACT_EPSILON: f32 = 0.0001;
+
+/// The voxel queries collision needs, implemented by the dynamic world and by the synthetic worlds
+/// in the tests. Positions are in world voxel and world brick coordinates.
+pub trait VoxelOccupancy {
+    /// Whether the brick has no solid voxels, used to skip bricks before testing voxels.
+    fn is_brick_empty(&self, brick_pos: Vector3<i32>) -> bool;
+
+    fn is_voxel_solid(&self, voxel_pos: Vector3<i32>) -> bool;
+}
+
+/// Unloaded bricks are solid so nothing falls through the world while it streams in.
+impl VoxelOccupancy for DynVoxelWorld {
+    fn is_brick_empty(&self, brick_pos: Vector3<i32>) -> bool {
+        self.world_brick_index(brick_pos).status() == SpatialStatus::LoadedEmpty
+    }
+
+    fn is_voxel_solid(&self, voxel_pos: Vector3<i32>) -> bool {
+        let brick_pos = voxel_pos.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+        match self.world_brick_index(brick_pos).status() {
+            SpatialStatus::Loaded => self.is_world_voxel_set(voxel_pos),
+            SpatialStatus::LoadedEmpty => false,
+            SpatialStatus::Unloaded | SpatialStatus::Loading => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SweepResult {
+    /// The motion the box can make before hitting a voxel.
+    pub motion: Vector3<f32>,
+    /// The axes the motion was cut short on.
+    pub collided: Vector3<bool>,
+}
+
+/// Moves the box one axis at a time, y first, so a blocked axis leaves the others free to slide
+/// along the face that was hit.
+pub fn sweep_aabb(world: &impl VoxelOccupancy, aabb: &AABB, motion: Vector3<f32>) -> SweepResult {
+    let mut min = aabb.min();
+    let mut max = aabb.max();
+    let mut result = SweepResult {
+        motion: Vector3::zeros(),
+        collided: Vector3::new(false, false, false),
+    };
+
+    for axis in [1, 0, 2] {
+        if motion[axis] == 0.0 {
+            continue;
+        }
+
+        let distance = sweep_axis(world, &min, &max, axis, motion[axis]);
+        result.collided[axis] = distance != motion[axis];
+        result.motion[axis] = distance;
+        min[axis] += distance;
+        max[axis] += distance;
+    }
+
+    result
+}
+
+/// Returns how far the box can move along the axis, at most `distance`.
+fn sweep_axis(
+    world: &impl VoxelOccupancy,
+    min: &Point3<f32>,
+    max: &Point3<f32>,
+    axis: usize,
+    distance: f32,
+) -> f32 {
+    let mut swept_min = *min;
+    let mut swept_max = *max;
+    if distance > 0.0 {
+        swept_max[axis] += distance;
+    } else {
+        swept_min[axis] += distance;
+    }
+
+    // Voxels the box only touches don't overlap it.
+    let voxel_min = swept_min
+        .coords
+        .map(|x| (x / VOXEL_WORLD_LENGTH + CONTACT_EPSILON).floor() as i32);
+    let voxel_max = swept_max
+        .coords
+        .map(|x| (x / VOXEL_WORLD_LENGTH - CONTACT_EPSILON).ceil() as i32 - 1);
+
+    let brick_min = voxel_min.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+    let brick_max = voxel_max.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+    if bricks_empty(world, brick_min, brick_max) {
+        return distance;
+    }
+
+    // Walk the voxel layers in the direction of motion starting with the first one in front of
+    // the box, the first layer with a solid voxel stops it.
+    let (first_layer, last_layer, step) = if distance > 0.0 {
+        let front = (max[axis] / VOXEL_WORLD_LENGTH - CONTACT_EPSILON).ceil() as i32;
+        (front, voxel_max[axis], 1)
+    } else {
+        let front = (min[axis] / VOXEL_WORLD_LENGTH + CONTACT_EPSILON).floor() as i32 - 1;
+        (front, voxel_min[axis], -1)
+    };
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+    let mut layer = first_layer;
+    while (layer - last_layer) * step <= 0 {
+        let mut voxel_pos = Vector3::zeros();
+        voxel_pos[axis] = layer;
+        for a in voxel_min[u]..=voxel_max[u] {
+            for b in voxel_min[v]..=voxel_max[v] {
+                voxel_pos[u] = a;
+                voxel_pos[v] = b;
+                if !world.is_voxel_solid(voxel_pos) {
+                    continue;
+                }
+
+                let face = if step > 0 {
+                    layer as f32 * VOXEL_WORLD_LENGTH - max[axis]
+                } else {
+                    (layer + 1) as f32 * VOXEL_WORLD_LENGTH - min[axis]
+                };
+                return if step > 0 {
+                    face.max(0.0)
+                } else {
+                    face.min(0.0)
+                };
+            }
+        }
+        layer += step;
+    }
+
+    distance
+}
+
+fn bricks_empty(
+    world: &impl VoxelOccupancy,
+    brick_min: Vector3<i32>,
+    brick_max: Vector3<i32>,
+) -> bool {
+    for x in brick_min.x..=brick_max.x {
+        for y in brick_min.y..=brick_max.y {
+            for z in brick_min.z..=brick_max.z {
+                if !world.is_brick_empty(Vector3::new(x, y, z)) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// Whether the box is resting on a solid voxel.
+pub fn is_grounded(world: &impl VoxelOccupancy, aabb: &AABB) -> bool {
+    let probe = Vector3::new(0.0, -CONTACT_EPSILON * 2.0, 0.0);
+    sweep_aabb(world, aabb, probe).collided.y
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MoveResult {
+    pub motion: Vector3<f32>,
+    pub collided: Vector3<bool>,
+    pub grounded: bool,
+    /// Whether the box stepped up onto a ledge.
+    pub stepped: bool,
+}
+
+/// Sweeps the box and slides it along whatever it hits. When grounded and blocked horizontally it
+/// also tries moving up by `step_height` first, taking the step if it gets further.
+pub fn move_and_slide(
+    world: &impl VoxelOccupancy,
+    aabb: &AABB,
+    motion: Vector3<f32>,
+    step_height: f32,
+    grounded: bool,
+) -> MoveResult {
+    let slide = sweep_aabb(world, aabb, motion);
+    let mut result = MoveResult {
+        motion: slide.motion,
+        collided: slide.collided,
+        grounded: slide.collided.y && motion.y < 0.0,
+        stepped: false,
+    };
+
+    let blocked_horizontally = slide.collided.x || slide.collided.z;
+    if !grounded || !blocked_horizontally || step_height <= 0.0 {
+        return result;
+    }
+
+    let horizontal_motion = Vector3::new(motion.x, 0.0, motion.z);
+    let up = sweep_aabb(world, aabb, Vector3::new(0.0, step_height, 0.0)).motion;
+    let raised = translate_aabb(aabb, &up);
+    let across = sweep_aabb(world, &raised, horizontal_motion);
+    let down_distance = -up.y + motion.y.min(0.0);
+    let down = sweep_aabb(
+        world,
+        &translate_aabb(&raised, &across.motion),
+        Vector3::new(0.0, down_distance, 0.0),
+    );
+
+    let slide_distance = slide.motion.xz().norm_squared();
+    let step_distance = across.motion.xz().norm_squared();
+    if step_distance > slide_distance + CONTACT_EPSILON {
+        result.motion = up + across.motion + down.motion;
+        result.collided = Vector3::new(across.collided.x, down.collided.y, across.collided.z);
+        result.grounded = down.collided.y;
+        result.stepped = true;
+    }
+
+    result
+}
+
+pub fn translate_aabb(aabb: &AABB, translation: &Vector3<f32>) -> AABB {
+    AABB::new_center_half_extent(aabb.center() + translation, aabb.half_extents())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// A world where only the listed voxels are solid.
+    struct TestWorld(HashSet<Vector3<i32>>);
+
+    impl TestWorld {
+        fn new() -> Self {
+            Self(HashSet::new())
+        }
+
+        fn fill(&mut self, min: Vector3<i32>, max: Vector3<i32>) -> &mut Self {
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        self.0.insert(Vector3::new(x, y, z));
+                    }
+                }
+            }
+            self
+        }
+
+        /// A floor with its top face at y = 0.
+        fn floor() -> Self {
+            let mut world = Self::new();
+            world.fill(Vector3::new(-16, -1, -16), Vector3::new(16, -1, 16));
+            world
+        }
+    }
+
+    impl VoxelOccupancy for TestWorld {
+        fn is_brick_empty(&self, brick_pos: Vector3<i32>) -> bool {
+            !self
+                .0
+                .iter()
+                .any(|v| v.map(|x| x.div_euclid(BRICK_LENGTH as i32)) == brick_pos)
+        }
+
+        fn is_voxel_solid(&self, voxel_pos: Vector3<i32>) -> bool {
+            self.0.contains(&voxel_pos)
+        }
+    }
+
+    fn player_aabb(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new_min_max(
+            Point3::new(x - 0.4, y, z - 0.4),
+            Point3::new(x + 0.4, y + 1.8, z + 0.4),
+        )
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_empty_world_moves_freely() {
+        let world = TestWorld::new();
+        let motion = Vector3::new(3.0, -20.0, 1.5);
+        let result = sweep_aabb(&world, &player_aabb(0.5, 4.0, 0.5), motion);
+
+        assert_eq!(result.motion, motion);
+        assert_eq!(result.collided, Vector3::new(false, false, false));
+    }
+
+    #[test]
+    fn test_lands_on_floor() {
+        let world = TestWorld::floor();
+        let result = sweep_aabb(
+            &world,
+            &player_aabb(0.5, 2.5, 0.5),
+            Vector3::new(0.0, -5.0, 0.0),
+        );
+
+        assert_close(result.motion.y, -2.5);
+        assert!(result.collided.y);
+        assert!(is_grounded(&world, &player_aabb(0.5, 0.0, 0.5)));
+        assert!(!is_grounded(&world, &player_aabb(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_fast_motion_does_not_tunnel() {
+        let mut world = TestWorld::new();
+        world.fill(Vector3::new(10, 0, -2), Vector3::new(10, 3, 2));
+        let result = sweep_aabb(
+            &world,
+            &player_aabb(0.5, 0.0, 0.5),
+            Vector3::new(100.0, 0.0, 0.0),
+        );
+
+        assert_close(result.motion.x, 10.0 - 0.9);
+        assert!(result.collided.x);
+    }
+
+    #[test]
+    fn test_slides_along_wall() {
+        let mut world = TestWorld::floor();
+        world.fill(Vector3::new(2, 0, -16), Vector3::new(2, 3, 16));
+        let aabb = player_aabb(0.5, 0.0, 0.5);
+        let result = move_and_slide(&world, &aabb, Vector3::new(3.0, -0.1, 2.0), 0.0, true);
+
+        assert_close(result.motion.x, 2.0 - 0.9);
+        assert_close(result.motion.z, 2.0);
+        assert_close(result.motion.y, 0.0);
+        assert!(result.collided.x && !result.collided.z);
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn test_steps_up_ledge() {
+        let mut world = TestWorld::floor();
+        world.fill(Vector3::new(2, 0, -16), Vector3::new(8, 0, 16));
+        let aabb = player_aabb(0.5, 0.0, 0.5);
+        let result = move_and_slide(&world, &aabb, Vector3::new(2.0, -0.1, 0.0), 1.1, true);
+
+        assert!(result.stepped);
+        assert!(result.grounded);
+        assert_close(result.motion.x, 2.0);
+        assert_close(result.motion.y, 1.0);
+    }
+
+    #[test]
+    fn test_does_not_step_up_wall() {
+        let mut world = TestWorld::floor();
+        world.fill(Vector3::new(2, 0, -16), Vector3::new(8, 1, 16));
+        let aabb = player_aabb(0.5, 0.0, 0.5);
+        let result = move_and_slide(&world, &aabb, Vector3::new(2.0, -0.1, 0.0), 1.1, true);
+
+        assert!(!result.stepped);
+        assert_close(result.motion.x, 2.0 - 0.9);
+        assert_close(result.motion.y, 0.0);
+    }
+
+    #[test]
+    fn test_does_not_step_while_airborne() {
+        let mut world = TestWorld::floor();
+        world.fill(Vector3::new(2, 0, -16), Vector3::new(8, 0, 16));
+        let aabb = player_aabb(0.5, 0.5, 0.5);
+        let result = move_and_slide(&world, &aabb, Vector3::new(2.0, 0.0, 0.0), 1.1, false);
+
+        assert!(!result.stepped);
+        assert_close(result.motion.x, 2.0 - 0.9);
+    }
+}
@@ -0,0 +1,76 @@
+use std::any::TypeId;
+
+use crate::{app::App, settings::Settings};
+
+use super::{
+    common::time::Time,
+    ecs::ecs_world::ECSWorld,
+    resource::Res,
+    schedule::{IntoSystemConfig, Stage},
+};
+
+/// A piece of engine or game setup that registers its resources and systems with the app.
+pub trait Plugin: 'static {
+    fn build(&self, app: &mut App);
+
+    /// The plugins that have to be added before this one.
+    fn dependencies(&self) -> Vec<PluginId> {
+        Vec::new()
+    }
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PluginId {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl PluginId {
+    pub fn of<P: Plugin>() -> Self {
+        Self {
+            id: TypeId::of::<P>(),
+            name: std::any::type_name::<P>(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// The settings, time and ECS world every other plugin relies on.
+pub struct CorePlugin;
+
+impl Plugin for CorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::default())
+            .insert_resource(Time::new())
+            .insert_resource(ECSWorld::new());
+
+        app.add_system(Stage::PreUpdate, Time::update.label("time"))
+            .add_system(
+                Stage::Cleanup,
+                Time::limit_frame_rate
+                    .run_if(|settings: Res<Settings>| settings.fps_limit.is_some()),
+            );
+    }
+}
+
+/// Steps time by exactly one fixed update per frame for apps run with `App::run_ticks`.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        let mut time = app.resource_bank().get_resource_mut::<Time>();
+        let fixed_delta_time = time.fixed_delta_time();
+        time.set_manual_delta_time(Some(fixed_delta_time));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<CorePlugin>()]
+    }
+}
@@ -1,4 +1,10 @@
-use std::{any::TypeId, collections::HashMap};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use downcast::{downcast, Any};
 
@@ -6,16 +12,102 @@ use parking_lot::{
     MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
 };
 
+use super::change::{next_change_tick, SystemTicks};
+
 pub trait Resource: Any + Send + Sync {}
 downcast!(dyn Resource);
 
 pub(crate) type BoxedResource = Box<dyn Resource>;
 
-pub type Res<'rb, R> = MappedRwLockReadGuard<'rb, R>;
-pub type ResMut<'rb, R> = MappedRwLockWriteGuard<'rb, R>;
+/// A shared borrow of a resource.
+pub struct Res<'rb, R> {
+    value: MappedRwLockReadGuard<'rb, R>,
+    changed_tick: u64,
+    last_run: u64,
+}
+
+impl<R> Res<'_, R> {
+    /// Whether the resource was written since the borrowing system last ran.
+    pub fn is_changed(&self) -> bool {
+        self.changed_tick > self.last_run
+    }
+}
+
+impl<R> Deref for Res<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// A mutable borrow of a resource, mutably dereferencing it marks the resource as changed.
+pub struct ResMut<'rb, R> {
+    value: MappedRwLockWriteGuard<'rb, R>,
+    changed_tick: &'rb AtomicU64,
+    last_run: u64,
+    marked: bool,
+}
+
+impl<R> ResMut<'_, R> {
+    /// Whether the resource was written since the borrowing system last ran.
+    pub fn is_changed(&self) -> bool {
+        self.marked || self.changed_tick.load(Ordering::Relaxed) > self.last_run
+    }
+
+    /// Mutably borrows the resource without marking it as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut R {
+        &mut self.value
+    }
+}
+
+impl<R> Deref for ResMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<R> DerefMut for ResMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if !self.marked {
+            self.marked = true;
+            self.changed_tick
+                .store(next_change_tick(), Ordering::Relaxed);
+        }
+        &mut self.value
+    }
+}
+
+struct ResourceEntry {
+    type_name: &'static str,
+    resource: RwLock<BoxedResource>,
+    changed_tick: AtomicU64,
+}
+
+impl ResourceEntry {
+    fn read<R: Resource>(&self, ticks: SystemTicks) -> Res<'_, R> {
+        Res {
+            value: RwLockReadGuard::map(self.resource.read(), |r| r.downcast_ref().unwrap()),
+            // Loaded after the lock so a write can't land in between.
+            changed_tick: self.changed_tick.load(Ordering::Relaxed),
+            last_run: ticks.last_run,
+        }
+    }
+
+    fn write<R: Resource>(&self, ticks: SystemTicks) -> ResMut<'_, R> {
+        ResMut {
+            value: RwLockWriteGuard::map(self.resource.write(), |r| r.downcast_mut().unwrap()),
+            changed_tick: &self.changed_tick,
+            last_run: ticks.last_run,
+            marked: false,
+        }
+    }
+}
 
 pub struct ResourceBank {
-    resources: HashMap<TypeId, RwLock<BoxedResource>>,
+    resources: HashMap<TypeId, ResourceEntry>,
 }
 
 impl ResourceBank {
@@ -25,37 +117,176 @@ impl ResourceBank {
         }
     }
 
+    fn entry<R: Resource>(&self) -> &ResourceEntry {
+        self.resources.get(&TypeId::of::<R>()).expect(&format!(
+            "Failed to get resource: {}",
+            std::any::type_name::<R>()
+        ))
+    }
+
     pub fn get_resource<R: Resource>(&self) -> Res<R> {
-        RwLockReadGuard::map(
-            self.resources
-                .get(&TypeId::of::<R>())
-                .expect(&format!(
-                    "Failed to get resource: {}",
-                    std::any::type_name::<R>()
-                ))
-                .read(),
-            |r| r.downcast_ref().unwrap(),
-        )
+        self.entry::<R>().read(SystemTicks::always_changed())
     }
 
     pub fn get_resource_mut<R: Resource>(&self) -> ResMut<R>
     where
         R: Resource,
     {
-        RwLockWriteGuard::map(
-            self.resources
-                .get(&TypeId::of::<R>())
-                .expect(&format!(
-                    "Failed to get resource: {}",
-                    std::any::type_name::<R>()
-                ))
-                .write(),
-            |r| r.downcast_mut().unwrap(),
-        )
+        self.entry::<R>().write(SystemTicks::always_changed())
     }
 
-    pub fn insert<R: Resource>(&mut self, resource: R) {
+    /// Borrows the resource for a system, changes are relative to the system's last run.
+    pub fn get_resource_with_ticks<R: Resource>(&self, ticks: SystemTicks) -> Res<R> {
+        self.entry::<R>().read(ticks)
+    }
+
+    /// Mutably borrows the resource for a system, changes are relative to the system's last run.
+    pub fn get_resource_mut_with_ticks<R: Resource>(&self, ticks: SystemTicks) -> ResMut<R> {
+        self.entry::<R>().write(ticks)
+    }
+
+    /// Like `get_resource` but returns `None` when the resource hasn't been inserted.
+    pub fn try_get<R: Resource>(&self) -> Option<Res<R>> {
+        self.try_get_with_ticks(SystemTicks::always_changed())
+    }
+
+    /// Like `get_resource_mut` but returns `None` when the resource hasn't been inserted.
+    pub fn try_get_mut<R: Resource>(&self) -> Option<ResMut<R>> {
+        self.try_get_mut_with_ticks(SystemTicks::always_changed())
+    }
+
+    pub fn try_get_with_ticks<R: Resource>(&self, ticks: SystemTicks) -> Option<Res<R>> {
         self.resources
-            .insert(TypeId::of::<R>(), RwLock::new(Box::new(resource)));
+            .get(&TypeId::of::<R>())
+            .map(|entry| entry.read(ticks))
+    }
+
+    pub fn try_get_mut_with_ticks<R: Resource>(&self, ticks: SystemTicks) -> Option<ResMut<R>> {
+        self.resources
+            .get(&TypeId::of::<R>())
+            .map(|entry| entry.write(ticks))
+    }
+
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    pub fn insert<R: Resource>(&mut self, resource: R) {
+        self.resources.insert(
+            TypeId::of::<R>(),
+            ResourceEntry {
+                type_name: std::any::type_name::<R>(),
+                resource: RwLock::new(Box::new(resource)),
+                changed_tick: AtomicU64::new(next_change_tick()),
+            },
+        );
+    }
+
+    pub fn remove<R: Resource>(&mut self) -> Option<R> {
+        let resource = self
+            .resources
+            .remove(&TypeId::of::<R>())?
+            .resource
+            .into_inner();
+        resource.downcast::<R>().ok().map(|resource| *resource)
+    }
+
+    /// Takes the resource out of the bank while the closure runs so it can be used alongside
+    /// the rest of the bank, then puts it back. A copy inserted by the closure is replaced. The
+    /// resource keeps its changed tick unless the closure writes to it.
+    pub fn resource_scope<R: Resource, T>(
+        &mut self,
+        f: impl FnOnce(&mut ResourceBank, ResMut<R>) -> T,
+    ) -> T {
+        let entry = self
+            .resources
+            .remove(&TypeId::of::<R>())
+            .unwrap_or_else(|| panic!("Failed to get resource: {}", std::any::type_name::<R>()));
+        let result = f(self, entry.write(SystemTicks::always_changed()));
+        self.resources.insert(TypeId::of::<R>(), entry);
+        result
+    }
+
+    /// The type names of every resource in the bank, sorted.
+    pub fn resource_names(&self) -> Vec<&'static str> {
+        let mut names = self
+            .resources
+            .values()
+            .map(|entry| entry.type_name)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Debug for ResourceBank {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.resource_names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voxei_macros::Resource;
+
+    use super::*;
+
+    #[derive(Resource, Debug, PartialEq)]
+    struct Counter(u32);
+
+    #[derive(Resource)]
+    struct Step(u32);
+
+    #[test]
+    fn test_try_get_and_remove() {
+        let mut resource_bank = ResourceBank::new();
+        assert!(!resource_bank.contains::<Counter>());
+        assert!(resource_bank.try_get::<Counter>().is_none());
+        assert!(resource_bank.remove::<Counter>().is_none());
+
+        resource_bank.insert(Counter(1));
+        assert!(resource_bank.contains::<Counter>());
+        resource_bank.try_get_mut::<Counter>().unwrap().0 += 1;
+        assert_eq!(resource_bank.try_get::<Counter>().unwrap().0, 2);
+        assert_eq!(resource_bank.remove::<Counter>(), Some(Counter(2)));
+        assert!(!resource_bank.contains::<Counter>());
+    }
+
+    #[test]
+    fn test_resource_scope() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Counter(0));
+        resource_bank.insert(Step(3));
+
+        let result = resource_bank.resource_scope(|resource_bank, mut counter: ResMut<Counter>| {
+            assert!(!resource_bank.contains::<Counter>());
+            counter.0 += resource_bank.get_resource::<Step>().0;
+            resource_bank.remove::<Step>();
+            counter.0
+        });
+        assert_eq!(result, 3);
+        assert_eq!(resource_bank.get_resource::<Counter>().0, 3);
+        assert_eq!(
+            resource_bank.resource_names(),
+            vec![std::any::type_name::<Counter>()]
+        );
+    }
+
+    #[test]
+    fn test_resource_scope_keeps_changed_tick() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Counter(0));
+        let tick = next_change_tick();
+        let ticks = SystemTicks::new(tick, tick);
+
+        resource_bank.resource_scope(|_, counter: ResMut<Counter>| assert_eq!(counter.0, 0));
+        assert!(!resource_bank
+            .get_resource_with_ticks::<Counter>(ticks)
+            .is_changed());
+
+        resource_bank.resource_scope(|_, mut counter: ResMut<Counter>| counter.0 += 1);
+        assert!(resource_bank
+            .get_resource_with_ticks::<Counter>(ticks)
+            .is_changed());
     }
 }
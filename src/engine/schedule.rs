@@ -0,0 +1,692 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+use voxei_macros::Resource;
+
+use super::{
+    change::{next_change_tick, SystemTicks},
+    command::CommandQueue,
+    common::time::Time,
+    executor::{self, ExecutorKind},
+    resource::ResourceBank,
+    system::{Condition, ConflictKind, System, SystemAccess},
+};
+
+/// The stages of a frame in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    PreUpdate,
+    /// Runs `Time::fixed_steps` times a frame, for the simulation that shouldn't depend on the
+    /// frame rate.
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    Render,
+    Cleanup,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Render,
+        Stage::Cleanup,
+    ];
+}
+
+/// Names a system or group of systems for `before` and `after` constraints.
+pub type SystemLabel = &'static str;
+
+type BoxedSystemFn = Box<dyn FnMut(&ResourceBank, SystemTicks) + Send>;
+type BoxedConditionFn = Box<dyn FnMut(&ResourceBank, SystemTicks) -> bool + Send>;
+
+/// A system with its labels, ordering constraints and run conditions.
+pub struct SystemConfig {
+    name: &'static str,
+    run: BoxedSystemFn,
+    access: SystemAccess,
+    labels: Vec<SystemLabel>,
+    before: Vec<SystemLabel>,
+    after: Vec<SystemLabel>,
+    conditions: Vec<BoxedConditionFn>,
+    /// The change tick the system last ran at, conditions see the same ticks as the system.
+    last_run: u64,
+    /// Time spent in the system and its conditions since the frame started.
+    run_time: Duration,
+}
+
+impl SystemConfig {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn access(&self) -> &SystemAccess {
+        &self.access
+    }
+
+    /// Runs the system if all of its conditions pass.
+    pub(crate) fn run(&mut self, resource_bank: &ResourceBank) {
+        let start = Instant::now();
+        let ticks = SystemTicks::new(self.last_run, next_change_tick());
+        if self
+            .conditions
+            .iter_mut()
+            .all(|condition| condition(resource_bank, ticks))
+        {
+            (self.run)(resource_bank, ticks);
+            self.last_run = ticks.this_run;
+        }
+        self.run_time += start.elapsed();
+    }
+}
+
+/// Marks the `IntoSystemConfig` impl of an already configured system.
+pub struct ConfiguredMarker;
+
+pub trait IntoSystemConfig<Marker>: Sized {
+    fn into_config(self) -> SystemConfig;
+
+    fn label(self, label: SystemLabel) -> SystemConfig {
+        let mut config = self.into_config();
+        config.labels.push(label);
+        config
+    }
+
+    /// Runs this system before every system with the label in the same stage.
+    fn before(self, label: SystemLabel) -> SystemConfig {
+        let mut config = self.into_config();
+        config.before.push(label);
+        config
+    }
+
+    /// Runs this system after every system with the label in the same stage.
+    fn after(self, label: SystemLabel) -> SystemConfig {
+        let mut config = self.into_config();
+        config.after.push(label);
+        config
+    }
+
+    /// Only runs this system on frames the condition returns true, conditions are evaluated
+    /// right before the system.
+    fn run_if<M>(self, mut condition: impl Condition<M> + Send + 'static) -> SystemConfig {
+        let mut config = self.into_config();
+        config.access.extend(&condition.access());
+        config
+            .conditions
+            .push(Box::new(move |resource_bank, ticks| {
+                condition.evaluate(resource_bank, ticks)
+            }));
+        config
+    }
+}
+
+impl<S, M> IntoSystemConfig<M> for S
+where
+    S: System<M> + Send + 'static,
+{
+    fn into_config(mut self) -> SystemConfig {
+        SystemConfig {
+            name: self.name(),
+            access: self.access(),
+            run: Box::new(move |resource_bank, ticks| self.run(resource_bank, ticks)),
+            labels: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            last_run: 0,
+            run_time: Duration::ZERO,
+        }
+    }
+}
+
+impl IntoSystemConfig<ConfiguredMarker> for SystemConfig {
+    fn into_config(self) -> SystemConfig {
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScheduleError {
+    /// A system borrows a resource mutably while also borrowing it elsewhere.
+    ConflictingAccess {
+        system: &'static str,
+        resource: &'static str,
+        kind: ConflictKind,
+    },
+    /// A `before` or `after` label that no system in the stage has.
+    UnknownLabel {
+        stage: Stage,
+        system: &'static str,
+        label: SystemLabel,
+    },
+    OrderingCycle {
+        stage: Stage,
+        systems: Vec<&'static str>,
+    },
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::ConflictingAccess {
+                system,
+                resource,
+                kind,
+            } => match kind {
+                ConflictKind::ReadWrite => write!(
+                    f,
+                    "System {} borrows {} both mutably and immutably",
+                    system, resource
+                ),
+                ConflictKind::WriteWrite => write!(
+                    f,
+                    "System {} borrows {} mutably more than once",
+                    system, resource
+                ),
+            },
+            ScheduleError::UnknownLabel {
+                stage,
+                system,
+                label,
+            } => write!(
+                f,
+                "System {} is ordered against label {} which isn't in stage {:?}",
+                system, label, stage
+            ),
+            ScheduleError::OrderingCycle { stage, systems } => write!(
+                f,
+                "Systems in stage {:?} have cyclic ordering: {}",
+                stage,
+                systems.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/// Two systems in a stage that write the same resource with nothing ordering them, they never run
+/// at the same time and keep the order `Schedule::build` sorted them in.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ambiguity {
+    pub stage: Stage,
+    pub systems: [&'static str; 2],
+    pub resources: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemTiming {
+    pub stage: Stage,
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// How long each system took on the cpu last frame in run order, `Stage::FixedUpdate` systems
+/// summed over the frame's steps. Written by `Schedule::run` once every stage has run.
+#[derive(Resource, Default)]
+pub struct SystemTimings {
+    timings: Vec<SystemTiming>,
+}
+
+impl SystemTimings {
+    pub fn timings(&self) -> &[SystemTiming] {
+        &self.timings
+    }
+
+    /// The time spent in systems, which is more than the frame's cpu time when they run in
+    /// parallel.
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|timing| timing.duration).sum()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct StageSystems {
+    pub(crate) systems: Vec<SystemConfig>,
+    /// Indices into `systems` in the order they run serially, set by `Schedule::build`.
+    pub(crate) order: Vec<usize>,
+    /// The systems that wait for each system, from its ordering constraints and from the
+    /// resources they both borrow.
+    pub(crate) dependents: Vec<Vec<usize>>,
+}
+
+/// Systems grouped into stages, ordered by their labels.
+pub struct Schedule {
+    stages: HashMap<Stage, StageSystems>,
+    ambiguities: Vec<Ambiguity>,
+    executor: ExecutorKind,
+    built: bool,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            stages: HashMap::new(),
+            ambiguities: Vec::new(),
+            executor: ExecutorKind::Parallel,
+            built: false,
+        }
+    }
+
+    pub fn set_executor(&mut self, executor: ExecutorKind) {
+        self.executor = executor;
+    }
+
+    pub fn add_system<M>(&mut self, stage: Stage, system: impl IntoSystemConfig<M>) -> &mut Self {
+        self.stages
+            .entry(stage)
+            .or_default()
+            .systems
+            .push(system.into_config());
+        self.built = false;
+        self
+    }
+
+    /// Checks every system's access and sorts each stage by its ordering constraints, systems
+    /// without constraints between them keep the order they were added in.
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        self.ambiguities.clear();
+        for stage in Stage::ALL {
+            let Some(stage_systems) = self.stages.get_mut(&stage) else {
+                continue;
+            };
+            let systems = &stage_systems.systems;
+
+            for system in systems {
+                if let Some(&(resource, kind)) = system.access.conflicts().first() {
+                    return Err(ScheduleError::ConflictingAccess {
+                        system: system.name,
+                        resource,
+                        kind,
+                    });
+                }
+            }
+
+            let mut labelled = HashMap::<SystemLabel, Vec<usize>>::new();
+            for (i, system) in systems.iter().enumerate() {
+                for label in &system.labels {
+                    labelled.entry(*label).or_default().push(i);
+                }
+            }
+
+            // Edges from each system to the systems that have to run after it.
+            let mut successors = vec![Vec::new(); systems.len()];
+            for (i, system) in systems.iter().enumerate() {
+                let constraints = system
+                    .before
+                    .iter()
+                    .map(|label| (label, true))
+                    .chain(system.after.iter().map(|label| (label, false)));
+                for (label, is_before) in constraints {
+                    let Some(others) = labelled.get(label) else {
+                        return Err(ScheduleError::UnknownLabel {
+                            stage,
+                            system: system.name,
+                            label,
+                        });
+                    };
+                    for &other in others.iter().filter(|other| **other != i) {
+                        if is_before {
+                            successors[i].push(other);
+                        } else {
+                            successors[other].push(i);
+                        }
+                    }
+                }
+            }
+
+            let order =
+                sort_systems(&successors).map_err(|cycle| ScheduleError::OrderingCycle {
+                    stage,
+                    systems: cycle.iter().map(|i| systems[*i].name).collect(),
+                })?;
+            self.ambiguities.extend(add_access_dependencies(
+                stage,
+                systems,
+                &order,
+                &mut successors,
+            ));
+            stage_systems.order = order;
+            stage_systems.dependents = successors;
+        }
+
+        self.built = true;
+        Ok(())
+    }
+
+    /// Runs every stage, building the schedule first if systems were added since the last build.
+    /// Commands queued by a stage's systems are applied before the next stage.
+    pub fn run(&mut self, resource_bank: &mut ResourceBank) {
+        if !self.built {
+            self.build()
+                .unwrap_or_else(|err| panic!("Failed to build schedule: {}", err));
+        }
+        if !resource_bank.contains::<CommandQueue>() {
+            resource_bank.insert(CommandQueue::new());
+        }
+        if !resource_bank.contains::<SystemTimings>() {
+            resource_bank.insert(SystemTimings::default());
+        }
+
+        for stage in Stage::ALL {
+            let runs = match stage {
+                Stage::FixedUpdate => resource_bank.get_resource::<Time>().fixed_steps(),
+                _ => 1,
+            };
+            for _ in 0..runs {
+                self.run_stage(stage, resource_bank);
+                CommandQueue::apply(resource_bank);
+            }
+        }
+
+        let mut system_timings = resource_bank.get_resource_mut::<SystemTimings>();
+        system_timings.timings.clear();
+        for stage in Stage::ALL {
+            let Some(stage_systems) = self.stages.get_mut(&stage) else {
+                continue;
+            };
+            for &i in &stage_systems.order {
+                let system = &mut stage_systems.systems[i];
+                system_timings.timings.push(SystemTiming {
+                    stage,
+                    name: system.name,
+                    duration: std::mem::take(&mut system.run_time),
+                });
+            }
+        }
+    }
+
+    fn run_stage(&mut self, stage: Stage, resource_bank: &ResourceBank) {
+        let Some(stage_systems) = self.stages.get_mut(&stage) else {
+            return;
+        };
+        match self.executor {
+            ExecutorKind::Serial => {
+                for &i in &stage_systems.order {
+                    stage_systems.systems[i].run(resource_bank);
+                }
+            }
+            ExecutorKind::Parallel => executor::run_parallel(stage_systems, resource_bank),
+        }
+    }
+
+    /// The unordered systems writing the same resources found by the last build.
+    pub fn ambiguities(&self) -> &[Ambiguity] {
+        &self.ambiguities
+    }
+
+    /// The system names of a stage in the order they run.
+    pub fn system_names(&self, stage: Stage) -> Vec<&'static str> {
+        self.stages
+            .get(&stage)
+            .map_or_else(Vec::new, |stage_systems| {
+                stage_systems
+                    .order
+                    .iter()
+                    .map(|i| stage_systems.systems[*i].name)
+                    .collect()
+            })
+    }
+}
+
+/// Topologically sorts the systems, picking the earliest added system whenever there is a
+/// choice. Returns the systems left in a cycle if there is one.
+fn sort_systems(successors: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut in_degree = vec![0; successors.len()];
+    for next in successors.iter().flatten() {
+        in_degree[*next] += 1;
+    }
+
+    let mut ready = (0..successors.len())
+        .filter(|i| in_degree[*i] == 0)
+        .collect::<BTreeSet<_>>();
+    let mut order = Vec::with_capacity(successors.len());
+    while let Some(i) = ready.pop_first() {
+        order.push(i);
+        for next in &successors[i] {
+            in_degree[*next] -= 1;
+            if in_degree[*next] == 0 {
+                ready.insert(*next);
+            }
+        }
+    }
+
+    if order.len() < successors.len() {
+        return Err((0..successors.len())
+            .filter(|i| in_degree[*i] > 0)
+            .collect());
+    }
+    Ok(order)
+}
+
+/// Makes every pair of systems that borrow a resource in conflicting ways wait on each other in
+/// the sorted order, returning the pairs the ordering constraints didn't already order.
+fn add_access_dependencies(
+    stage: Stage,
+    systems: &[SystemConfig],
+    order: &[usize],
+    successors: &mut [Vec<usize>],
+) -> Vec<Ambiguity> {
+    // Walking in run order means every predecessor's ancestors are known before they are needed.
+    let mut ancestors = vec![vec![false; systems.len()]; systems.len()];
+    for &i in order {
+        let inherited = ancestors[i].clone();
+        for &next in &successors[i] {
+            ancestors[next][i] = true;
+            for (ancestor, inherited) in ancestors[next].iter_mut().zip(&inherited) {
+                *ancestor |= inherited;
+            }
+        }
+    }
+
+    let mut ambiguities = Vec::new();
+    for (position, &a) in order.iter().enumerate() {
+        for &b in &order[(position + 1)..] {
+            if ancestors[b][a] {
+                continue;
+            }
+            let resources = systems[a].access.conflicts_with(&systems[b].access);
+            if !resources.is_empty() {
+                successors[a].push(b);
+                ambiguities.push(Ambiguity {
+                    stage,
+                    systems: [systems[a].name, systems[b].name],
+                    resources,
+                });
+            }
+        }
+    }
+    ambiguities
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use voxei_macros::Resource;
+
+    use super::*;
+    use crate::engine::resource::{Res, ResMut};
+
+    #[derive(Resource)]
+    struct Log(Vec<&'static str>);
+
+    #[derive(Resource)]
+    struct Enabled(bool);
+
+    fn resource_bank() -> ResourceBank {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Log(Vec::new()));
+        resource_bank.insert(Enabled(false));
+        resource_bank.insert(Time::new());
+        resource_bank
+    }
+
+    fn first(mut log: ResMut<Log>) {
+        log.0.push("first");
+    }
+
+    fn second(mut log: ResMut<Log>) {
+        log.0.push("second");
+    }
+
+    fn third(mut log: ResMut<Log>) {
+        log.0.push("third");
+    }
+
+    #[test]
+    fn test_systems_run_in_label_order() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, third.after("second"))
+            .add_system(Stage::Update, second.label("second").after("first"))
+            .add_system(Stage::Update, first.label("first"));
+        let mut resource_bank = resource_bank();
+
+        schedule.run(&mut resource_bank);
+        assert_eq!(
+            resource_bank.get_resource::<Log>().0,
+            vec!["first", "second", "third"]
+        );
+        // Everything is ordered so nothing is ambiguous even though they all write the log.
+        assert!(schedule.ambiguities().is_empty());
+    }
+
+    #[test]
+    fn test_stages_run_in_order() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Render, third)
+            .add_system(Stage::Update, second)
+            .add_system(Stage::PreUpdate, first);
+        let mut resource_bank = resource_bank();
+
+        schedule.run(&mut resource_bank);
+        assert_eq!(
+            resource_bank.get_resource::<Log>().0,
+            vec!["first", "second", "third"]
+        );
+    }
+
+    #[test]
+    fn test_run_conditions() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            first.run_if(|enabled: Res<Enabled>| enabled.0),
+        );
+        let mut resource_bank = resource_bank();
+
+        schedule.run(&mut resource_bank);
+        assert!(resource_bank.get_resource::<Log>().0.is_empty());
+
+        resource_bank.get_resource_mut::<Enabled>().0 = true;
+        schedule.run(&mut resource_bank);
+        assert_eq!(resource_bank.get_resource::<Log>().0, vec!["first"]);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, |_: Res<Log>, _: ResMut<Log>| {});
+        assert!(matches!(
+            schedule.build(),
+            Err(ScheduleError::ConflictingAccess {
+                kind: ConflictKind::ReadWrite,
+                ..
+            })
+        ));
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, |_: ResMut<Log>, _: ResMut<Log>| {});
+        assert!(matches!(
+            schedule.build(),
+            Err(ScheduleError::ConflictingAccess {
+                kind: ConflictKind::WriteWrite,
+                ..
+            })
+        ));
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, first.after("missing"));
+        assert!(matches!(
+            schedule.build(),
+            Err(ScheduleError::UnknownLabel {
+                label: "missing",
+                ..
+            })
+        ));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, first.label("first").after("second"))
+            .add_system(Stage::Update, second.label("second").after("first"))
+            .add_system(Stage::Update, third);
+        let Err(ScheduleError::OrderingCycle { systems, .. }) = schedule.build() else {
+            panic!("Expected an ordering cycle");
+        };
+        assert_eq!(systems.len(), 2);
+    }
+
+    #[test]
+    fn test_unordered_writes_are_ambiguous() {
+        let ran = Arc::new(Mutex::new(false));
+        let ran_clone = ran.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, first)
+            .add_system(Stage::Update, second)
+            .add_system(Stage::Update, move |_: Res<Enabled>| {
+                *ran_clone.lock().unwrap() = true;
+            });
+        let mut resource_bank = resource_bank();
+
+        schedule.run(&mut resource_bank);
+        assert!(*ran.lock().unwrap());
+        assert_eq!(
+            resource_bank.get_resource::<Log>().0,
+            vec!["first", "second"]
+        );
+        assert_eq!(schedule.ambiguities().len(), 1);
+        assert_eq!(
+            schedule.ambiguities()[0].resources,
+            vec![std::any::type_name::<Log>()]
+        );
+    }
+
+    #[test]
+    fn test_system_timings_sum_fixed_steps() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::PreUpdate, Time::update)
+            .add_system(Stage::FixedUpdate, || {
+                std::thread::sleep(Duration::from_millis(5))
+            })
+            .add_system(Stage::Update, first);
+        let mut resource_bank = resource_bank();
+        {
+            let mut time = resource_bank.get_resource_mut::<Time>();
+            let fixed_delta_time = time.fixed_delta_time();
+            time.set_manual_delta_time(Some(fixed_delta_time * 2));
+        }
+
+        schedule.run(&mut resource_bank);
+        let system_timings = resource_bank.get_resource::<SystemTimings>();
+        let stages = system_timings
+            .timings()
+            .iter()
+            .map(|timing| timing.stage)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            vec![Stage::PreUpdate, Stage::FixedUpdate, Stage::Update]
+        );
+        assert!(system_timings.timings()[1].duration >= Duration::from_millis(10));
+    }
+}
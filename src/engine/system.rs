@@ -1,12 +1,94 @@
+use std::{any::TypeId, collections::HashMap};
+
 use voxei_macros::generate_tuples;
 
-use super::resource::{Res, ResMut, Resource, ResourceBank};
+use super::{
+    change::SystemTicks,
+    resource::{Res, ResMut, Resource, ResourceBank},
+};
+
+/// How a resource is borrowed more than once by the same system.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictKind {
+    /// Borrowed both mutably and immutably.
+    ReadWrite,
+    /// Borrowed mutably more than once.
+    WriteWrite,
+}
+
+/// The resources a system borrows, used by the schedule to order and check systems before they
+/// run.
+#[derive(Default, Clone, Debug)]
+pub struct SystemAccess {
+    reads: HashMap<TypeId, &'static str>,
+    writes: HashMap<TypeId, &'static str>,
+    /// Resources borrowed more than once with at least one write, these deadlock on the
+    /// resource's lock.
+    conflicts: Vec<(&'static str, ConflictKind)>,
+}
+
+impl SystemAccess {
+    pub fn add_read<R: Resource>(&mut self) {
+        let name = std::any::type_name::<R>();
+        if self.writes.contains_key(&TypeId::of::<R>()) {
+            self.conflicts.push((name, ConflictKind::ReadWrite));
+        }
+        self.reads.insert(TypeId::of::<R>(), name);
+    }
+
+    pub fn add_write<R: Resource>(&mut self) {
+        let name = std::any::type_name::<R>();
+        if self.writes.contains_key(&TypeId::of::<R>()) {
+            self.conflicts.push((name, ConflictKind::WriteWrite));
+        } else if self.reads.contains_key(&TypeId::of::<R>()) {
+            self.conflicts.push((name, ConflictKind::ReadWrite));
+        }
+        self.writes.insert(TypeId::of::<R>(), name);
+    }
+
+    /// Adds the access of something that borrows separately from this, like a run condition.
+    pub fn extend(&mut self, other: &SystemAccess) {
+        self.reads.extend(&other.reads);
+        self.writes.extend(&other.writes);
+        self.conflicts.extend(&other.conflicts);
+    }
+
+    pub fn reads(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.reads.keys().copied()
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.writes.keys().copied()
+    }
+
+    /// The resources borrowed in a way that would deadlock.
+    pub fn conflicts(&self) -> &[(&'static str, ConflictKind)] {
+        &self.conflicts
+    }
+
+    /// The names of the resources both accesses borrow where at least one of them writes.
+    pub fn conflicts_with(&self, other: &SystemAccess) -> Vec<&'static str> {
+        self.writes
+            .iter()
+            .filter(|(id, _)| other.reads.contains_key(id) || other.writes.contains_key(id))
+            .chain(
+                self.reads
+                    .iter()
+                    .filter(|(id, _)| other.writes.contains_key(id)),
+            )
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
 
 type SystemParamItem<'rb, T> = <T as SystemParam>::Item<'rb>;
 pub trait SystemParam {
     type Item<'rb>: SystemParam;
 
-    fn from_resource_bank(resource_bank: &ResourceBank) -> Self::Item<'_>;
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_>;
+
+    /// Records the resources this param borrows.
+    fn access(access: &mut SystemAccess);
 }
 
 impl<R> SystemParam for Res<'_, R>
@@ -15,8 +97,12 @@ where
 {
     type Item<'rb> = Res<'rb, R>;
 
-    fn from_resource_bank(resource_bank: &ResourceBank) -> Self::Item<'_> {
-        resource_bank.get_resource::<R>()
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        resource_bank.get_resource_with_ticks::<R>(ticks)
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_read::<R>();
     }
 }
 
@@ -26,13 +112,60 @@ where
 {
     type Item<'rb> = ResMut<'rb, R>;
 
-    fn from_resource_bank(resource_bank: &ResourceBank) -> Self::Item<'_> {
-        resource_bank.get_resource_mut::<R>()
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        resource_bank.get_resource_mut_with_ticks::<R>(ticks)
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_write::<R>();
+    }
+}
+
+impl<R> SystemParam for Option<Res<'_, R>>
+where
+    R: Resource,
+{
+    type Item<'rb> = Option<Res<'rb, R>>;
+
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        resource_bank.try_get_with_ticks::<R>(ticks)
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_read::<R>();
+    }
+}
+
+impl<R> SystemParam for Option<ResMut<'_, R>>
+where
+    R: Resource,
+{
+    type Item<'rb> = Option<ResMut<'rb, R>>;
+
+    fn from_resource_bank(resource_bank: &ResourceBank, ticks: SystemTicks) -> Self::Item<'_> {
+        resource_bank.try_get_mut_with_ticks::<R>(ticks)
+    }
+
+    fn access(access: &mut SystemAccess) {
+        access.add_write::<R>();
     }
 }
 
 pub trait System<Marker> {
-    fn run(&mut self, resource_bank: &ResourceBank);
+    fn run(&mut self, resource_bank: &ResourceBank, ticks: SystemTicks);
+
+    fn access(&self) -> SystemAccess;
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// A system that decides whether others run, see `IntoSystemConfig::run_if`.
+pub trait Condition<Marker> {
+    fn evaluate(&mut self, resource_bank: &ResourceBank, ticks: SystemTicks) -> bool;
+
+    fn access(&self) -> SystemAccess;
 }
 
 macro_rules! impl_system {
@@ -42,11 +175,93 @@ macro_rules! impl_system {
             F: FnMut($($param),*) + FnMut($(SystemParamItem<$param>),*),
             $($param: SystemParam),*
         {
-            fn run(&mut self, _resource_bank: &ResourceBank) {
-                self($(<$param as SystemParam>::from_resource_bank(_resource_bank)),*);
+            fn run(&mut self, _resource_bank: &ResourceBank, _ticks: SystemTicks) {
+                self($(<$param as SystemParam>::from_resource_bank(_resource_bank, _ticks)),*);
+            }
+
+            fn access(&self) -> SystemAccess {
+                let mut _access = SystemAccess::default();
+                $(<$param as SystemParam>::access(&mut _access);)*
+                _access
+            }
+        }
+    };
+}
+
+macro_rules! impl_condition {
+    ($($param:ident),*) => {
+        impl<F, $($param: SystemParam),*> Condition<fn($($param),*) -> bool> for F
+        where
+            F: FnMut($($param),*) -> bool + FnMut($(SystemParamItem<$param>),*) -> bool,
+            $($param: SystemParam),*
+        {
+            fn evaluate(&mut self, _resource_bank: &ResourceBank, _ticks: SystemTicks) -> bool {
+                self($(<$param as SystemParam>::from_resource_bank(_resource_bank, _ticks)),*)
+            }
+
+            fn access(&self) -> SystemAccess {
+                let mut _access = SystemAccess::default();
+                $(<$param as SystemParam>::access(&mut _access);)*
+                _access
             }
         }
     };
 }
 
 generate_tuples!(impl_system, 16);
+generate_tuples!(impl_condition, 16);
+
+#[cfg(test)]
+mod tests {
+    use voxei_macros::{Resource, SystemParam};
+
+    use super::*;
+    use crate::engine::{
+        common::time::Time,
+        schedule::{Schedule, Stage},
+    };
+
+    #[derive(Resource)]
+    struct Speed(u32);
+
+    #[derive(Resource)]
+    struct Position(u32);
+
+    #[derive(Resource)]
+    struct Missing;
+
+    #[derive(SystemParam)]
+    struct Movement<'rb> {
+        speed: Res<'rb, Speed>,
+        position: ResMut<'rb, Position>,
+        missing: Option<Res<'rb, Missing>>,
+    }
+
+    #[test]
+    fn test_derived_param() {
+        let mut resource_bank = ResourceBank::new();
+        resource_bank.insert(Time::new());
+        resource_bank.insert(Speed(2));
+        resource_bank.insert(Position(1));
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, |mut movement: Movement| {
+            assert!(movement.missing.is_none());
+            movement.position.0 += movement.speed.0;
+        });
+        schedule.run(&mut resource_bank);
+        assert_eq!(resource_bank.get_resource::<Position>().0, 3);
+    }
+
+    #[test]
+    fn test_derived_param_access() {
+        let system = |_: Movement, _: Res<Position>| {};
+        let access = System::access(&system);
+        assert_eq!(
+            access.conflicts(),
+            [(std::any::type_name::<Position>(), ConflictKind::ReadWrite)]
+        );
+        assert_eq!(access.reads().count(), 3);
+        assert_eq!(access.writes().count(), 1);
+    }
+}
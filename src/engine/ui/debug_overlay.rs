@@ -0,0 +1,322 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector3;
+use voxei_macros::Resource;
+
+use crate::{
+    engine::{
+        common::{time::Time, transform::Transform},
+        ecs::{ecs_world::ECSWorld, tracked::Tracked},
+        graphics::pass::voxel::VoxelPipeline,
+        input::Input,
+        resource::{Res, ResMut},
+        schedule::SystemTimings,
+        voxel::{
+            dynamic_world::MemoryUsage,
+            vox_constants::{BRICK_LENGTH, CHUNK_LENGTH, VOXEL_WORLD_LENGTH},
+            vox_world::VoxelWorld,
+        },
+    },
+    game::player::player::PlayerTag,
+    settings::Settings,
+};
+
+use super::egui::Egui;
+
+/// How many frames the timing graphs show.
+const FRAME_HISTORY: usize = 240;
+/// The graphs have a line at 60 fps.
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+const GRAPH_SIZE: [f32; 2] = [FRAME_HISTORY as f32, 48.0];
+/// The slowest systems listed in the timings table.
+const SHOWN_SYSTEMS: usize = 16;
+
+/// Frame timings, voxel world stats and the player's position drawn over the game, toggled with
+/// the `toggle_debug_overlay` action.
+#[derive(Resource)]
+pub struct DebugOverlay {
+    visible: bool,
+    /// In milliseconds, oldest first.
+    frame_times: VecDeque<f32>,
+    /// The summed system timings of each frame in milliseconds, oldest first.
+    system_times: VecDeque<f32>,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            system_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Toggles the overlay and records the frame's timings, also while hidden so the graphs are
+    /// full when it opens.
+    pub fn update(
+        mut overlay: ResMut<DebugOverlay>,
+        input: Res<Input>,
+        time: Res<Time>,
+        system_timings: Res<SystemTimings>,
+    ) {
+        let overlay = &mut *overlay;
+        if input.action_pressed("toggle_debug_overlay") {
+            overlay.visible = !overlay.visible;
+        }
+
+        push_sample(
+            &mut overlay.frame_times,
+            time.delta_time().as_secs_f32() * 1000.0,
+        );
+        push_sample(
+            &mut overlay.system_times,
+            system_timings.total().as_secs_f32() * 1000.0,
+        );
+    }
+
+    pub fn draw(
+        overlay: Res<DebugOverlay>,
+        egui: Res<Egui>,
+        system_timings: Res<SystemTimings>,
+        vox_world: Res<VoxelWorld>,
+        voxel_pipeline: Res<VoxelPipeline>,
+        ecs_world: Res<ECSWorld>,
+        settings: Res<Settings>,
+    ) {
+        let player_position = ecs_world
+            .query::<&Tracked<Transform>>()
+            .with::<&PlayerTag>()
+            .iter()
+            .next()
+            .map(|(_, transform)| transform.isometry.translation.vector);
+
+        egui::Window::new("Debug")
+            .default_pos([8.0, 8.0])
+            .resizable(false)
+            .show(egui.ctx(), |ui| {
+                overlay.draw_timings(ui, &system_timings);
+                ui.separator();
+                draw_voxel_stats(ui, &vox_world, &voxel_pipeline, &settings);
+                if let Some(player_position) = player_position {
+                    ui.separator();
+                    draw_player_position(ui, player_position);
+                }
+            });
+    }
+
+    fn draw_timings(&self, ui: &mut egui::Ui, system_timings: &SystemTimings) {
+        let frame_ms = self.frame_times.back().copied().unwrap_or(0.0);
+        ui.label(format!(
+            "Frame: {:.2} ms ({:.0} fps)",
+            frame_ms,
+            1000.0 / frame_ms.max(0.001)
+        ));
+        timing_graph(ui, &self.frame_times, egui::Color32::LIGHT_GREEN);
+
+        ui.label(format!(
+            "Systems: {:.2} ms",
+            system_timings.total().as_secs_f32() * 1000.0
+        ));
+        timing_graph(ui, &self.system_times, egui::Color32::LIGHT_BLUE);
+
+        egui::CollapsingHeader::new("System timings").show(ui, |ui| {
+            let mut timings = system_timings.timings().iter().collect::<Vec<_>>();
+            timings.sort_by(|a, b| b.duration.cmp(&a.duration));
+
+            egui::Grid::new("system_timings")
+                .striped(true)
+                .show(ui, |ui| {
+                    for timing in timings.into_iter().take(SHOWN_SYSTEMS) {
+                        ui.label(format!("{:?}", timing.stage));
+                        ui.label(short_system_name(timing.name));
+                        ui.label(format!("{:.3} ms", timing.duration.as_secs_f32() * 1000.0));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<f32>, sample: f32) {
+    if samples.len() == FRAME_HISTORY {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// A line graph of the samples in milliseconds, scaled to fit the slowest.
+fn timing_graph(ui: &mut egui::Ui, samples: &VecDeque<f32>, color: egui::Color32) {
+    let (rect, _) = ui.allocate_exact_size(GRAPH_SIZE.into(), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(150));
+
+    let max_ms = samples
+        .iter()
+        .copied()
+        .fold(TARGET_FRAME_MS * 2.0, f32::max);
+    let y = |ms: f32| rect.bottom() - ms / max_ms * rect.height();
+    painter.hline(
+        rect.x_range(),
+        y(TARGET_FRAME_MS),
+        egui::Stroke::new(1.0, egui::Color32::from_gray(100)),
+    );
+
+    let points = samples
+        .iter()
+        .enumerate()
+        .map(|(i, ms)| {
+            let x = rect.left() + i as f32 / (FRAME_HISTORY - 1) as f32 * rect.width();
+            egui::pos2(x, y(*ms))
+        })
+        .collect::<Vec<_>>();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+}
+
+fn draw_voxel_stats(
+    ui: &mut egui::Ui,
+    vox_world: &VoxelWorld,
+    voxel_pipeline: &VoxelPipeline,
+    settings: &Settings,
+) {
+    let stats = vox_world.dyn_world().stats(settings);
+    ui.label(format!(
+        "Chunks: {} loaded, {} loading, {} empty, {} unloaded",
+        stats.loaded_chunks, stats.loading_chunks, stats.empty_chunks, stats.unloaded_chunks
+    ));
+    ui.label(format!(
+        "Bricks: {} live, {} free slots",
+        stats.live_brick_slots, stats.free_brick_slots
+    ));
+    ui.label(format!(
+        "Brick index blocks: {} live, {} free",
+        stats.live_brick_index_blocks, stats.free_brick_index_blocks
+    ));
+    ui.label(format!(
+        "Upload queues: {} brick updates, {} normal updates",
+        voxel_pipeline.queued_brick_update_count(),
+        voxel_pipeline.queued_brick_normal_update_count()
+    ));
+
+    for (name, usage) in [
+        ("Brick indices", stats.gpu_brick_index_pool),
+        ("Brick data", stats.gpu_brick_data),
+        ("Palettes", stats.gpu_brick_palettes),
+        ("Palette indices", stats.gpu_brick_palette_indices),
+    ] {
+        draw_memory_usage(ui, name, usage);
+    }
+
+    let overflow = &stats.overflow;
+    let overflowed = overflow.brick_index_blocks
+        + overflow.gpu_brick_indices
+        + overflow.gpu_brick_data
+        + overflow.gpu_brick_palettes;
+    if overflowed > 0 {
+        ui.colored_label(
+            egui::Color32::LIGHT_RED,
+            format!("Overflow: {:?}", overflow),
+        );
+    }
+}
+
+fn draw_memory_usage(ui: &mut egui::Ui, name: &str, usage: MemoryUsage) {
+    const MIB: f32 = 1024.0 * 1024.0;
+    ui.add(
+        egui::ProgressBar::new(usage.fraction().min(1.0))
+            .desired_width(GRAPH_SIZE[0])
+            .text(format!(
+                "{}: {:.1} / {:.1} MiB",
+                name,
+                usage.used as f32 / MIB,
+                usage.max as f32 / MIB
+            )),
+    );
+}
+
+fn draw_player_position(ui: &mut egui::Ui, position: Vector3<f32>) {
+    let coordinates = VoxelCoordinates::from_world_position(position);
+    ui.label(format!(
+        "Position: {:.2} {:.2} {:.2}",
+        position.x, position.y, position.z
+    ));
+    ui.label(format!(
+        "Chunk: {} {} {}",
+        coordinates.chunk.x, coordinates.chunk.y, coordinates.chunk.z
+    ));
+    ui.label(format!(
+        "Brick in chunk: {} {} {}",
+        coordinates.brick.x, coordinates.brick.y, coordinates.brick.z
+    ));
+    ui.label(format!(
+        "Voxel in brick: {} {} {}",
+        coordinates.voxel.x, coordinates.voxel.y, coordinates.voxel.z
+    ));
+}
+
+/// Where a world position is in the voxel grid.
+#[derive(Debug, PartialEq)]
+struct VoxelCoordinates {
+    chunk: Vector3<i32>,
+    /// Within the chunk.
+    brick: Vector3<i32>,
+    /// Within the brick.
+    voxel: Vector3<i32>,
+}
+
+impl VoxelCoordinates {
+    fn from_world_position(position: Vector3<f32>) -> Self {
+        let voxel = position.map(|x| (x / VOXEL_WORLD_LENGTH).floor() as i32);
+        let brick = voxel.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+        Self {
+            chunk: brick.map(|x| x.div_euclid(CHUNK_LENGTH as i32)),
+            brick: brick.map(|x| x.rem_euclid(CHUNK_LENGTH as i32)),
+            voxel: voxel.map(|x| x.rem_euclid(BRICK_LENGTH as i32)),
+        }
+    }
+}
+
+/// Drops the module path from a system's type name so `voxei::engine::common::time::Time::update`
+/// reads `Time::update`.
+fn short_system_name(name: &str) -> &str {
+    let path_end = name.find('<').unwrap_or(name.len());
+    let start = name[..path_end]
+        .rmatch_indices("::")
+        .nth(1)
+        .map_or(0, |(i, _)| i + 2);
+    &name[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voxel_coordinates() {
+        let coordinates = VoxelCoordinates::from_world_position(Vector3::new(70.5, -0.5, 8.0));
+        assert_eq!(
+            coordinates,
+            VoxelCoordinates {
+                chunk: Vector3::new(1, -1, 0),
+                brick: Vector3::new(0, 7, 1),
+                voxel: Vector3::new(6, 7, 0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_short_system_name() {
+        assert_eq!(
+            short_system_name("voxei::engine::common::time::Time::update"),
+            "Time::update"
+        );
+        assert_eq!(
+            short_system_name("voxei::engine::event::Events<voxei::game::Hit>::update"),
+            "event::Events<voxei::game::Hit>::update"
+        );
+        assert_eq!(short_system_name("update"), "update");
+    }
+}
@@ -1,14 +1,24 @@
 use parking_lot::Mutex;
 use voxei_macros::Resource;
 
-use crate::engine::window::window::Window;
+use crate::engine::{
+    input::Input,
+    resource::{Res, ResMut},
+    window::window::Window,
+};
 
-/// Rendering found in graphics/egui.rs
+/// egui's context and its last frame's output, systems build UI with `Egui::ctx` between
+/// `Egui::begin_frame` and `Egui::end_frame`. Rendering found in graphics/pass/egui.rs
 #[derive(Resource)]
 pub struct Egui {
     ctx: egui::Context,
     primary_state: Mutex<egui_winit::State>,
     viewport_info: egui::ViewportInfo,
+
+    primitives: Vec<egui::ClippedPrimitive>,
+    pixels_per_point: f32,
+    /// Texture changes since the renderer last took them, frames it skips still add to these.
+    textures_delta: egui::TexturesDelta,
 }
 
 impl Egui {
@@ -27,6 +37,10 @@ impl Egui {
                 None,
             )),
             viewport_info: egui::ViewportInfo::default(),
+
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+            textures_delta: egui::TexturesDelta::default(),
         }
     }
 
@@ -44,10 +58,12 @@ impl Egui {
         response.consumed
     }
 
-    pub fn draw_ui(&mut self, window: &Window, func: impl FnOnce(&egui::Context) -> ()) {
-        egui_winit::update_viewport_info(&mut self.viewport_info, &self.ctx, window.handle());
+    /// Starts the frame's UI with the window events egui received since the last frame.
+    pub fn begin_frame(mut egui: ResMut<Egui>, window: Res<Window>) {
+        let egui = &mut *egui;
+        egui_winit::update_viewport_info(&mut egui.viewport_info, &egui.ctx, window.handle());
 
-        let mut raw_input = self
+        let mut raw_input = egui
             .primary_state
             .get_mut()
             .take_egui_input(window.handle());
@@ -55,18 +71,59 @@ impl Egui {
         raw_input.viewport_id = egui::ViewportId::ROOT;
         raw_input
             .viewports
-            .insert(egui::ViewportId::ROOT, self.viewport_info.clone());
+            .insert(egui::ViewportId::ROOT, egui.viewport_info.clone());
 
-        let full_output = self
-            .primary_state
+        egui.ctx.begin_frame(raw_input);
+    }
+
+    /// Finishes the frame's UI and tessellates it for the renderer.
+    pub fn end_frame(mut egui: ResMut<Egui>, window: Res<Window>, input: Res<Input>) {
+        let egui = &mut *egui;
+        let mut full_output = egui.ctx.end_frame();
+
+        // egui would show the cursor again while the game has it hidden.
+        if !window.is_cursor_visible() {
+            full_output.platform_output.cursor_icon = egui::CursorIcon::None;
+        }
+        // egui-winit allows the IME and places it from this, egui's own text fields come first.
+        if full_output.platform_output.ime.is_none() {
+            if let Some(area) = input.keyboard().text_input_area() {
+                let pixels_per_point = full_output.pixels_per_point;
+                let rect = egui::Rect::from_min_size(
+                    egui::pos2(area.x, area.y) / pixels_per_point,
+                    egui::vec2(area.width, area.height) / pixels_per_point,
+                );
+                full_output.platform_output.ime = Some(egui::output::IMEOutput {
+                    rect,
+                    cursor_rect: rect,
+                });
+            }
+        }
+        egui.primary_state
             .get_mut()
-            .egui_ctx()
-            .run(raw_input, |ui| {
-                func(ui);
-            });
+            .handle_platform_output(window.handle(), full_output.platform_output);
 
-        let triangles = self
+        egui.primitives = egui
             .ctx
             .tessellate(full_output.shapes, full_output.pixels_per_point);
+        egui.pixels_per_point = full_output.pixels_per_point;
+        egui.textures_delta.append(full_output.textures_delta);
+    }
+
+    pub fn ctx(&self) -> &egui::Context {
+        &self.ctx
+    }
+
+    /// The last finished frame's meshes, in points.
+    pub fn primitives(&self) -> &[egui::ClippedPrimitive] {
+        &self.primitives
+    }
+
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    pub fn take_textures_delta(&mut self) -> egui::TexturesDelta {
+        std::mem::take(&mut self.textures_delta)
     }
 }
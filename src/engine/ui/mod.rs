@@ -1 +1,3 @@
+pub mod debug_overlay;
 pub mod egui;
+pub mod plugin;
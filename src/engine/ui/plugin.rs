@@ -0,0 +1,38 @@
+use crate::{
+    app::App,
+    engine::{
+        graphics::plugin::RenderPlugin,
+        input::plugin::InputPlugin,
+        plugin::{Plugin, PluginId},
+        resource::Res,
+        schedule::{IntoSystemConfig, Stage},
+        voxel::plugin::VoxelWorldPlugin,
+    },
+};
+
+use super::debug_overlay::DebugOverlay;
+
+/// The debug overlay toggled with F3.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugOverlay::new());
+
+        app.add_system(Stage::Update, DebugOverlay::update.label("debug_overlay"))
+            .add_system(
+                Stage::Update,
+                DebugOverlay::draw
+                    .after("debug_overlay")
+                    .run_if(|overlay: Res<DebugOverlay>| overlay.is_visible()),
+            );
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<InputPlugin>(),
+            PluginId::of::<VoxelWorldPlugin>(),
+            PluginId::of::<RenderPlugin>(),
+        ]
+    }
+}
@@ -85,12 +85,14 @@ impl ChunkGenerator {
     }
 
     // Update bounds so we skip generating chunks that are no longer in bounds of the dyn world.
+    // The max bound is exclusive.
     pub fn update_bounds(&mut self, chunk_center: WorldChunkPos, render_distance: ChunkRadius) {
-        let hl = render_distance.pow2_half_side_length() as i32;
-        let hl = Vector3::new(hl, hl, hl);
+        let hl = render_distance.half_side_length() as i32;
         let mut dyn_world_chunk_bounds = self.dyn_world_chunk_bounds.write().unwrap();
-        dyn_world_chunk_bounds.0 = chunk_center.vector - hl;
-        dyn_world_chunk_bounds.1 = chunk_center.vector + hl - Vector3::new(1, 1, 1);
+        dyn_world_chunk_bounds.0 = chunk_center.vector.add_scalar(-hl);
+        dyn_world_chunk_bounds.1 = dyn_world_chunk_bounds
+            .0
+            .add_scalar(render_distance.side_length() as i32);
     }
 
     pub fn generate_chunk(&mut self, chunk_pos: WorldChunkPos) {
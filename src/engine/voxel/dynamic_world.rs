@@ -1,29 +1,50 @@
+use std::collections::HashMap;
+
 use nalgebra::{SimdPartialOrd, Vector3};
 
-use crate::{engine::voxel::vox_constants::CHUNK_VOLUME, settings::Settings};
+use crate::{
+    engine::{
+        geometry::{ray::Ray, shapes::aabb::AABB},
+        voxel::vox_constants::CHUNK_VOLUME,
+    },
+    settings::Settings,
+};
 
 use super::{
     chunk_generator::GeneratedChunk,
+    events::{VoxelEditSource, VoxelEvent},
     util::{next_pow2, Morton},
-    vox_constants::{BRICK_AREA, BRICK_VOLUME, SUPER_CHUNK_VOLUME},
-    vox_world::{ChunkRadius, DynChunkPos, WorldChunkPos},
+    vox_constants::{
+        BRICK_AREA, BRICK_LENGTH, BRICK_MORTON_LENGTH, BRICK_VOLUME, CHUNK_LENGTH,
+        SUPER_CHUNK_LENGTH, VOXEL_WORLD_LENGTH,
+    },
+    vox_world::{ChunkRadius, DynBrickPos, DynChunkPos, WorldChunkPos},
 };
 
 /// Our voxel world representation for rendering and is more easily editable due to the flat array
 /// structure for each brick and masks for each hierarchy level.
+///
+/// Each chunk in the chunk indices grid points to a block of brick indices in the brick index
+/// pool, blocks are only allocated for chunks that are loaded and not empty.
 pub struct DynVoxelWorld {
+    /// A bit per `SUPER_CHUNK_LENGTH` cubed group of chunks in the chunk grid's memory layout, set
+    /// when any chunk in the group is loaded with bricks.
     super_chunk_grid_mask: BitGridMask,
-    chunk_occupancy_mask: GridMask,
-    chunk_bit_mask: BitGridMask,
+    chunk_indices_grid: ChunkIndexGrid,
     chunk_normal_grid: BitGridMask,
-    brick_indices_grid: BrickIndexGrid,
+    brick_index_pool: BrickIndexPool,
     brick_data: BrickDataList,
     brick_palette_data: BrickPaletteList,
 
     brick_changes: Vec<BrickChange>,
-    brick_normal_updates: Vec<BrickChange>,
+    brick_normal_updates: Vec<BrickNormalUpdate>,
+    allocated_blocks: Vec<u32>,
+    resizes: Vec<DynWorldResize>,
+    overflow: DynWorldOverflow,
+    events: Vec<VoxelEvent>,
 
     chunk_render_distance: ChunkRadius,
+    chunk_center: WorldChunkPos,
 
     /// The logical local translation we perform so memory can stay in place as we change origins.
     chunk_translation: Vector3<i32>,
@@ -31,37 +52,98 @@ pub struct DynVoxelWorld {
 
 impl DynVoxelWorld {
     pub fn new(settings: &Settings) -> Self {
-        let chunk_render_volume = settings.chunk_render_distance.pow2_volume();
+        let chunk_render_volume = settings.chunk_render_distance.volume();
         println!("Chunk render volume: {}", chunk_render_volume);
-        let super_chunk_render_volume =
-            (chunk_render_volume as f32 / SUPER_CHUNK_VOLUME as f32).ceil() as u64;
+        let super_chunk_render_volume = super_chunk_volume(settings.chunk_render_distance);
         println!("Super chunk render volume: {}", super_chunk_render_volume);
-        let brick_render_volume = chunk_render_volume * BRICK_VOLUME as u64;
-        println!("Brick render volume: {}", brick_render_volume);
 
         Self {
             super_chunk_grid_mask: BitGridMask::new(super_chunk_render_volume as usize),
-            chunk_occupancy_mask: GridMask::new(chunk_render_volume as usize),
-            chunk_bit_mask: BitGridMask::new(chunk_render_volume as usize),
+            chunk_indices_grid: ChunkIndexGrid::new(chunk_render_volume as usize),
             chunk_normal_grid: BitGridMask::new(chunk_render_volume as usize),
-            brick_indices_grid: BrickIndexGrid::new(brick_render_volume as usize),
+            brick_index_pool: BrickIndexPool::new(settings.brick_index_block_max_size),
             brick_data: BrickDataList::new(),
             brick_palette_data: BrickPaletteList::new(),
 
             brick_changes: Vec::new(),
             brick_normal_updates: Vec::new(),
+            allocated_blocks: Vec::new(),
+            resizes: Vec::new(),
+            overflow: DynWorldOverflow::default(),
+            events: Vec::new(),
 
             chunk_render_distance: settings.chunk_render_distance,
+            chunk_center: WorldChunkPos::new(0, 0, 0),
             chunk_translation: Vector3::zeros(),
         }
     }
 
+    /// The bytes used by the grids which scale with the render distance.
+    pub fn grid_memory_size(&self) -> usize {
+        self.super_chunk_grid_mask.buffer_size()
+            + self.chunk_indices_grid.buffer_size()
+            + self.chunk_normal_grid.buffer_size()
+    }
+
+    /// Reallocates the grids for a new render distance and moves over every chunk that still fits
+    /// around the chunk center. Brick index blocks, brick data and palettes are referenced by index
+    /// so they stay in place, only the chunk grids are rebuilt.
+    pub fn resize(&mut self, chunk_render_distance: ChunkRadius, chunk_center: WorldChunkPos) {
+        let old_chunk_render_distance = self.chunk_render_distance;
+        let slm = chunk_render_distance.side_length() as i32;
+        let resize = DynWorldResize {
+            old_chunk_render_distance,
+            new_chunk_render_distance: chunk_render_distance,
+            local_offset: chunk_render_distance.half_side_length() as i32
+                - old_chunk_render_distance.half_side_length() as i32,
+            old_translation: self.chunk_translation,
+            new_translation: chunk_center.vector.map(|x| x.rem_euclid(slm)),
+        };
+
+        let chunk_render_volume = chunk_render_distance.volume();
+        let mut chunk_indices_grid = ChunkIndexGrid::new(chunk_render_volume as usize);
+        let mut chunk_normal_grid = BitGridMask::new(chunk_render_volume as usize);
+
+        for old_index in 0..old_chunk_render_distance.volume() {
+            let chunk_index = self.chunk_indices_grid.get(old_index);
+            if chunk_index.status() == SpatialStatus::Unloaded {
+                continue;
+            }
+            let Some(new_index) = resize.remap_chunk(old_index) else {
+                if chunk_index.status() == SpatialStatus::Loaded {
+                    self.free_block(chunk_index.block_index());
+                }
+                if chunk_index.status().is_loaded() {
+                    let dyn_pos = DynChunkPos::from_index(old_index, old_chunk_render_distance);
+                    self.events
+                        .push(VoxelEvent::ChunkUnloaded(self.world_chunk_pos(dyn_pos)));
+                }
+                continue;
+            };
+
+            chunk_indices_grid.set(new_index, chunk_index);
+            chunk_normal_grid.set_status(new_index, self.chunk_normal_grid.status(old_index));
+        }
+
+        self.chunk_indices_grid = chunk_indices_grid;
+        self.chunk_normal_grid = chunk_normal_grid;
+        self.chunk_render_distance = chunk_render_distance;
+        self.chunk_translation = resize.new_translation;
+        self.chunk_center = chunk_center;
+        self.rebuild_super_chunk_grid_mask();
+
+        // Normal updates that haven't been collected yet are moved to the new layout directly.
+        self.brick_normal_updates =
+            resize.remap_brick_normal_updates(std::mem::take(&mut self.brick_normal_updates));
+        self.resizes.push(resize);
+    }
+
     pub fn update_translation(
         &mut self,
         chunk_translation: Vector3<i32>,
         old_chunk_center: WorldChunkPos,
     ) {
-        let slm = self.chunk_render_distance.pow2_side_length() as i32;
+        let slm = self.chunk_render_distance.side_length() as i32;
         let new_chunk_center = old_chunk_center.vector + chunk_translation;
         let dyn_translation = Vector3::new(
             new_chunk_center.x.rem_euclid(slm),
@@ -69,7 +151,6 @@ impl DynVoxelWorld {
             new_chunk_center.z.rem_euclid(slm),
         );
         let old_dyn_translation = self.chunk_translation;
-        self.chunk_translation = dyn_translation;
 
         // Calculates on each axis the range of values on that axis need to be unloaded, the reference, my whiteboard
         let unloaded =
@@ -107,132 +188,549 @@ impl DynVoxelWorld {
                 }
             }
         }
+
+        // Only moved after unloading so the unloaded chunks report their old world positions.
+        self.chunk_translation = dyn_translation;
+        self.chunk_center = WorldChunkPos {
+            vector: new_chunk_center,
+        };
+    }
+
+    /// The world position the local chunk position currently maps to.
+    pub fn world_chunk_pos(&self, local_chunk_pos: DynChunkPos) -> WorldChunkPos {
+        let slm = self.chunk_render_distance.side_length() as i32;
+        let hl = self.chunk_render_distance.half_side_length() as i32;
+        let local_pos = (local_chunk_pos.vector.map(|x| x as i32) - self.chunk_translation)
+            .map(|x| x.rem_euclid(slm));
+        WorldChunkPos {
+            vector: local_pos.add_scalar(-hl) + self.chunk_center.vector,
+        }
+    }
+
+    /// The local chunk position the world position maps to, None if it's outside the render
+    /// distance.
+    pub fn dyn_chunk_pos(&self, world_chunk_pos: WorldChunkPos) -> Option<DynChunkPos> {
+        let slm = self.chunk_render_distance.side_length() as i32;
+        let hl = self.chunk_render_distance.half_side_length() as i32;
+        let local_pos = world_chunk_pos.vector - self.chunk_center.vector.add_scalar(-hl);
+        if local_pos.iter().any(|x| *x < 0 || *x >= slm) {
+            return None;
+        }
+
+        let mem_pos = (local_pos + self.chunk_translation).map(|x| x.rem_euclid(slm) as u32);
+        Some(DynChunkPos { vector: mem_pos })
+    }
+
+    /// The dyn brick index of the world brick position, None if it's outside the render
+    /// distance. See `DynBrickPos::index`.
+    pub fn world_dyn_brick_index(&self, world_brick_pos: Vector3<i32>) -> Option<u64> {
+        let chunk_pos = world_brick_pos.map(|x| x.div_euclid(CHUNK_LENGTH as i32));
+        let dyn_chunk_pos = self.dyn_chunk_pos(WorldChunkPos { vector: chunk_pos })?;
+
+        let local_brick_pos = world_brick_pos.map(|x| x.rem_euclid(CHUNK_LENGTH as i32) as u32);
+        let dyn_brick_pos = DynBrickPos {
+            vector: dyn_chunk_pos.vector * CHUNK_LENGTH as u32 + local_brick_pos,
+        };
+        Some(dyn_brick_pos.index(self.chunk_render_distance))
+    }
+
+    /// Looks up a brick by its world brick position, bricks outside the render distance are
+    /// unloaded.
+    pub fn world_brick_index(&self, world_brick_pos: Vector3<i32>) -> BrickIndex {
+        self.world_dyn_brick_index(world_brick_pos)
+            .map_or(BrickIndex::new_unloaded(), |dyn_brick_index| {
+                self.brick_index(dyn_brick_index)
+            })
+    }
+
+    /// Whether the voxel at the world voxel position is solid, unloaded voxels are empty.
+    pub fn is_world_voxel_set(&self, world_voxel_pos: Vector3<i32>) -> bool {
+        let brick_pos = world_voxel_pos.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+        let brick_index = self.world_brick_index(brick_pos);
+        if brick_index.status() != SpatialStatus::Loaded {
+            return false;
+        }
+
+        let voxel_pos = world_voxel_pos.map(|x| x.rem_euclid(BRICK_LENGTH as i32) as u32);
+        self.brick_data
+            .get(brick_index.index())
+            .is_voxel_set(*Morton::encode(voxel_pos))
+    }
+
+    /// Steps the ray through the voxel grid and returns the first solid voxel within the max
+    /// distance, the ray's direction is expected to be normalized.
+    pub fn raycast_voxel(&self, ray: &Ray, max_distance: f32) -> Option<Vector3<i32>> {
+        let origin = ray.position().coords / VOXEL_WORLD_LENGTH;
+        let step = ray.direction().map(|x| if x < 0.0 { -1 } else { 1 });
+        let t_delta = ray.inv_direction().abs() * VOXEL_WORLD_LENGTH;
+
+        let mut voxel_pos = origin.map(|x| x.floor() as i32);
+        // The t where the ray crosses into the next voxel on each axis.
+        let mut t_next = Vector3::from_fn(|axis, _| {
+            let boundary = voxel_pos[axis] as f32 + (step[axis] > 0) as i32 as f32;
+            (boundary - origin[axis]) * ray.inv_direction()[axis] * VOXEL_WORLD_LENGTH
+        });
+        let mut t = 0.0;
+        while t <= max_distance {
+            if self.is_world_voxel_set(voxel_pos) {
+                return Some(voxel_pos);
+            }
+
+            let (axis, axis_t) = t_next.argmin();
+            t = axis_t;
+            t_next[axis] += t_delta[axis];
+            voxel_pos[axis] += step[axis];
+        }
+
+        None
     }
 
     pub fn unload_chunk(&mut self, local_chunk_pos: DynChunkPos) {
-        let morton = local_chunk_pos.morton();
-        self.chunk_occupancy_mask
-            .set_status(morton, SpatialStatus::Unloaded);
-        self.chunk_bit_mask.set_status(morton, false);
-        self.chunk_normal_grid.set_status(morton, false);
+        let index = local_chunk_pos.index(self.chunk_render_distance);
+        if self.chunk_indices_grid.get(index).status().is_loaded() {
+            self.events.push(VoxelEvent::ChunkUnloaded(
+                self.world_chunk_pos(local_chunk_pos),
+            ));
+        }
+        self.free_chunk_block(index);
+        self.set_chunk_index(index, ChunkIndex::new(SpatialStatus::Unloaded));
+        self.chunk_normal_grid.set_status(index, false);
+    }
+
+    /// Returns the chunk's brick index block to the pool if it has one.
+    fn free_chunk_block(&mut self, index: u64) {
+        let chunk_index = self.chunk_indices_grid.get(index);
+        if chunk_index.status() == SpatialStatus::Loaded {
+            self.free_block(chunk_index.block_index());
+        }
+    }
+
+    /// Allocates a brick index block and reports it through `collect_allocated_blocks`, counts
+    /// the overflow if the pool is full.
+    fn allocate_block(&mut self) -> Option<u32> {
+        let block_index = self.brick_index_pool.allocate_block();
+        match block_index {
+            Some(block_index) => self.allocated_blocks.push(block_index),
+            None => self.overflow.brick_index_blocks += 1,
+        }
+        block_index
+    }
+
+    /// Frees the bricks referenced by the block then the block itself.
+    fn free_block(&mut self, block_index: u32) {
+        let slot_min = BrickIndexPool::block_slot_min(block_index);
+        for brick_slot in slot_min..(slot_min + CHUNK_VOLUME as u64) {
+            let brick_index = self.brick_index_pool.get(brick_slot);
+            if brick_index.status() == SpatialStatus::Loaded {
+                self.remove_brick(brick_index.index());
+            }
+        }
+        self.brick_index_pool.free_block(block_index);
+    }
+
+    /// Frees the brick's data slot and its palette.
+    fn remove_brick(&mut self, brick_data_index: u32) {
+        let brick_data = self.brick_data.get(brick_data_index);
+        self.brick_palette_data.remove(
+            brick_data.palette_index(),
+            brick_data.palette_size(),
+            self.brick_data.palette_entry_count(brick_data_index),
+        );
+        self.brick_data.remove(brick_data_index);
+    }
+
+    /// Sets the chunk's index and keeps its super chunk's bit in sync.
+    fn set_chunk_index(&mut self, index: u64, chunk_index: ChunkIndex) {
+        self.chunk_indices_grid.set(index, chunk_index);
+
+        let super_chunk_pos = super_chunk_pos(index, self.chunk_render_distance);
+        let chunk_min = super_chunk_pos * SUPER_CHUNK_LENGTH as u32;
+        let chunk_max = chunk_min
+            .add_scalar(SUPER_CHUNK_LENGTH as u32)
+            .inf(&Vector3::repeat(self.chunk_render_distance.side_length()));
+        let is_chunk_occupied = |x, y, z| {
+            let index = DynChunkPos::new(x, y, z).index(self.chunk_render_distance);
+            self.chunk_indices_grid.get(index).status() == SpatialStatus::Loaded
+        };
+        let is_occupied = chunk_index.status() == SpatialStatus::Loaded
+            || (chunk_min.z..chunk_max.z).any(|z| {
+                (chunk_min.y..chunk_max.y)
+                    .any(|y| (chunk_min.x..chunk_max.x).any(|x| is_chunk_occupied(x, y, z)))
+            });
+
+        self.super_chunk_grid_mask.set_status(
+            super_chunk_index(super_chunk_pos, self.chunk_render_distance),
+            is_occupied,
+        );
+    }
+
+    /// Rebuilds the super chunk grid mask from the chunk grid.
+    fn rebuild_super_chunk_grid_mask(&mut self) {
+        self.super_chunk_grid_mask =
+            BitGridMask::new(super_chunk_volume(self.chunk_render_distance) as usize);
+        for (index, chunk_index) in self.chunk_indices_grid.as_slice().iter().enumerate() {
+            if chunk_index.status() != SpatialStatus::Loaded {
+                continue;
+            }
+            let super_chunk_pos = super_chunk_pos(index as u64, self.chunk_render_distance);
+            self.super_chunk_grid_mask.set_status(
+                super_chunk_index(super_chunk_pos, self.chunk_render_distance),
+                true,
+            );
+        }
     }
 
     pub fn chunk_status(&self, local_chunk_pos: DynChunkPos) -> SpatialStatus {
-        let morton = Morton::encode(local_chunk_pos.vector);
-        self.chunk_occupancy_mask.status(morton)
+        let index = local_chunk_pos.index(self.chunk_render_distance);
+        self.chunk_indices_grid.get(index).status()
+    }
+
+    /// Looks up the brick index through the brick's chunk, see `DynBrickPos::index`.
+    pub fn brick_index(&self, dyn_brick_index: u64) -> BrickIndex {
+        let chunk_index = self
+            .chunk_indices_grid
+            .get(dyn_brick_index / CHUNK_VOLUME as u64);
+        match chunk_index.status() {
+            SpatialStatus::Loaded => self.brick_index_pool.get(
+                BrickIndexPool::block_slot_min(chunk_index.block_index())
+                    + dyn_brick_index % CHUNK_VOLUME as u64,
+            ),
+            SpatialStatus::LoadedEmpty => BrickIndex::new_loaded_empty(),
+            SpatialStatus::Unloaded | SpatialStatus::Loading => BrickIndex::new_unloaded(),
+        }
+    }
+
+    pub fn is_brick_loaded(&self, dyn_brick_index: u64) -> bool {
+        self.brick_index(dyn_brick_index).status().is_loaded()
+    }
+
+    /// Whether the voxel at the position local to the block's chunk sized volume is solid.
+    pub fn is_block_voxel_set(&self, block_index: u32, voxel_pos: Vector3<u32>) -> bool {
+        let brick_morton = Morton::encode(voxel_pos / BRICK_LENGTH as u32);
+        let brick_index = self
+            .brick_index_pool
+            .get(BrickIndexPool::block_slot_min(block_index) + *brick_morton);
+        if brick_index.status() != SpatialStatus::Loaded {
+            return false;
+        }
+
+        let voxel_morton = Morton::encode(voxel_pos.map(|x| x % BRICK_LENGTH as u32));
+        self.brick_data
+            .get(brick_index.index())
+            .is_voxel_set(*voxel_morton)
     }
 
-    pub fn is_brick_loaded(&self, morton: u64) -> bool {
-        self.brick_indices_grid.0[morton as usize]
-            .status()
-            .is_loaded()
+    /// The albedo of the voxel at the world voxel position, None if it's empty or unloaded.
+    pub fn world_voxel_albedo(&self, world_voxel_pos: Vector3<i32>) -> Option<Vector3<f32>> {
+        let brick_pos = world_voxel_pos.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+        let voxel_pos = world_voxel_pos.map(|x| x.rem_euclid(BRICK_LENGTH as i32) as u32);
+        self.brick_voxels(self.world_brick_index(brick_pos))[*Morton::encode(voxel_pos) as usize]
+    }
+
+    /// The albedo of each voxel in the brick in morton order.
+    fn brick_voxels(&self, brick_index: BrickIndex) -> Vec<Option<Vector3<f32>>> {
+        let mut voxels = vec![None; BRICK_VOLUME];
+        if brick_index.status() != SpatialStatus::Loaded {
+            return voxels;
+        }
+
+        let brick_data = self.brick_data.get(brick_index.index());
+        let palette = self
+            .brick_palette_data
+            .get(brick_data.palette_index(), brick_data.palette_size());
+        let palette_indices = self.brick_data.get_indices(brick_index.index());
+        for (voxel_morton, voxel) in voxels.iter_mut().enumerate() {
+            if brick_data.is_voxel_set(voxel_morton as u64) {
+                *voxel = Some(palette[palette_indices[voxel_morton] as usize].albedo());
+            }
+        }
+
+        voxels
+    }
+
+    /// Sets the voxels at the world voxel positions to the albedo, None removes the voxel. Voxels
+    /// in chunks that aren't loaded are skipped. Pushes a region edited event covering the
+    /// voxels that changed.
+    pub fn set_world_voxels(
+        &mut self,
+        voxels: impl IntoIterator<Item = (Vector3<i32>, Option<Vector3<f32>>)>,
+        source: VoxelEditSource,
+    ) {
+        let mut brick_edits =
+            HashMap::<Vector3<i32>, Vec<(Vector3<i32>, Option<Vector3<f32>>)>>::new();
+        for (world_voxel_pos, voxel) in voxels {
+            let brick_pos = world_voxel_pos.map(|x| x.div_euclid(BRICK_LENGTH as i32));
+            brick_edits
+                .entry(brick_pos)
+                .or_default()
+                .push((world_voxel_pos, voxel));
+        }
+
+        let mut edited_min = Vector3::repeat(i32::MAX);
+        let mut edited_max = Vector3::repeat(i32::MIN);
+        for (brick_pos, edits) in brick_edits {
+            let Some(dyn_brick_index) = self.world_dyn_brick_index(brick_pos) else {
+                continue;
+            };
+            let chunk_index = dyn_brick_index / CHUNK_VOLUME as u64;
+            let block_index = match self.chunk_indices_grid.get(chunk_index).status() {
+                SpatialStatus::Loaded => self.chunk_indices_grid.get(chunk_index).block_index(),
+                SpatialStatus::LoadedEmpty => {
+                    if edits.iter().all(|(_, voxel)| voxel.is_none()) {
+                        continue;
+                    }
+                    let Some(block_index) = self.allocate_block() else {
+                        continue;
+                    };
+                    self.set_chunk_index(chunk_index, ChunkIndex::new_loaded(block_index));
+                    let slot_min = BrickIndexPool::block_slot_min(block_index);
+                    for brick_slot in slot_min..(slot_min + CHUNK_VOLUME as u64) {
+                        self.set_brick(brick_slot, None);
+                    }
+                    block_index
+                }
+                SpatialStatus::Unloaded | SpatialStatus::Loading => continue,
+            };
+
+            let brick_slot =
+                BrickIndexPool::block_slot_min(block_index) + dyn_brick_index % CHUNK_VOLUME as u64;
+            let old_brick_index = self.brick_index_pool.get(brick_slot);
+            let mut brick_voxels = self.brick_voxels(old_brick_index);
+            for (world_voxel_pos, voxel) in edits {
+                let voxel_pos = world_voxel_pos.map(|x| x.rem_euclid(BRICK_LENGTH as i32) as u32);
+                brick_voxels[*Morton::encode(voxel_pos) as usize] = voxel;
+                edited_min = edited_min.inf(&world_voxel_pos);
+                edited_max = edited_max.sup(&world_voxel_pos.add_scalar(1));
+            }
+
+            if old_brick_index.status() == SpatialStatus::Loaded {
+                self.remove_brick(old_brick_index.index());
+            }
+            if brick_voxels.iter().all(|voxel| voxel.is_none()) {
+                self.set_brick(brick_slot, None);
+            } else {
+                let brick_data = BrickData::from_voxel_array(&brick_voxels);
+                let brick_palette = BrickPalette::from_voxel_array(&brick_voxels);
+                self.set_brick(brick_slot, Some((brick_data, brick_palette)));
+            }
+
+            // Normals sample across brick borders so the neighbours are recalculated too.
+            for offset in [
+                Vector3::zeros(),
+                Vector3::x(),
+                -Vector3::x(),
+                Vector3::y(),
+                -Vector3::y(),
+                Vector3::z(),
+                -Vector3::z(),
+            ] {
+                let Some(dyn_brick_index) = self.world_dyn_brick_index(brick_pos + offset) else {
+                    continue;
+                };
+                if self.brick_index(dyn_brick_index).status() == SpatialStatus::Loaded {
+                    self.brick_normal_updates
+                        .push(BrickNormalUpdate { dyn_brick_index });
+                }
+            }
+        }
+
+        if edited_min.x <= edited_max.x {
+            let aabb = AABB::new_min_max(
+                edited_min.map(|x| x as f32 * VOXEL_WORLD_LENGTH).into(),
+                edited_max.map(|x| x as f32 * VOXEL_WORLD_LENGTH).into(),
+            );
+            self.events.push(VoxelEvent::RegionEdited { aabb, source });
+        }
     }
 
     pub fn set_generated_chunk(&mut self, local_chunk_pos: DynChunkPos, chunk: GeneratedChunk) {
-        let morton = local_chunk_pos.morton();
+        let index = local_chunk_pos.index(self.chunk_render_distance);
+        self.free_chunk_block(index);
+        self.events.push(VoxelEvent::ChunkLoaded(
+            self.world_chunk_pos(local_chunk_pos),
+        ));
         if chunk.is_empty {
-            self.chunk_occupancy_mask
-                .set_status(morton, SpatialStatus::LoadedEmpty);
-        } else {
-            self.chunk_occupancy_mask
-                .set_status(morton, SpatialStatus::Loaded);
-            self.chunk_bit_mask.set_status(morton, true);
-            let local_brick_min = local_chunk_pos.to_dyn_brick_pos();
-            let local_brick_min_morton = *local_brick_min.morton();
-            for brick_morton in 0..CHUNK_VOLUME {
-                let generated_voxels = chunk.brick_data(Morton::new(brick_morton as u64));
-                let is_empty = generated_voxels.iter().all(|voxel| voxel.is_none());
-
-                let dyn_brick_morton = local_brick_min_morton + brick_morton as u64;
-                if is_empty {
-                    self.set_brick(dyn_brick_morton, None);
-                } else {
-                    let brick_data = BrickData::from_voxel_array(&generated_voxels);
-                    let brick_palette = BrickPalette::from_voxel_array(&generated_voxels);
-                    self.set_brick(dyn_brick_morton, Some((brick_data, brick_palette)));
-                }
+            self.set_chunk_index(index, ChunkIndex::new(SpatialStatus::LoadedEmpty));
+            return;
+        }
+
+        let Some(block_index) = self.allocate_block() else {
+            self.set_chunk_index(index, ChunkIndex::new(SpatialStatus::LoadedEmpty));
+            return;
+        };
+        self.set_chunk_index(index, ChunkIndex::new_loaded(block_index));
+        self.set_block_voxels(block_index, chunk.voxel_data.as_ref().unwrap());
+    }
+
+    /// Stores a chunk sized voxel volume in its own brick index block without placing it in the
+    /// grid, used by voxel objects. Returns None if the brick index pool is full.
+    pub fn insert_object_volume(&mut self, voxel_data: &[Option<Vector3<f32>>]) -> Option<u32> {
+        let block_index = self.allocate_block()?;
+        self.set_block_voxels(block_index, voxel_data);
+
+        Some(block_index)
+    }
+
+    pub fn remove_object_volume(&mut self, block_index: u32) {
+        self.free_block(block_index);
+    }
+
+    /// Sets every brick in the block from chunk voxel data in morton order.
+    fn set_block_voxels(&mut self, block_index: u32, voxel_data: &[Option<Vector3<f32>>]) {
+        let brick_slot_min = BrickIndexPool::block_slot_min(block_index);
+        for brick_morton in 0..CHUNK_VOLUME {
+            let voxel_morton_min = brick_morton << BRICK_MORTON_LENGTH;
+            let generated_voxels =
+                voxel_data[voxel_morton_min..(voxel_morton_min + BRICK_VOLUME)].to_vec();
+            let is_empty = generated_voxels.iter().all(|voxel| voxel.is_none());
+
+            let brick_slot = brick_slot_min + brick_morton as u64;
+            if is_empty {
+                self.set_brick(brick_slot, None);
+            } else {
+                let brick_data = BrickData::from_voxel_array(&generated_voxels);
+                let brick_palette = BrickPalette::from_voxel_array(&generated_voxels);
+                self.set_brick(brick_slot, Some((brick_data, brick_palette)));
             }
         }
     }
 
     pub fn set_chunk_loading(&mut self, local_chunk_pos: DynChunkPos) {
-        let morton = local_chunk_pos.morton();
-        self.chunk_occupancy_mask
-            .set_status(morton, SpatialStatus::Loading);
+        let index = local_chunk_pos.index(self.chunk_render_distance);
+        self.free_chunk_block(index);
+        self.set_chunk_index(index, ChunkIndex::new(SpatialStatus::Loading));
     }
 
-    pub fn set_brick(&mut self, morton: u64, brick: Option<(BrickData, BrickPalette)>) {
+    /// Sets the brick at the slot in the brick index pool, see `BrickIndexPool::block_slot_min`.
+    pub fn set_brick(&mut self, brick_slot: u64, brick: Option<(BrickData, BrickPalette)>) {
         let brick_index = if let Some((mut brick_data, mut brick_material_data)) = brick {
-            let size_i = match brick_material_data.next_pow_2_size() {
-                64 => 0,
-                128 => 1,
-                256 => 2,
-                512 => 3,
-                _ => unreachable!(),
-            };
+            let size_i = palette_size_class(brick_material_data.next_pow_2_size()) as u32;
             let indices = brick_material_data.indices.take();
+            let palette_entry_count = brick_material_data.data.len() as u16;
             let material_index = self.brick_palette_data.insert(brick_material_data);
             brick_data.palette_index = material_index | (size_i << 30);
-            let index = self.brick_data.insert(brick_data, *indices.unwrap());
-            if index == 1347 {
-                println!("palette index: {}", material_index);
-            }
+            let index = self
+                .brick_data
+                .insert(brick_data, *indices.unwrap(), palette_entry_count);
             BrickIndex::new_loaded(index)
         } else {
             BrickIndex::new_loaded_empty()
         };
         self.brick_changes.push(BrickChange {
-            brick_morton: Morton::new(morton),
+            brick_index: brick_slot,
         });
-        self.brick_indices_grid.0[morton as usize] = brick_index;
+        self.brick_index_pool.set(brick_slot, brick_index);
     }
 
     pub fn update_chunk_normals(&mut self, local_chunk_pos: DynChunkPos) {
-        // self.brick_normal_updates.push(BrickChange {
-        //     brick_morton: Morton::new(14682733),
-        // });
-        let morton = local_chunk_pos.morton();
-        if self.chunk_occupancy_mask.status(morton) != SpatialStatus::Loaded {
+        let index = local_chunk_pos.index(self.chunk_render_distance);
+        if self.chunk_indices_grid.get(index).status() != SpatialStatus::Loaded {
             return;
         }
 
-        self.chunk_normal_grid.set_status(morton, true);
-        let local_brick_min = local_chunk_pos.to_dyn_brick_pos();
-        let local_brick_min_morton = *local_brick_min.morton();
+        self.chunk_normal_grid.set_status(index, true);
+        let brick_index_min = local_chunk_pos.brick_index_min(self.chunk_render_distance);
         for brick_morton in 0..CHUNK_VOLUME {
-            let dyn_brick_morton = local_brick_min_morton + brick_morton as u64;
-            if self.brick_indices_grid.0[dyn_brick_morton as usize].status()
-                == SpatialStatus::Loaded
-            {
-                self.brick_normal_updates.push(BrickChange {
-                    brick_morton: Morton::new(dyn_brick_morton),
-                });
+            let dyn_brick_index = brick_index_min + brick_morton as u64;
+            if self.brick_index(dyn_brick_index).status() == SpatialStatus::Loaded {
+                self.brick_normal_updates
+                    .push(BrickNormalUpdate { dyn_brick_index });
             }
         }
     }
 
+    /// Collects the chunk load, unload and edit events since the last call.
+    pub fn collect_events(&mut self) -> Vec<VoxelEvent> {
+        std::mem::replace(&mut self.events, Vec::new())
+    }
+
     pub fn collect_brick_changes(&mut self) -> Vec<BrickChange> {
         std::mem::replace(&mut self.brick_changes, Vec::new())
     }
 
-    pub fn collect_brick_normal_updates(&mut self) -> Vec<BrickChange> {
+    pub fn collect_brick_normal_updates(&mut self) -> Vec<BrickNormalUpdate> {
         std::mem::replace(&mut self.brick_normal_updates, Vec::new())
     }
 
-    pub fn super_chunk_bit_grid(&self) -> &BitGridMask {
-        &self.super_chunk_grid_mask
+    /// Collects the brick index blocks allocated since the last call, the gpu still holds the
+    /// slots of whatever used the block before until the block's brick changes are uploaded.
+    pub fn collect_allocated_blocks(&mut self) -> Vec<u32> {
+        std::mem::replace(&mut self.allocated_blocks, Vec::new())
+    }
+
+    /// Collects the resizes since the last call so anything holding dyn brick indices, like the
+    /// gpu normal update queue, can move them to the new layout.
+    pub fn collect_resizes(&mut self) -> Vec<DynWorldResize> {
+        std::mem::replace(&mut self.resizes, Vec::new())
+    }
+
+    /// Counts of everything the dynamic world holds and how much of the gpu buffers sized by the
+    /// settings is in use.
+    pub fn stats(&self, settings: &Settings) -> DynWorldStats {
+        let mut stats = DynWorldStats::default();
+        for chunk_index in self.chunk_indices_grid.as_slice() {
+            match chunk_index.status() {
+                SpatialStatus::Unloaded => stats.unloaded_chunks += 1,
+                SpatialStatus::Loading => stats.loading_chunks += 1,
+                SpatialStatus::Loaded => stats.loaded_chunks += 1,
+                SpatialStatus::LoadedEmpty => stats.empty_chunks += 1,
+            }
+        }
+
+        stats.live_brick_slots = self.brick_data.live_count();
+        stats.free_brick_slots = self.brick_data.free_count();
+        stats.live_brick_index_blocks =
+            self.brick_index_pool.block_count() - self.brick_index_pool.free_block_count();
+        stats.free_brick_index_blocks = self.brick_index_pool.free_block_count();
+        stats.palette_size_classes = self.brick_palette_data.size_classes();
+
+        stats.cpu_bytes = (self.grid_memory_size()
+            + self.brick_index_pool.buffer_size()
+            + self.brick_data.buffer_size()
+            + self.brick_data.palette_indices_buffer_size()
+            + self.brick_palette_data.buffer_size()) as u64;
+        stats.gpu_grid_bytes = (self.super_chunk_grid_mask.buffer_size()
+            + self.chunk_indices_grid.buffer_size()) as u64;
+        stats.gpu_brick_index_pool = MemoryUsage {
+            used: self.brick_index_pool.buffer_size() as u64,
+            max: self.brick_index_pool.max_buffer_size() as u64,
+        };
+        stats.gpu_brick_data = MemoryUsage {
+            used: self.brick_data.buffer_size() as u64,
+            max: settings.brick_data_max_size as u64 * std::mem::size_of::<BrickData>() as u64,
+        };
+        stats.gpu_brick_palettes = MemoryUsage {
+            used: self.brick_palette_data.buffer_size() as u64,
+            max: settings.brick_palette_max_size as u64
+                * 256
+                * std::mem::size_of::<PackedVoxelMaterial>() as u64,
+        };
+        stats.gpu_brick_palette_indices = MemoryUsage {
+            used: self.brick_data.palette_indices_buffer_size() as u64,
+            max: settings.brick_data_max_size as u64
+                * BRICK_VOLUME as u64
+                * std::mem::size_of::<u16>() as u64,
+        };
+        stats.overflow = self.overflow;
+
+        stats
     }
 
-    pub fn chunk_occupancy_grid(&self) -> &GridMask {
-        &self.chunk_occupancy_mask
+    /// The upload code reports the bricks that didn't fit in the gpu buffers here.
+    pub fn overflow_mut(&mut self) -> &mut DynWorldOverflow {
+        &mut self.overflow
     }
 
-    pub fn chunk_bit_grid(&self) -> &BitGridMask {
-        &self.chunk_bit_mask
+    pub fn super_chunk_bit_grid(&self) -> &BitGridMask {
+        &self.super_chunk_grid_mask
     }
 
-    pub fn brick_indices_grid(&self) -> &BrickIndexGrid {
-        &self.brick_indices_grid
+    pub fn chunk_indices_grid(&self) -> &ChunkIndexGrid {
+        &self.chunk_indices_grid
+    }
+
+    pub fn brick_index_pool(&self) -> &BrickIndexPool {
+        &self.brick_index_pool
     }
 
     pub fn brick_palette_list(&self) -> &BrickPaletteList {
@@ -252,6 +750,127 @@ impl DynVoxelWorld {
     }
 }
 
+/// The side length of the super chunk grid, the chunk grid rounded up to whole super chunks.
+fn super_chunk_side_length(chunk_render_distance: ChunkRadius) -> u64 {
+    (chunk_render_distance.side_length() as u64).div_ceil(SUPER_CHUNK_LENGTH as u64)
+}
+
+fn super_chunk_volume(chunk_render_distance: ChunkRadius) -> u64 {
+    super_chunk_side_length(chunk_render_distance).pow(3)
+}
+
+/// The position of the super chunk holding the chunk in the super chunk grid.
+fn super_chunk_pos(chunk_index: u64, chunk_render_distance: ChunkRadius) -> Vector3<u32> {
+    DynChunkPos::from_index(chunk_index, chunk_render_distance)
+        .vector
+        .map(|x| x / SUPER_CHUNK_LENGTH as u32)
+}
+
+/// The linear index of the super chunk in the super chunk grid mask.
+fn super_chunk_index(super_chunk_pos: Vector3<u32>, chunk_render_distance: ChunkRadius) -> u64 {
+    let side_length = super_chunk_side_length(chunk_render_distance);
+    super_chunk_pos.x as u64
+        + (super_chunk_pos.y as u64 + super_chunk_pos.z as u64 * side_length) * side_length
+}
+
+/// The palette sizes bricks are allocated with, a brick palette is rounded up to the next one.
+pub const PALETTE_SIZE_CLASSES: [u32; 4] = [64, 128, 256, 512];
+
+fn palette_size_class(aligned_size: u32) -> usize {
+    match aligned_size {
+        64 => 0,
+        128 => 1,
+        256 => 2,
+        512 => 3,
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DynWorldStats {
+    pub loaded_chunks: u32,
+    pub loading_chunks: u32,
+    pub empty_chunks: u32,
+    pub unloaded_chunks: u32,
+
+    pub live_brick_slots: u32,
+    pub free_brick_slots: u32,
+    pub live_brick_index_blocks: u32,
+    pub free_brick_index_blocks: u32,
+
+    /// Ordered the same as `PALETTE_SIZE_CLASSES`.
+    pub palette_size_classes: [PaletteSizeClassStats; 4],
+
+    /// The bytes held by the grids, pools and lists on the cpu.
+    pub cpu_bytes: u64,
+    /// The bytes of the grids on the gpu, these are sized by the render distance.
+    pub gpu_grid_bytes: u64,
+    pub gpu_brick_index_pool: MemoryUsage,
+    pub gpu_brick_data: MemoryUsage,
+    pub gpu_brick_palettes: MemoryUsage,
+    pub gpu_brick_palette_indices: MemoryUsage,
+
+    pub overflow: DynWorldOverflow,
+}
+
+/// The bytes used of a gpu buffer, `used` goes past `max` when the buffer overflows.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryUsage {
+    pub used: u64,
+    pub max: u64,
+}
+
+impl MemoryUsage {
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.used as f32 / self.max as f32
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PaletteSizeClassStats {
+    pub size: u32,
+    pub live: u32,
+    /// The palette entries holding a material.
+    pub used_entries: u64,
+    /// The entries lost to rounding palettes up to the size class.
+    pub padding_entries: u64,
+}
+
+impl PaletteSizeClassStats {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// The fraction of the allocated entries lost to padding.
+    pub fn fragmentation(&self) -> f32 {
+        let allocated = self.used_entries + self.padding_entries;
+        if allocated == 0 {
+            return 0.0;
+        }
+        self.padding_entries as f32 / allocated as f32
+    }
+}
+
+/// Counts of what didn't fit since the world was created, anything that overflows is skipped
+/// and stays invisible until space frees up.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DynWorldOverflow {
+    /// Chunks left empty since the brick index pool was full.
+    pub brick_index_blocks: u64,
+    /// Brick indices that didn't fit in the gpu brick index pool.
+    pub gpu_brick_indices: u64,
+    /// Bricks that didn't fit in the gpu brick data buffer.
+    pub gpu_brick_data: u64,
+    /// Brick palettes that didn't fit in the gpu brick palette buffer.
+    pub gpu_brick_palettes: u64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SpatialStatus {
     Unloaded = 0b00,
@@ -288,51 +907,130 @@ impl From<u32> for SpatialStatus {
     }
 }
 
-pub struct BrickIndexGrid(Vec<BrickIndex>);
+/// The chunk grid of the dynamic world, each chunk points to its brick index block.
+pub struct ChunkIndexGrid(Vec<ChunkIndex>);
 
-impl BrickIndexGrid {
+impl ChunkIndexGrid {
     pub fn new(volume: usize) -> Self {
-        Self(vec![BrickIndex::new_unloaded(); volume])
+        Self(vec![ChunkIndex::new(SpatialStatus::Unloaded); volume])
     }
 
-    pub fn as_slice(&self) -> &[BrickIndex] {
+    pub fn get(&self, index: u64) -> ChunkIndex {
+        self.0[index as usize]
+    }
+
+    pub fn set(&mut self, index: u64, chunk_index: ChunkIndex) {
+        self.0[index as usize] = chunk_index;
+    }
+
+    pub fn as_slice(&self) -> &[ChunkIndex] {
         &self.0
     }
 
     pub fn buffer_size(&self) -> usize {
-        self.0.len() * std::mem::size_of::<BrickIndex>()
+        self.0.len() * std::mem::size_of::<ChunkIndex>()
     }
 }
 
-// Every 2 bits is a status with the index being morton encoded.
-pub struct GridMask(Vec<u16>);
+/// The first two most significant bits are the status, the rest is the brick index block index
+/// which is only valid when the chunk is loaded.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ChunkIndex(u32);
 
-impl GridMask {
-    pub fn new(volume: usize) -> Self {
-        Self(vec![0; volume / 8])
+impl ChunkIndex {
+    pub fn new(status: SpatialStatus) -> Self {
+        Self((status as u32) << 30)
     }
 
-    pub fn set_status(&mut self, morton: Morton, status: SpatialStatus) {
-        let bit_index = (*morton & 0b111) * 2;
-        // Clear the status bits
-        self.0[(*morton >> 3) as usize] &= !(0b11 << bit_index);
-        // Set the status bits
-        self.0[(*morton >> 3) as usize] |= (status as u16) << bit_index;
+    pub fn new_loaded(block_index: u32) -> Self {
+        Self((SpatialStatus::Loaded as u32) << 30 | (block_index & 0x3FFF_FFFF))
+    }
+
+    pub fn status(&self) -> SpatialStatus {
+        (self.0 >> 30).into()
+    }
+
+    pub fn block_index(&self) -> u32 {
+        self.0 & 0x3FFF_FFFF
     }
+}
 
-    pub fn status(&self, morton: Morton) -> SpatialStatus {
-        let bit_index = (*morton & 0b111) * 2;
-        let status = (self.0[(*morton >> 3) as usize] >> bit_index) & 0b11;
+/// Blocks of `CHUNK_VOLUME` morton ordered brick indices, one for each loaded chunk.
+pub struct BrickIndexPool {
+    indices: Vec<BrickIndex>,
+    free_blocks: Vec<u32>,
+    max_blocks: u32,
+}
 
-        status.into()
+impl BrickIndexPool {
+    pub fn new(max_blocks: u32) -> Self {
+        Self {
+            indices: Vec::new(),
+            free_blocks: Vec::new(),
+            max_blocks,
+        }
     }
 
-    pub fn as_slice(&self) -> &[u16] {
-        &self.0
+    /// Returns None if the pool already holds the max # of blocks.
+    pub fn allocate_block(&mut self) -> Option<u32> {
+        if let Some(block_index) = self.free_blocks.pop() {
+            return Some(block_index);
+        }
+
+        let block_index = self.block_count();
+        if block_index >= self.max_blocks {
+            return None;
+        }
+        self.indices.resize(
+            self.indices.len() + CHUNK_VOLUME,
+            BrickIndex::new_unloaded(),
+        );
+
+        Some(block_index)
+    }
+
+    pub fn free_block(&mut self, block_index: u32) {
+        let slot_min = Self::block_slot_min(block_index) as usize;
+        self.indices[slot_min..(slot_min + CHUNK_VOLUME)].fill(BrickIndex::new_unloaded());
+        self.free_blocks.push(block_index);
+    }
+
+    /// The slot of the block's first brick index.
+    pub fn block_slot_min(block_index: u32) -> u64 {
+        block_index as u64 * CHUNK_VOLUME as u64
+    }
+
+    pub fn get(&self, brick_slot: u64) -> BrickIndex {
+        self.indices[brick_slot as usize]
+    }
+
+    pub fn set(&mut self, brick_slot: u64, brick_index: BrickIndex) {
+        self.indices[brick_slot as usize] = brick_index;
+    }
+
+    pub fn block_count(&self) -> u32 {
+        (self.indices.len() / CHUNK_VOLUME) as u32
+    }
+
+    pub fn free_block_count(&self) -> u32 {
+        self.free_blocks.len() as u32
+    }
+
+    pub fn max_blocks(&self) -> u32 {
+        self.max_blocks
+    }
+
+    pub fn as_slice(&self) -> &[BrickIndex] {
+        &self.indices
     }
 
     pub fn buffer_size(&self) -> usize {
-        self.0.len() * std::mem::size_of::<u16>()
+        self.indices.len() * std::mem::size_of::<BrickIndex>()
+    }
+
+    pub fn max_buffer_size(&self) -> usize {
+        Self::block_slot_min(self.max_blocks) as usize * std::mem::size_of::<BrickIndex>()
     }
 }
 
@@ -343,17 +1041,17 @@ impl BitGridMask {
         Self(vec![0; (volume as f32 / 8.0).ceil() as usize])
     }
 
-    pub fn set_status(&mut self, morton: Morton, status: bool) {
-        let bit_index = *morton & 0b111;
+    pub fn set_status(&mut self, index: u64, status: bool) {
+        let bit_index = index & 0b111;
         // Clear the status bits
-        self.0[(*morton >> 3) as usize] &= !(1 << bit_index);
+        self.0[(index >> 3) as usize] &= !(1 << bit_index);
         // Set the status bits
-        self.0[(*morton >> 3) as usize] |= (status as u8) << bit_index;
+        self.0[(index >> 3) as usize] |= (status as u8) << bit_index;
     }
 
-    pub fn status(&self, morton: Morton) -> bool {
-        let bit_index = *morton & 0b111;
-        let status = self.0[(*morton >> 3) as usize] >> bit_index;
+    pub fn status(&self, index: u64) -> bool {
+        let bit_index = index & 0b111;
+        let status = (self.0[(index >> 3) as usize] >> bit_index) & 1;
 
         status == 1
     }
@@ -395,10 +1093,14 @@ impl BrickIndex {
 
 pub struct BrickDataList {
     free_head: u32,
+    free_count: u32,
     data: Vec<BrickData>,
 
     // Each index is u9
     palette_indices: Vec<u16>,
+    /// The # of entries each brick's palette was inserted with, needed to free the palette since
+    /// the top entries aren't always referenced by a voxel.
+    palette_entry_counts: Vec<u16>,
 }
 
 const NULL_FREE_INDEX: u32 = 0x7FFFFFFF;
@@ -407,8 +1109,10 @@ impl BrickDataList {
     pub fn new() -> Self {
         Self {
             free_head: NULL_FREE_INDEX,
+            free_count: 0,
             data: Vec::new(),
             palette_indices: Vec::new(),
+            palette_entry_counts: Vec::new(),
         }
     }
 
@@ -417,24 +1121,36 @@ impl BrickDataList {
         &mut self,
         brick_data: BrickData,
         brick_palette_indices: [u16; BRICK_VOLUME],
+        palette_entry_count: u16,
     ) -> u32 {
         if self.free_head != NULL_FREE_INDEX {
             let new_index = self.free_head;
             self.free_head = self.data[self.free_head as usize].next_free();
+            self.free_count -= 1;
             self.data[new_index as usize] = brick_data;
             let indices_index = new_index as usize * BRICK_VOLUME;
             self.palette_indices[indices_index..(indices_index + BRICK_VOLUME)]
                 .copy_from_slice(&brick_palette_indices);
+            self.palette_entry_counts[new_index as usize] = palette_entry_count;
 
             return new_index;
         } else {
             self.data.push(brick_data);
             self.palette_indices
                 .extend_from_slice(&brick_palette_indices);
+            self.palette_entry_counts.push(palette_entry_count);
             return self.data.len() as u32 - 1;
         }
     }
 
+    /// Adds the brick slot to the free list, the brick's palette is freed separately with
+    /// `BrickPaletteList::remove`.
+    pub fn remove(&mut self, index: u32) {
+        self.data[index as usize].set_free(self.free_head);
+        self.free_head = index;
+        self.free_count += 1;
+    }
+
     pub fn get(&self, index: u32) -> &BrickData {
         &self.data[index as usize]
     }
@@ -443,6 +1159,26 @@ impl BrickDataList {
         let index = index as usize * BRICK_VOLUME;
         &self.palette_indices[index..(index + BRICK_VOLUME)]
     }
+
+    pub fn palette_entry_count(&self, index: u32) -> u32 {
+        self.palette_entry_counts[index as usize] as u32
+    }
+
+    pub fn live_count(&self) -> u32 {
+        self.data.len() as u32 - self.free_count
+    }
+
+    pub fn free_count(&self) -> u32 {
+        self.free_count
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.data.len() * std::mem::size_of::<BrickData>()
+    }
+
+    pub fn palette_indices_buffer_size(&self) -> usize {
+        self.palette_indices.len() * std::mem::size_of::<u16>()
+    }
 }
 
 #[repr(C)]
@@ -475,6 +1211,11 @@ impl BrickData {
         unsafe { self.voxel_mask.next_free }
     }
 
+    pub fn is_voxel_set(&self, voxel_morton: u64) -> bool {
+        let voxel_mask = unsafe { &self.voxel_mask.voxel_mask };
+        voxel_mask[(voxel_morton >> 3) as usize] & (1 << (voxel_morton & 0b111)) != 0
+    }
+
     pub fn from_voxel_array(voxel_data: &Vec<Option<Vector3<f32>>>) -> Self {
         let mut voxel_mask = [0; BRICK_AREA];
         for i in 0..BRICK_VOLUME {
@@ -507,7 +1248,69 @@ impl BrickData {
 }
 
 pub struct BrickChange {
-    pub brick_morton: Morton,
+    /// The slot in the brick index pool, see `BrickIndexPool::block_slot_min`.
+    pub brick_index: u64,
+}
+
+pub struct BrickNormalUpdate {
+    /// The brick's index in the dynamic world, see `DynBrickPos::index`.
+    pub dyn_brick_index: u64,
+}
+
+/// Describes how positions in the dynamic world moved after a resize.
+pub struct DynWorldResize {
+    old_chunk_render_distance: ChunkRadius,
+    new_chunk_render_distance: ChunkRadius,
+    /// The offset from the old local chunk position to the new local chunk position.
+    local_offset: i32,
+    old_translation: Vector3<i32>,
+    new_translation: Vector3<i32>,
+}
+
+impl DynWorldResize {
+    /// Returns the new chunk index, None if the chunk no longer fits in the dynamic world.
+    pub fn remap_chunk(&self, chunk_index: u64) -> Option<u64> {
+        let old_side_length = self.old_chunk_render_distance.side_length() as i32;
+        let new_side_length = self.new_chunk_render_distance.side_length() as i32;
+
+        let old_pos = DynChunkPos::from_index(chunk_index, self.old_chunk_render_distance)
+            .vector
+            .map(|x| x as i32);
+        let local_pos = (old_pos - self.old_translation)
+            .map(|x| x.rem_euclid(old_side_length))
+            .add_scalar(self.local_offset);
+        if local_pos.iter().any(|x| *x < 0 || *x >= new_side_length) {
+            return None;
+        }
+
+        let new_pos =
+            (local_pos + self.new_translation).map(|x| x.rem_euclid(new_side_length) as u32);
+        Some(
+            DynChunkPos::new(new_pos.x, new_pos.y, new_pos.z).index(self.new_chunk_render_distance),
+        )
+    }
+
+    /// Returns the new dyn brick index, None if the brick's chunk no longer fits in the dynamic
+    /// world.
+    pub fn remap_brick(&self, dyn_brick_index: u64) -> Option<u64> {
+        let chunk_index = dyn_brick_index / CHUNK_VOLUME as u64;
+        let local_brick_morton = dyn_brick_index % CHUNK_VOLUME as u64;
+        self.remap_chunk(chunk_index)
+            .map(|chunk_index| chunk_index * CHUNK_VOLUME as u64 + local_brick_morton)
+    }
+
+    pub fn remap_brick_normal_updates(
+        &self,
+        brick_normal_updates: impl IntoIterator<Item = BrickNormalUpdate>,
+    ) -> Vec<BrickNormalUpdate> {
+        brick_normal_updates
+            .into_iter()
+            .filter_map(|update| {
+                self.remap_brick(update.dyn_brick_index)
+                    .map(|dyn_brick_index| BrickNormalUpdate { dyn_brick_index })
+            })
+            .collect()
+    }
 }
 
 pub struct BrickPalette {
@@ -550,82 +1353,76 @@ impl BrickPalette {
 
 pub struct BrickPaletteList {
     voxels: Vec<PackedVoxelMaterial>,
-    free_head: u32,
+    /// The first free palette of each size class, a free palette holds the index of the next one
+    /// in its first entry.
+    free_heads: [u32; 4],
+    size_classes: [PaletteSizeClassStats; 4],
 }
 
 impl BrickPaletteList {
     pub fn new() -> Self {
         Self {
             voxels: Vec::new(),
-            free_head: NULL_FREE_INDEX,
+            free_heads: [NULL_FREE_INDEX; 4],
+            size_classes: PALETTE_SIZE_CLASSES.map(PaletteSizeClassStats::new),
         }
     }
 
-    // Finds a chunk with the desired palette size, if one is found that is greater, then it is
-    // split
+    /// Reuses a free palette of the same size class if there is one, otherwise appends a new
+    /// one. Returns the index of the palette's first entry.
     pub fn insert(&mut self, brick_palette: BrickPalette) -> u32 {
-        let palette_aligned_size = brick_palette.next_pow_2_size();
-        if self.free_head != NULL_FREE_INDEX {
-            let mut new_index = self.free_head;
-            let mut next_free = NULL_FREE_INDEX;
-            let mut last_free = NULL_FREE_INDEX;
-            while new_index != NULL_FREE_INDEX {
-                last_free = new_index;
-                new_index = self.voxels[new_index as usize].material;
-                if self.voxels[(new_index + 1) as usize].material >= palette_aligned_size {
-                    next_free = self.voxels[new_index as usize].material;
-                    break;
-                }
-            }
-            if new_index == NULL_FREE_INDEX {
-                return self.append(brick_palette);
-            }
-
-            todo!("Splitting free chunk");
-            // let mut free_size = self.voxels[(new_index + 1) as usize].material;
-
-            // // Split the free chunk
-            // while free_size != palette_aligned_size {
-            //     let new_free_size = free_size / 2;
-            //     let split_index = new_index + new_free_size;
-            //     self.voxels[split_index as usize] = PackedVoxelMaterial {
-            //         material: next_free,
-            //     };
-            //     self.voxels[(split_index + 1) as usize] = PackedVoxelMaterial {
-            //         material: new_free_size,
-            //     };
+        let aligned_size = brick_palette.next_pow_2_size();
+        let size_class = palette_size_class(aligned_size);
 
-            //     free_size = new_free_size;
-            // }
+        let free_head = self.free_heads[size_class];
+        let new_index = if free_head != NULL_FREE_INDEX {
+            self.free_heads[size_class] = self.voxels[free_head as usize].material;
+            free_head
+        } else {
+            let new_index = self.voxels.len() as u32;
+            self.voxels.resize(
+                self.voxels.len() + aligned_size as usize,
+                PackedVoxelMaterial::new([0.0, 1.0, 1.0], [0.0; 3]),
+            );
+            new_index
+        };
+        let used_entries = brick_palette.data.len();
+        self.voxels[new_index as usize..(new_index as usize + used_entries)]
+            .copy_from_slice(&brick_palette.data);
 
-            // self.free_head = self.voxels[self.free_head as usize].get_next_free_index();
-            // for i in 0..brick_palette.data.len() {
-            //     self.voxels[new_index as usize + i] = brick_palette.data[i as usize];
-            // }
+        let stats = &mut self.size_classes[size_class];
+        stats.live += 1;
+        stats.used_entries += used_entries as u64;
+        stats.padding_entries += (aligned_size as usize - used_entries) as u64;
 
-            // return new_index;
-        }
-        return self.append(brick_palette);
+        new_index
     }
 
-    fn append(&mut self, chunk_palette: BrickPalette) -> u32 {
-        let aligned_size = chunk_palette.next_pow_2_size();
-        let new_index = self.voxels.len() as u32;
-        self.voxels.resize(
-            self.voxels.len() + aligned_size as usize,
-            PackedVoxelMaterial::new([0.0, 1.0, 1.0], [0.0; 3]),
-        );
-        for i in 0..chunk_palette.data.len() {
-            self.voxels[new_index as usize + i] = chunk_palette.data[i];
-        }
-
-        new_index
+    /// Adds the palette to its size class's free list, `used_entries` is the # of entries the
+    /// palette was inserted with.
+    pub fn remove(&mut self, index: u32, size: u32, used_entries: u32) {
+        let size_class = palette_size_class(size);
+        self.voxels[index as usize].material = self.free_heads[size_class];
+        self.free_heads[size_class] = index;
+
+        let stats = &mut self.size_classes[size_class];
+        stats.live -= 1;
+        stats.used_entries -= used_entries as u64;
+        stats.padding_entries -= (size - used_entries) as u64;
     }
 
     pub fn get(&self, index: u32, size: u32) -> &[PackedVoxelMaterial] {
         let index = index as usize;
         &self.voxels[index..(index + size as usize)]
     }
+
+    pub fn size_classes(&self) -> [PaletteSizeClassStats; 4] {
+        self.size_classes
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.voxels.len() * std::mem::size_of::<PackedVoxelMaterial>()
+    }
 }
 
 #[repr(C)]
@@ -646,4 +1443,422 @@ impl PackedVoxelMaterial {
         let albedo = (albedo[0] as u32) << 12 | (albedo[1] as u32) << 6 | albedo[2] as u32;
         Self { material: albedo }
     }
+
+    pub fn albedo(&self) -> Vector3<f32> {
+        Vector3::new(
+            (self.material >> 12 & 63) as f32,
+            (self.material >> 6 & 63) as f32,
+            (self.material & 63) as f32,
+        ) / 63.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+    use crate::engine::voxel::vox_constants::CHUNK_VOXEL_LENGTH;
+
+    fn test_world(radius: u32) -> DynVoxelWorld {
+        DynVoxelWorld::new(&Settings {
+            chunk_render_distance: ChunkRadius::new(radius),
+            brick_index_block_max_size: 256,
+            ..Default::default()
+        })
+    }
+
+    /// The voxel at the chunk's min corner, every chunk loaded by `load_chunks` has only this one
+    /// set so tests can tell where a chunk ended up.
+    fn corner_voxel(world_pos: WorldChunkPos) -> Vector3<i32> {
+        world_pos.vector * CHUNK_VOXEL_LENGTH as i32
+    }
+
+    /// Loads every unloaded chunk in the render distance with only its corner voxel set.
+    fn load_chunks(dyn_world: &mut DynVoxelWorld) {
+        for index in 0..dyn_world.chunk_render_distance.volume() {
+            let dyn_pos = DynChunkPos::from_index(index, dyn_world.chunk_render_distance);
+            if dyn_world.chunk_status(dyn_pos) != SpatialStatus::Unloaded {
+                continue;
+            }
+
+            let mut voxel_data = vec![None; CHUNK_VOLUME * BRICK_VOLUME];
+            voxel_data[0] = Some(Vector3::new(1.0, 0.0, 0.0));
+            let chunk_position = dyn_world.world_chunk_pos(dyn_pos);
+            dyn_world.set_generated_chunk(
+                dyn_pos,
+                GeneratedChunk {
+                    chunk_position,
+                    is_empty: false,
+                    voxel_data: Some(voxel_data),
+                },
+            );
+        }
+        dyn_world.collect_events();
+    }
+
+    fn world_chunk_positions(center: Vector3<i32>, radius: i32) -> Vec<WorldChunkPos> {
+        let mut positions = Vec::new();
+        for z in -radius..=radius {
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    positions.push(WorldChunkPos {
+                        vector: center + Vector3::new(x, y, z),
+                    });
+                }
+            }
+        }
+        positions
+    }
+
+    /// Checks each super chunk bit against the chunks it covers.
+    fn assert_super_chunk_mask_matches(dyn_world: &DynVoxelWorld) {
+        let mut occupied =
+            vec![false; super_chunk_volume(dyn_world.chunk_render_distance) as usize];
+        for (index, chunk_index) in dyn_world.chunk_indices_grid.as_slice().iter().enumerate() {
+            if chunk_index.status() == SpatialStatus::Loaded {
+                let dyn_pos =
+                    DynChunkPos::from_index(index as u64, dyn_world.chunk_render_distance);
+                let super_pos = dyn_pos.vector / SUPER_CHUNK_LENGTH as u32;
+                occupied[super_chunk_index(super_pos, dyn_world.chunk_render_distance) as usize] =
+                    true;
+            }
+        }
+
+        for (super_index, is_occupied) in occupied.into_iter().enumerate() {
+            assert_eq!(
+                dyn_world.super_chunk_bit_grid().status(super_index as u64),
+                is_occupied,
+                "super chunk {}",
+                super_index
+            );
+        }
+    }
+
+    #[test]
+    fn test_resize_grow_keeps_chunks() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+
+        dyn_world.resize(ChunkRadius::new(2), WorldChunkPos::new(0, 0, 0));
+        assert!(dyn_world.collect_events().is_empty());
+        for world_pos in world_chunk_positions(Vector3::zeros(), 2) {
+            let dyn_pos = dyn_world.dyn_chunk_pos(world_pos).unwrap();
+            if world_pos.vector.abs().max() <= 1 {
+                assert!(dyn_world.is_world_voxel_set(corner_voxel(world_pos)));
+            } else {
+                assert_eq!(dyn_world.chunk_status(dyn_pos), SpatialStatus::Unloaded);
+            }
+        }
+        assert_super_chunk_mask_matches(&dyn_world);
+    }
+
+    #[test]
+    fn test_resize_shrink_unloads_outer_chunks() {
+        let mut dyn_world = test_world(2);
+        load_chunks(&mut dyn_world);
+
+        dyn_world.resize(ChunkRadius::new(1), WorldChunkPos::new(0, 0, 0));
+        let unloaded = dyn_world
+            .collect_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                VoxelEvent::ChunkUnloaded(world_pos) => Some(world_pos),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(unloaded.len(), 125 - 27);
+        assert!(unloaded
+            .iter()
+            .all(|world_pos| world_pos.vector.abs().max() == 2));
+
+        for world_pos in world_chunk_positions(Vector3::zeros(), 1) {
+            assert!(dyn_world.is_world_voxel_set(corner_voxel(world_pos)));
+        }
+        let stats = dyn_world.stats(&Settings::default());
+        assert_eq!(stats.live_brick_index_blocks, 27);
+        assert_eq!(stats.free_brick_index_blocks, 125 - 27);
+        assert_super_chunk_mask_matches(&dyn_world);
+    }
+
+    #[test]
+    fn test_resize_after_translation() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+        dyn_world.update_translation(Vector3::new(1, 0, -1), WorldChunkPos::new(0, 0, 0));
+        load_chunks(&mut dyn_world);
+
+        let center = Vector3::new(1, 0, -1);
+        dyn_world.resize(ChunkRadius::new(2), WorldChunkPos { vector: center });
+        for world_pos in world_chunk_positions(center, 1) {
+            assert!(dyn_world.is_world_voxel_set(corner_voxel(world_pos)));
+        }
+        assert_super_chunk_mask_matches(&dyn_world);
+
+        dyn_world.resize(ChunkRadius::new(1), WorldChunkPos { vector: center });
+        for world_pos in world_chunk_positions(center, 1) {
+            assert!(dyn_world.is_world_voxel_set(corner_voxel(world_pos)));
+        }
+        assert_super_chunk_mask_matches(&dyn_world);
+    }
+
+    #[test]
+    fn test_super_chunk_mask_follows_chunks() {
+        let mut dyn_world = test_world(2);
+        load_chunks(&mut dyn_world);
+        assert_super_chunk_mask_matches(&dyn_world);
+
+        for world_pos in world_chunk_positions(Vector3::zeros(), 2) {
+            if world_pos.vector.x <= 0 {
+                let dyn_pos = dyn_world.dyn_chunk_pos(world_pos).unwrap();
+                dyn_world.unload_chunk(dyn_pos);
+            }
+        }
+        assert_super_chunk_mask_matches(&dyn_world);
+    }
+
+    #[test]
+    fn test_dyn_brick_index_is_tiled_by_chunk() {
+        let dyn_world = test_world(1);
+        let side_length = dyn_world.chunk_render_distance.side_length() as i32;
+        let brick_side_length = side_length * CHUNK_LENGTH as i32;
+        let brick_min = -(CHUNK_LENGTH as i32);
+
+        let mut seen =
+            vec![false; dyn_world.chunk_render_distance.volume() as usize * CHUNK_VOLUME];
+        for z in brick_min..(brick_min + brick_side_length) {
+            for y in brick_min..(brick_min + brick_side_length) {
+                for x in brick_min..(brick_min + brick_side_length) {
+                    let world_brick_pos = Vector3::new(x, y, z);
+                    let dyn_brick_index = dyn_world.world_dyn_brick_index(world_brick_pos).unwrap();
+                    assert!(!seen[dyn_brick_index as usize]);
+                    seen[dyn_brick_index as usize] = true;
+
+                    let world_chunk_pos = WorldChunkPos {
+                        vector: world_brick_pos.map(|x| x.div_euclid(CHUNK_LENGTH as i32)),
+                    };
+                    let dyn_chunk_pos = dyn_world.dyn_chunk_pos(world_chunk_pos).unwrap();
+                    let local_brick_pos =
+                        world_brick_pos.map(|x| x.rem_euclid(CHUNK_LENGTH as i32) as u32);
+                    assert_eq!(
+                        dyn_brick_index,
+                        dyn_chunk_pos.brick_index_min(dyn_world.chunk_render_distance)
+                            + *Morton::encode(local_brick_pos)
+                    );
+                }
+            }
+        }
+        assert!(seen.into_iter().all(|seen| seen));
+        assert_eq!(
+            dyn_world.world_dyn_brick_index(Vector3::repeat(brick_min + brick_side_length)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_brick_index_pool_reuses_freed_blocks() {
+        let mut pool = BrickIndexPool::new(2);
+        assert_eq!(pool.allocate_block(), Some(0));
+        assert_eq!(pool.allocate_block(), Some(1));
+        assert_eq!(pool.allocate_block(), None);
+
+        let slot = BrickIndexPool::block_slot_min(1) + 3;
+        pool.set(slot, BrickIndex::new_loaded(7));
+        pool.free_block(1);
+        assert_eq!(pool.free_block_count(), 1);
+        assert_eq!(pool.get(slot).status(), SpatialStatus::Unloaded);
+
+        assert_eq!(pool.allocate_block(), Some(1));
+        assert_eq!(pool.free_block_count(), 0);
+        assert_eq!(pool.block_count(), 2);
+    }
+
+    #[test]
+    fn test_unloaded_chunk_block_is_reused() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+        assert_eq!(dyn_world.collect_allocated_blocks().len(), 27);
+
+        let dyn_pos = dyn_world
+            .dyn_chunk_pos(WorldChunkPos::new(1, 0, 0))
+            .unwrap();
+        let block_index = dyn_world
+            .chunk_indices_grid
+            .get(dyn_pos.index(dyn_world.chunk_render_distance))
+            .block_index();
+        let live_bricks = dyn_world.brick_data.live_count();
+        dyn_world.unload_chunk(dyn_pos);
+        assert_eq!(dyn_world.brick_data.live_count(), live_bricks - 1);
+
+        load_chunks(&mut dyn_world);
+        assert_eq!(dyn_world.collect_allocated_blocks(), [block_index]);
+        assert_eq!(dyn_world.brick_index_pool.block_count(), 27);
+        assert!(dyn_world.is_world_voxel_set(corner_voxel(WorldChunkPos::new(1, 0, 0))));
+    }
+
+    #[test]
+    fn test_edits_reuse_freed_palettes() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+        let palette_buffer_size = dyn_world.brick_palette_data.buffer_size();
+
+        // The chunk's corner voxel keeps the brick from emptying.
+        let voxel = Vector3::new(3, 3, 3);
+        for i in 0..16 {
+            let albedo = (i % 2 == 0).then(|| Vector3::new(0.0, 1.0, 0.0));
+            dyn_world.set_world_voxels([(voxel, albedo)], VoxelEditSource::Player);
+            assert_eq!(dyn_world.is_world_voxel_set(voxel), albedo.is_some());
+            assert_eq!(
+                dyn_world.brick_palette_data.buffer_size(),
+                palette_buffer_size
+            );
+        }
+
+        let dyn_pos = dyn_world
+            .dyn_chunk_pos(WorldChunkPos::new(0, 0, 0))
+            .unwrap();
+        dyn_world.unload_chunk(dyn_pos);
+        assert_eq!(dyn_world.brick_palette_data.size_classes()[0].live, 26);
+        load_chunks(&mut dyn_world);
+        assert_eq!(dyn_world.brick_palette_data.size_classes()[0].live, 27);
+        assert_eq!(
+            dyn_world.brick_palette_data.buffer_size(),
+            palette_buffer_size
+        );
+        assert_eq!(
+            dyn_world.world_voxel_albedo(Vector3::zeros()),
+            Some(Vector3::new(1.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_unreferenced_palette_entries_are_freed() {
+        let mut dyn_world = test_world(1);
+        let size_classes = dyn_world.brick_palette_data.size_classes();
+
+        // Only the first of the palette's three entries is used by a voxel.
+        let mut voxels = vec![None; BRICK_VOLUME];
+        voxels[0] = Some(Vector3::new(1.0, 0.0, 0.0));
+        let palette = BrickPalette::new(
+            vec![PackedVoxelMaterial::new([1.0, 0.0, 0.0], [0.0; 3]); 3],
+            [0; BRICK_VOLUME],
+        );
+        let block_index = dyn_world.allocate_block().unwrap();
+        dyn_world.set_brick(
+            BrickIndexPool::block_slot_min(block_index),
+            Some((BrickData::from_voxel_array(&voxels), palette)),
+        );
+        assert_eq!(
+            dyn_world.brick_palette_data.size_classes()[0].used_entries,
+            3
+        );
+
+        dyn_world.free_block(block_index);
+        assert_eq!(dyn_world.brick_palette_data.size_classes(), size_classes);
+    }
+
+    #[test]
+    fn test_stats_counts() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+
+        let loading_pos = dyn_world
+            .dyn_chunk_pos(WorldChunkPos::new(1, 0, 0))
+            .unwrap();
+        dyn_world.set_chunk_loading(loading_pos);
+        let empty_pos = dyn_world
+            .dyn_chunk_pos(WorldChunkPos::new(0, 1, 0))
+            .unwrap();
+        dyn_world.set_generated_chunk(
+            empty_pos,
+            GeneratedChunk {
+                chunk_position: WorldChunkPos::new(0, 1, 0),
+                is_empty: true,
+                voxel_data: None,
+            },
+        );
+        let unloaded_pos = dyn_world
+            .dyn_chunk_pos(WorldChunkPos::new(0, 0, 1))
+            .unwrap();
+        dyn_world.unload_chunk(unloaded_pos);
+
+        let stats = dyn_world.stats(&Settings::default());
+        assert_eq!(stats.loaded_chunks, 24);
+        assert_eq!(stats.loading_chunks, 1);
+        assert_eq!(stats.empty_chunks, 1);
+        assert_eq!(stats.unloaded_chunks, 1);
+        assert_eq!(stats.live_brick_slots, 24);
+        assert_eq!(stats.free_brick_slots, 3);
+        assert_eq!(stats.live_brick_index_blocks, 24);
+        assert_eq!(stats.free_brick_index_blocks, 3);
+
+        let size_class = stats.palette_size_classes[0];
+        assert_eq!(size_class.live, 24);
+        assert_eq!(size_class.used_entries, 24);
+        assert_eq!(size_class.padding_entries, 24 * 63);
+        assert!(stats.palette_size_classes[1..]
+            .iter()
+            .all(|size_class| size_class.live == 0 && size_class.used_entries == 0));
+        assert_eq!(stats.overflow.brick_index_blocks, 0);
+    }
+
+    #[test]
+    fn test_stats_count_block_overflow() {
+        let mut dyn_world = DynVoxelWorld::new(&Settings {
+            chunk_render_distance: ChunkRadius::new(1),
+            brick_index_block_max_size: 2,
+            ..Default::default()
+        });
+        load_chunks(&mut dyn_world);
+
+        let stats = dyn_world.stats(&Settings::default());
+        assert_eq!(stats.loaded_chunks, 2);
+        assert_eq!(stats.empty_chunks, 25);
+        assert_eq!(stats.live_brick_index_blocks, 2);
+        assert_eq!(stats.overflow.brick_index_blocks, 25);
+    }
+
+    #[test]
+    fn test_raycast_voxel() {
+        let mut dyn_world = test_world(1);
+        load_chunks(&mut dyn_world);
+
+        let ray = Ray::new(Point3::new(0.5, 0.5, -5.5), Vector3::z());
+        assert_eq!(
+            dyn_world.raycast_voxel(&ray, 10.0),
+            Some(Vector3::new(0, 0, 0))
+        );
+        assert_eq!(dyn_world.raycast_voxel(&ray, 4.0), None);
+
+        let ray = Ray::new(
+            Point3::new(70.5, -10.0, 0.5),
+            Vector3::new(-6.0, 10.5, 0.0).normalize(),
+        );
+        assert_eq!(
+            dyn_world.raycast_voxel(&ray, 100.0),
+            Some(Vector3::new(64, 0, 0))
+        );
+
+        let ray = Ray::new(Point3::new(0.5, 0.5, -5.5), -Vector3::z());
+        assert_eq!(dyn_world.raycast_voxel(&ray, 50.0), None);
+        assert_eq!(
+            dyn_world.raycast_voxel(&ray, 100.0),
+            Some(Vector3::new(0, 0, -64))
+        );
+    }
+
+    #[test]
+    fn test_bit_grid_mask_status() {
+        let mut mask = BitGridMask::new(16);
+        mask.set_status(1, true);
+        mask.set_status(9, true);
+        assert!(!mask.status(0));
+        assert!(mask.status(1));
+        assert!(!mask.status(8));
+        assert!(mask.status(9));
+
+        mask.set_status(1, false);
+        assert!(!mask.status(1));
+        assert!(mask.status(9));
+    }
 }
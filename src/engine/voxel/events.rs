@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use voxei_macros::Resource;
+
+use crate::engine::geometry::shapes::aabb::AABB;
+
+use super::vox_world::WorldChunkPos;
+
+#[derive(Clone, Copy)]
+pub enum VoxelEvent {
+    ChunkLoaded(WorldChunkPos),
+    ChunkUnloaded(WorldChunkPos),
+    RegionEdited {
+        /// The world space bounds of the voxels that changed.
+        aabb: AABB,
+        source: VoxelEditSource,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelEditSource {
+    Generation,
+    Player,
+    Physics,
+    Other(&'static str),
+}
+
+/// A subscription to the voxel events, each reader has its own cursor so it sees every event
+/// sent after it subscribed exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelEventReader(usize);
+
+/// Publishes voxel world changes so systems can react without polling the world. Events are kept
+/// until every reader has read them.
+#[derive(Resource)]
+pub struct VoxelEvents {
+    events: VecDeque<VoxelEvent>,
+    /// The id of the front event, ids increase by one for each event sent.
+    front_id: u64,
+    /// The id of the next event each reader will read, None for unsubscribed slots.
+    cursors: Vec<Option<u64>>,
+}
+
+impl VoxelEvents {
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            front_id: 0,
+            cursors: Vec::new(),
+        }
+    }
+
+    fn end_id(&self) -> u64 {
+        self.front_id + self.events.len() as u64
+    }
+
+    pub fn send(&mut self, event: VoxelEvent) {
+        // Nobody would ever read it.
+        if self.cursors.iter().all(|cursor| cursor.is_none()) {
+            self.front_id += 1;
+            return;
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = VoxelEvent>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Creates a reader which will only see events sent from now on.
+    pub fn subscribe(&mut self) -> VoxelEventReader {
+        let cursor = Some(self.end_id());
+        if let Some(slot) = self.cursors.iter().position(|cursor| cursor.is_none()) {
+            self.cursors[slot] = cursor;
+            return VoxelEventReader(slot);
+        }
+
+        self.cursors.push(cursor);
+        VoxelEventReader(self.cursors.len() - 1)
+    }
+
+    pub fn unsubscribe(&mut self, reader: VoxelEventReader) {
+        self.cursors[reader.0] = None;
+        self.trim();
+    }
+
+    /// The events the reader hasn't read yet.
+    pub fn unread(&self, reader: VoxelEventReader) -> usize {
+        let cursor = self.cursors[reader.0].expect("Reader is unsubscribed.");
+        (self.end_id() - cursor) as usize
+    }
+
+    /// Returns the events sent since the reader last read and moves its cursor to the end.
+    pub fn read(&mut self, reader: VoxelEventReader) -> Vec<VoxelEvent> {
+        let end_id = self.end_id();
+        let cursor = self.cursors[reader.0]
+            .replace(end_id)
+            .expect("Reader is unsubscribed.");
+        let events = self
+            .events
+            .range((cursor - self.front_id) as usize..)
+            .copied()
+            .collect();
+        self.trim();
+
+        events
+    }
+
+    /// Drops the events every reader has read.
+    fn trim(&mut self) {
+        let min_cursor = self
+            .cursors
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or(self.end_id());
+        let read_count = (min_cursor - self.front_id) as usize;
+        self.events.drain(..read_count);
+        self.front_id = min_cursor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(x: i32) -> VoxelEvent {
+        VoxelEvent::ChunkLoaded(WorldChunkPos::new(x, 0, 0))
+    }
+
+    fn loaded_x(events: &[VoxelEvent]) -> Vec<i32> {
+        events
+            .iter()
+            .map(|event| match event {
+                VoxelEvent::ChunkLoaded(pos) => pos.vector.x,
+                _ => panic!("Expected a chunk loaded event."),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_readers_have_independent_cursors() {
+        let mut events = VoxelEvents::new();
+        let a = events.subscribe();
+        events.send(loaded(0));
+        let b = events.subscribe();
+        events.send(loaded(1));
+
+        assert_eq!(loaded_x(&events.read(a)), vec![0, 1]);
+        assert_eq!(loaded_x(&events.read(b)), vec![1]);
+        assert!(events.read(a).is_empty());
+
+        events.send(loaded(2));
+        assert_eq!(events.unread(a), 1);
+        assert_eq!(loaded_x(&events.read(b)), vec![2]);
+        assert_eq!(loaded_x(&events.read(a)), vec![2]);
+    }
+
+    #[test]
+    fn test_read_events_are_trimmed() {
+        let mut events = VoxelEvents::new();
+        events.send(loaded(0));
+        assert!(events.events.is_empty());
+
+        let a = events.subscribe();
+        let b = events.subscribe();
+        events.send(loaded(1));
+        events.read(a);
+        assert_eq!(events.events.len(), 1);
+
+        events.unsubscribe(b);
+        assert!(events.events.is_empty());
+
+        let c = events.subscribe();
+        assert_eq!(c, b);
+        events.send(loaded(2));
+        assert_eq!(loaded_x(&events.read(c)), vec![2]);
+        assert_eq!(loaded_x(&events.read(a)), vec![2]);
+    }
+}
@@ -2,6 +2,9 @@ use nalgebra::Vector3;
 
 pub mod chunk_generator;
 pub mod dynamic_world;
+pub mod events;
+pub mod object;
+pub mod plugin;
 pub mod static_world;
 pub mod vox_world;
 
@@ -0,0 +1,236 @@
+use hecs::Entity;
+use nalgebra::{Isometry3, Point3, Translation3, Vector3};
+use voxei_macros::Resource;
+
+use crate::{
+    engine::{
+        common::{time::Time, transform::Transform},
+        ecs::ecs_world::ECSWorld,
+        geometry::{bvh::Bvh, ray::Ray, shapes::aabb::AABB},
+        physics::phy_world::PhysicsWorld,
+        resource::{Res, ResMut},
+    },
+    settings::Settings,
+};
+
+use super::{
+    dynamic_world::DynVoxelWorld,
+    util::Morton,
+    vox_constants::{BRICK_VOLUME, CHUNK_VOLUME, CHUNK_VOXEL_LENGTH, VOXEL_WORLD_LENGTH},
+};
+
+/// A voxel volume that moves with the entity's `Transform` instead of being part of the world
+/// grid. The volume is stored in its own brick index block so it can be at most a chunk in size,
+/// the transform's scale is ignored.
+pub struct VoxelObject {
+    block_index: u32,
+    /// The size of the volume in voxels, the object rotates around its center.
+    extent: Vector3<u32>,
+}
+
+impl VoxelObject {
+    pub const MAX_EXTENT: u32 = CHUNK_VOXEL_LENGTH as u32;
+
+    /// Builds the object from the voxel albedo at each position within the extent, None being
+    /// empty. Returns None if the extent is larger than a chunk or the brick index pool is full.
+    pub fn new(
+        dyn_world: &mut DynVoxelWorld,
+        extent: Vector3<u32>,
+        voxel_fn: impl Fn(Vector3<u32>) -> Option<Vector3<f32>>,
+    ) -> Option<Self> {
+        if extent.iter().any(|x| *x == 0 || *x > Self::MAX_EXTENT) {
+            return None;
+        }
+
+        let mut voxel_data = vec![None; CHUNK_VOLUME * BRICK_VOLUME];
+        for x in 0..extent.x {
+            for y in 0..extent.y {
+                for z in 0..extent.z {
+                    let position = Vector3::new(x, y, z);
+                    voxel_data[*Morton::encode(position) as usize] = voxel_fn(position);
+                }
+            }
+        }
+        let block_index = dyn_world.insert_object_volume(&voxel_data)?;
+
+        Some(Self {
+            block_index,
+            extent,
+        })
+    }
+
+    /// Frees the object's bricks, call this before despawning the entity.
+    pub fn destroy(self, dyn_world: &mut DynVoxelWorld) {
+        dyn_world.remove_object_volume(self.block_index);
+    }
+
+    pub fn block_index(&self) -> u32 {
+        self.block_index
+    }
+
+    pub fn extent(&self) -> Vector3<u32> {
+        self.extent
+    }
+
+    /// Object space has the volume's min corner at the origin with the same units as world space.
+    pub fn world_to_object(&self, transform: &Transform) -> Isometry3<f32> {
+        let half_extent = self.extent.map(|x| x as f32 * VOXEL_WORLD_LENGTH / 2.0);
+        Translation3::from(half_extent) * transform.isometry.inverse()
+    }
+
+    pub fn world_aabb(&self, transform: &Transform) -> AABB {
+        let object_to_world = self.world_to_object(transform).inverse();
+        let size = self.extent.map(|x| x as f32 * VOXEL_WORLD_LENGTH);
+
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for corner in 0..8 {
+            let local_corner = Point3::new(
+                if corner & 1 == 0 { 0.0 } else { size.x },
+                if corner & 2 == 0 { 0.0 } else { size.y },
+                if corner & 4 == 0 { 0.0 } else { size.z },
+            );
+            let world_corner = object_to_world * local_corner;
+            min = min.inf(&world_corner);
+            max = max.sup(&world_corner);
+        }
+
+        AABB::new_min_max(min, max)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct VoxelObjectInstance {
+    pub entity: Entity,
+    pub block_index: u32,
+    pub extent: Vector3<u32>,
+    pub world_to_object: Isometry3<f32>,
+    pub aabb: AABB,
+}
+
+/// The voxel objects of this frame with a BVH over their world AABBs.
+#[derive(Resource)]
+pub struct VoxelObjects {
+    instances: Vec<VoxelObjectInstance>,
+    bvh: Bvh,
+    overflow_count: u32,
+}
+
+impl VoxelObjects {
+    pub fn new() -> Self {
+        Self {
+            instances: Vec::new(),
+            bvh: Bvh::build(&[]),
+            overflow_count: 0,
+        }
+    }
+
+    /// Rebuilds the BVH from every entity with a voxel object and transform. Physics driven
+    /// objects are drawn between their last two fixed steps.
+    pub fn update(
+        ecs_world: Res<ECSWorld>,
+        mut voxel_objects: ResMut<VoxelObjects>,
+        physics_world: Option<Res<PhysicsWorld>>,
+        time: Res<Time>,
+        settings: Res<Settings>,
+    ) {
+        let mut instances = ecs_world
+            .query::<(&VoxelObject, &Transform)>()
+            .iter()
+            .map(|(entity, (object, transform))| {
+                let mut transform = transform.clone();
+                if let Some(physics_world) = &physics_world {
+                    transform.isometry = physics_world.interpolated_isometry(
+                        entity,
+                        &transform.isometry,
+                        time.interpolation_alpha(),
+                    );
+                }
+                VoxelObjectInstance {
+                    entity,
+                    block_index: object.block_index,
+                    extent: object.extent,
+                    world_to_object: object.world_to_object(&transform),
+                    aabb: object.world_aabb(&transform),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let max_count = settings.voxel_object_max_count as usize;
+        voxel_objects.overflow_count = instances.len().saturating_sub(max_count) as u32;
+        instances.truncate(max_count);
+
+        let aabbs = instances.iter().map(|i| i.aabb).collect::<Vec<_>>();
+        voxel_objects.bvh = Bvh::build(&aabbs);
+        voxel_objects.instances = instances;
+    }
+
+    pub fn instances(&self) -> &[VoxelObjectInstance] {
+        &self.instances
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    /// The # of objects left out this frame since there were more than the max.
+    pub fn overflow_count(&self) -> u32 {
+        self.overflow_count
+    }
+
+    /// Returns the closest object hit by the ray and the distance to it.
+    pub fn raycast(&self, ray: &Ray, dyn_world: &DynVoxelWorld) -> Option<(Entity, f32)> {
+        self.bvh
+            .ray_candidates(ray)
+            .into_iter()
+            .filter_map(|i| {
+                let instance = &self.instances[i as usize];
+                raycast_instance(instance, ray, dyn_world).map(|t| (instance.entity, t))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+/// Steps through the object's voxels in object space, the transform is rigid so the distance is
+/// the same as in world space.
+fn raycast_instance(
+    instance: &VoxelObjectInstance,
+    ray: &Ray,
+    dyn_world: &DynVoxelWorld,
+) -> Option<f32> {
+    let local_ray = Ray::new(
+        instance.world_to_object * ray.position(),
+        instance.world_to_object * ray.direction(),
+    );
+    let size = instance.extent.map(|x| x as f32 * VOXEL_WORLD_LENGTH);
+    let bounds = AABB::new_min_max(Point3::origin(), size.into());
+    let mut t = local_ray.intersect_aabb(&bounds)?;
+
+    let extent = instance.extent.map(|x| x as i32);
+    let mut voxel_pos = (local_ray.traverse(t).coords / VOXEL_WORLD_LENGTH)
+        .map(|x| x.floor() as i32)
+        .zip_map(&extent, |x, e| x.clamp(0, e - 1));
+    let step = local_ray.direction().map(|x| x.signum() as i32);
+    let t_delta = local_ray.inv_direction().abs() * VOXEL_WORLD_LENGTH;
+    let mut t_next = Vector3::from_fn(|i, _| {
+        let boundary = (voxel_pos[i] + (step[i] > 0) as i32) as f32 * VOXEL_WORLD_LENGTH;
+        (boundary - local_ray.position()[i]) * local_ray.inv_direction()[i]
+    });
+
+    while voxel_pos
+        .iter()
+        .zip(extent.iter())
+        .all(|(x, e)| *x >= 0 && x < e)
+    {
+        if dyn_world.is_block_voxel_set(instance.block_index, voxel_pos.map(|x| x as u32)) {
+            return Some(t);
+        }
+
+        let axis = t_next.imin();
+        t = t_next[axis];
+        voxel_pos[axis] += step[axis];
+        t_next[axis] += t_delta[axis];
+    }
+
+    None
+}
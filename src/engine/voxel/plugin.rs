@@ -0,0 +1,50 @@
+use crate::{
+    app::App,
+    engine::{
+        plugin::{CorePlugin, Plugin, PluginId},
+        schedule::{IntoSystemConfig, Stage},
+    },
+    settings::Settings,
+};
+
+use super::{events::VoxelEvents, object::VoxelObjects, vox_world::VoxelWorld};
+
+/// The voxel world, its streaming and voxel objects, none of which need the GPU.
+pub struct VoxelWorldPlugin;
+
+impl Plugin for VoxelWorldPlugin {
+    fn build(&self, app: &mut App) {
+        let vox_world = VoxelWorld::new(&app.resource_bank().get_resource::<Settings>());
+
+        app.insert_resource(vox_world)
+            .insert_resource(VoxelEvents::new())
+            .insert_resource(VoxelObjects::new());
+
+        app.add_system(
+            Stage::Update,
+            VoxelWorld::update_settings.label("voxel_settings"),
+        )
+        .add_system(
+            Stage::Update,
+            VoxelWorld::update_world_streaming
+                .label("voxel_streaming")
+                .after("voxel_settings"),
+        )
+        .add_system(
+            Stage::Update,
+            VoxelWorld::publish_events
+                .label("voxel_events")
+                .after("voxel_streaming"),
+        )
+        .add_system(
+            Stage::Update,
+            VoxelObjects::update
+                .label("voxel_objects")
+                .after("voxel_events"),
+        );
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<CorePlugin>()]
+    }
+}
@@ -18,7 +18,7 @@ use crate::{
     constants,
     engine::{
         common::transform::Transform,
-        ecs::ecs_world::ECSWorld,
+        ecs::{ecs_world::ECSWorld, tracked::Tracked},
         geometry::{ray::Ray, shapes::aabb::AABB},
         graphics::{
             device::DeviceResource,
@@ -38,8 +38,9 @@ use crate::{
 use super::{
     chunk_generator::ChunkGenerator,
     dynamic_world::DynVoxelWorld,
-    util::{next_pow2, Morton},
-    vox_constants::{CHUNK_LENGTH, CHUNK_VOXEL_LENGTH, CHUNK_WORLD_LENGTH},
+    events::{VoxelEditSource, VoxelEvents},
+    util::Morton,
+    vox_constants::{CHUNK_LENGTH, CHUNK_VOLUME, CHUNK_VOXEL_LENGTH, CHUNK_WORLD_LENGTH},
 };
 
 #[derive(Resource)]
@@ -75,13 +76,30 @@ impl VoxelWorld {
         s
     }
 
-    pub fn update_settings(mut vox_world: ResMut<VoxelWorld>, settings: Res<Settings>) {}
+    /// Resizes the dynamic world when the render distance setting changes.
+    pub fn update_settings(mut vox_world: ResMut<VoxelWorld>, settings: Res<Settings>) {
+        if vox_world.chunk_render_distance == settings.chunk_render_distance {
+            return;
+        }
 
-    pub fn update_world_streaming(
-        mut vox_world: ResMut<VoxelWorld>,
+        let chunk_center = vox_world.chunk_center;
+        let chunk_render_distance = settings.chunk_render_distance;
+        vox_world
+            .dyn_world
+            .resize(chunk_render_distance, chunk_center);
+        vox_world.chunk_render_distance = chunk_render_distance;
+        vox_world
+            .chunk_generator
+            .update_bounds(chunk_center, chunk_render_distance);
+    }
+
+    /// Collects the bricks rays requested on the GPU in the frames that finished since the last
+    /// call.
+    pub fn update_brick_requests(
+        vox_world: Res<VoxelWorld>,
         device: Res<DeviceResource>,
         swapchain: Res<SwapchainResource>,
-        vox_pipeline: ResMut<VoxelPipeline>,
+        vox_pipeline: Res<VoxelPipeline>,
         settings: Res<Settings>,
     ) {
         let gpu_index = unsafe {
@@ -101,6 +119,12 @@ impl VoxelWorld {
             }
         }
 
+        //        for brick in requested_bricks {}
+    }
+
+    /// Queues generation for the unloaded chunks around the chunk center and loads the chunks
+    /// that finished generating, this doesn't touch the GPU so it also runs headless.
+    pub fn update_world_streaming(mut vox_world: ResMut<VoxelWorld>, settings: Res<Settings>) {
         // Calculate chunks that should be loaded dynamically
         let mut dyn_load_queue = Vec::new();
 
@@ -153,9 +177,11 @@ impl VoxelWorld {
             };
             vox_world.dyn_world.set_generated_chunk(dyn_pos, chunk);
         }
-        // Process any generated chunks.
+    }
 
-        //        for brick in requested_bricks {}
+    /// Publishes the chunk loads and unloads since the last call to the voxel events.
+    pub fn publish_events(mut vox_world: ResMut<VoxelWorld>, mut events: ResMut<VoxelEvents>) {
+        events.send_batch(vox_world.dyn_world.collect_events());
     }
 
     pub fn update_world_position(
@@ -163,7 +189,7 @@ impl VoxelWorld {
         ecs: Res<ECSWorld>,
         input: Res<Input>,
     ) {
-        let mut player_query = ecs.player_query::<&Transform>();
+        let mut player_query = ecs.player_query::<&Tracked<Transform>>();
         let (_, transform) = player_query.player();
         let player_pos = transform.isometry.translation.vector;
 
@@ -189,19 +215,7 @@ impl VoxelWorld {
         //     chunk_center.vector.y -= 1;
         // }
 
-        if chunk_center != vox_world.chunk_center {
-            let translation = chunk_center.vector - vox_world.chunk_center.vector;
-            let old_chunk_center = vox_world.chunk_center;
-            vox_world
-                .dyn_world
-                .update_translation(translation, old_chunk_center);
-            vox_world.chunk_center = chunk_center;
-
-            let chunk_render_distance = vox_world.chunk_render_distance;
-            vox_world
-                .chunk_generator
-                .update_bounds(chunk_center, chunk_render_distance);
-        }
+        vox_world.set_chunk_center(chunk_center);
 
         if input.is_key_pressed(Key::T) {
             const NORM_RANGE: i32 = 3;
@@ -224,11 +238,12 @@ impl VoxelWorld {
                                 .rem_euclid(CHUNK_LENGTH as i32) as u32,
                         );
                         println!("Chunk: {:?} Brick: {:?}", dyn_pos, brick_center);
-                        let brick_morton =
-                            Morton::encode(dyn_pos.to_dyn_brick_pos().vector + brick_center);
+                        let brick_pos = dyn_pos.to_dyn_brick_pos().vector + brick_center;
+                        let brick_index = DynBrickPos::new(brick_pos.x, brick_pos.y, brick_pos.z)
+                            .index(vox_world.chunk_render_distance);
                         println!(
-                            "Chunk: {:?} Brick: {:?}, morton: {:?}",
-                            dyn_pos, brick_center, brick_morton
+                            "Chunk: {:?} Brick: {:?}, index: {:?}",
+                            dyn_pos, brick_center, brick_index
                         );
                         vox_world.dyn_world_mut().update_chunk_normals(dyn_pos);
                     }
@@ -236,26 +251,45 @@ impl VoxelWorld {
             }
         }
 
-        if let Some(chunk_pos) = vox_world.raycast_world(transform.into()) {
-            if input.is_mouse_button_pressed(mouse::Button::Left) {
-                println!("Raycast hit chunk: {:?}", chunk_pos);
-                vox_world.dyn_world_mut().unload_chunk(chunk_pos);
+        if input.is_mouse_button_pressed(mouse::Button::Left) {
+            const BREAK_REACH: f32 = 64.0;
+            const BREAK_RADIUS: i32 = 2;
+            let ray = Ray::from(&**transform);
+            if let Some(voxel_pos) = vox_world.dyn_world().raycast_voxel(&ray, BREAK_REACH) {
+                vox_world.break_voxels(voxel_pos, BREAK_RADIUS);
+            }
+        }
+    }
+
+    /// Removes the voxels within the radius of the world voxel position as a player edit.
+    pub fn break_voxels(&mut self, center: Vector3<i32>, radius: i32) {
+        let mut voxels = Vec::new();
+        for z in -radius..=radius {
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    let offset = Vector3::new(x, y, z);
+                    if offset.dot(&offset) <= radius * radius {
+                        voxels.push((center + offset, None));
+                    }
+                }
             }
         }
+        self.dyn_world
+            .set_world_voxels(voxels, VoxelEditSource::Player);
     }
 
     // Returns the intersected chunk, brick, and local voxel morton.
     pub fn raycast_world(&self, ray: Ray) -> Option<(DynChunkPos)> {
-        let dyn_world_side_length =
-            self.chunk_render_distance.pow2_side_length() as f32 * CHUNK_WORLD_LENGTH as f32;
-        let dyn_world_half_side_length = dyn_world_side_length / 2.0;
-        let dyn_world_aabb = AABB::new_center_half_extent(
-            (self.chunk_center().vector.map(|x| x as f32) * CHUNK_WORLD_LENGTH).into(),
-            Vector3::new(
-                dyn_world_side_length,
-                dyn_world_side_length,
-                dyn_world_side_length,
-            ),
+        let side_length = self.chunk_render_distance.side_length();
+        let dyn_world_side_length = side_length as f32 * CHUNK_WORLD_LENGTH as f32;
+        let dyn_world_min = self
+            .chunk_center()
+            .vector
+            .add_scalar(-(self.chunk_render_distance.half_side_length() as i32))
+            .map(|x| x as f32 * CHUNK_WORLD_LENGTH);
+        let dyn_world_aabb = AABB::new_min_max(
+            dyn_world_min.into(),
+            dyn_world_min.add_scalar(dyn_world_side_length).into(),
         );
 
         let Some(initial_t) = ray.intersect_aabb(&dyn_world_aabb) else {
@@ -264,16 +298,9 @@ impl VoxelWorld {
         const EPSILON: f32 = 0.000001;
         // TODO: abstract this dda somehow or like clean it up cause this is atrocious
         let initial_pos = ray.traverse(initial_t);
-        let normalized_initial_pos = (initial_pos
-            - self
-                .chunk_center()
-                .vector
-                .map(|x| x as f32 * CHUNK_WORLD_LENGTH))
-        .coords
-        .add_scalar(dyn_world_half_side_length)
-        .map(|x| x / dyn_world_side_length);
-        let dyn_initial_pos = normalized_initial_pos
-            .map(|x| x * self.chunk_render_distance.pow2_side_length() as f32);
+        let normalized_initial_pos =
+            (initial_pos.coords - dyn_world_min).map(|x| x / dyn_world_side_length);
+        let dyn_initial_pos = normalized_initial_pos.map(|x| x * side_length as f32);
         let step_axes = ray.direction().map(|x| x.signum() as i32);
         let t_unit_delta = ray.inv_direction().abs();
         let mut c_map_pos = dyn_initial_pos.map(|x| x.floor() as i32);
@@ -287,13 +314,13 @@ impl VoxelWorld {
         while c_map_pos.x >= 0
             && c_map_pos.y >= 0
             && c_map_pos.z >= 0
-            && c_map_pos.x < self.chunk_render_distance.pow2_side_length() as i32
-            && c_map_pos.y < self.chunk_render_distance.pow2_side_length() as i32
-            && c_map_pos.z < self.chunk_render_distance.pow2_side_length() as i32
+            && c_map_pos.x < side_length as i32
+            && c_map_pos.y < side_length as i32
+            && c_map_pos.z < side_length as i32
         {
             let dyn_chunk_pos = (c_map_pos.map(|x| x as u32)
                 + self.dyn_world().chunk_translation())
-            .map(|x| x.rem_euclid(self.chunk_render_distance.pow2_side_length()));
+            .map(|x| x.rem_euclid(side_length));
             let dyn_chunk_pos = DynChunkPos::new(dyn_chunk_pos.x, dyn_chunk_pos.y, dyn_chunk_pos.z);
             let chunk_status = self.dyn_world().chunk_status(dyn_chunk_pos);
             if chunk_status == SpatialStatus::Loaded {
@@ -320,8 +347,9 @@ impl VoxelWorld {
                         dyn_chunk_pos.vector.y * CHUNK_LENGTH as u32 + b_map_pos.y as u32,
                         dyn_chunk_pos.vector.z * CHUNK_LENGTH as u32 + b_map_pos.z as u32,
                     );
-                    let brick_index = self.dyn_world().brick_indices_grid().as_slice()
-                        [*dyn_brick_pos.morton() as usize];
+                    let brick_index = self
+                        .dyn_world()
+                        .brick_index(dyn_brick_pos.index(self.chunk_render_distance));
                     if brick_index.status() == SpatialStatus::Loaded {
                         let c_enter_pos = ray.traverse(b_last_t);
                         let v_initial_pos = (c_enter_pos - b_map_pos.map(|x| x as f32))
@@ -374,38 +402,50 @@ impl VoxelWorld {
     pub fn chunk_center(&self) -> WorldChunkPos {
         self.chunk_center
     }
+
+    /// Moves the center of the loaded world, chunks that leave the render distance are unloaded
+    /// and the ones that enter it are streamed in.
+    pub fn set_chunk_center(&mut self, chunk_center: WorldChunkPos) {
+        if chunk_center == self.chunk_center {
+            return;
+        }
+
+        let translation = chunk_center.vector - self.chunk_center.vector;
+        let old_chunk_center = self.chunk_center;
+        self.dyn_world
+            .update_translation(translation, old_chunk_center);
+        self.chunk_center = chunk_center;
+        self.chunk_generator
+            .update_bounds(chunk_center, self.chunk_render_distance);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChunkRadius {
     radius: u32,
-    pow2_side_length: u32,
 }
 
 impl ChunkRadius {
     pub fn new(radius: u32) -> Self {
-        Self {
-            radius,
-            pow2_side_length: next_pow2(radius * 2 + 1),
-        }
+        Self { radius }
     }
 
     pub fn radius(&self) -> u32 {
         self.radius
     }
 
-    pub fn pow2_side_length(&self) -> u32 {
-        self.pow2_side_length
+    /// The center chunk plus the radius on each side.
+    pub fn side_length(&self) -> u32 {
+        self.radius * 2 + 1
     }
 
-    pub fn pow2_half_side_length(&self) -> u32 {
-        self.pow2_side_length / 2
+    pub fn half_side_length(&self) -> u32 {
+        self.radius
     }
 
-    pub fn pow2_volume(&self) -> u64 {
-        let side_length = self.pow2_side_length() as u64; // 64 = 1000000
-        let side_length_power = side_length.trailing_zeros();
-        1 << (side_length_power * 3)
+    pub fn volume(&self) -> u64 {
+        let side_length = self.side_length() as u64;
+        side_length * side_length * side_length
     }
 }
 
@@ -422,8 +462,8 @@ impl WorldChunkPos {
     }
 
     pub fn to_dyn_pos(&self, vox_world: &VoxelWorld) -> Option<DynChunkPos> {
-        let slm = vox_world.chunk_render_distance.pow2_side_length() as i32;
-        let hl = vox_world.chunk_render_distance.pow2_half_side_length() as i32;
+        let slm = vox_world.chunk_render_distance.side_length() as i32;
+        let hl = vox_world.chunk_render_distance.half_side_length() as i32;
         let local_pos = self.vector + Vector3::new(hl, hl, hl) - vox_world.chunk_center().vector;
 
         if local_pos.x < 0
@@ -458,6 +498,15 @@ impl DynChunkPos {
         }
     }
 
+    pub fn from_index(index: u64, chunk_render_distance: ChunkRadius) -> Self {
+        let side_length = chunk_render_distance.side_length() as u64;
+        Self::new(
+            (index % side_length) as u32,
+            (index / side_length % side_length) as u32,
+            (index / (side_length * side_length)) as u32,
+        )
+    }
+
     pub fn to_dyn_brick_pos(&self) -> DynBrickPos {
         DynBrickPos::new(
             self.vector.x * CHUNK_LENGTH as u32,
@@ -466,8 +515,16 @@ impl DynChunkPos {
         )
     }
 
-    pub fn morton(&self) -> Morton {
-        Morton::encode(self.vector)
+    /// The linear index of the chunk in the dynamic world's chunk grid.
+    pub fn index(&self, chunk_render_distance: ChunkRadius) -> u64 {
+        let side_length = chunk_render_distance.side_length() as u64;
+        self.vector.x as u64
+            + (self.vector.y as u64 + self.vector.z as u64 * side_length) * side_length
+    }
+
+    /// The dyn brick index of the chunk's first brick, see `DynBrickPos::index`.
+    pub fn brick_index_min(&self, chunk_render_distance: ChunkRadius) -> u64 {
+        self.index(chunk_render_distance) * CHUNK_VOLUME as u64
     }
 }
 
@@ -482,8 +539,18 @@ impl DynBrickPos {
         }
     }
 
-    pub fn morton(&self) -> Morton {
-        Morton::encode(self.vector)
+    pub fn dyn_chunk_pos(&self) -> DynChunkPos {
+        let chunk_pos = self.vector / CHUNK_LENGTH as u32;
+        DynChunkPos::new(chunk_pos.x, chunk_pos.y, chunk_pos.z)
+    }
+
+    /// The index of the brick in the dynamic world, tiled by chunk so each chunk has a contiguous
+    /// morton ordered range of bricks matching the layout of its brick index block. This lets the
+    /// world be any side length instead of a power of two.
+    pub fn index(&self, chunk_render_distance: ChunkRadius) -> u64 {
+        let local_brick_pos = self.vector.map(|x| x % CHUNK_LENGTH as u32);
+        self.dyn_chunk_pos().brick_index_min(chunk_render_distance)
+            + *Morton::encode(local_brick_pos)
     }
 }
 
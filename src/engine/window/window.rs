@@ -23,6 +23,7 @@ impl WindowConfig {
 #[derive(Resource)]
 pub struct Window {
     winit_window: WinitWindow,
+    cursor_visible: bool,
 }
 
 impl raw_window_handle::HasDisplayHandle for Window {
@@ -49,7 +50,10 @@ impl Window {
             .build(event_loop)
             .unwrap();
 
-        Self { winit_window }
+        Self {
+            winit_window,
+            cursor_visible: true,
+        }
     }
 
     pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
@@ -63,6 +67,11 @@ impl Window {
 
     pub fn set_cursor_visible(&mut self, visible: bool) {
         self.winit_window.set_cursor_visible(visible);
+        self.cursor_visible = visible;
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
     }
 
     pub fn set_visible(&mut self, visible: bool) {
@@ -1,20 +1,30 @@
 pub struct PlayerTag;
 use hecs::{Entity, Query, QueryBorrow, With};
-use nalgebra::{Quaternion, Unit, UnitQuaternion, Vector3};
+use nalgebra::{Point3, Quaternion, Unit, UnitQuaternion, Vector3};
 use paya::device::Device;
 
 use crate::{
     engine::{
         common::{camera::Camera, time::Time, transform::Transform},
-        ecs::ecs_world::ECSWorld,
-        input::{keyboard::Key, Input},
+        ecs::{ecs_world::ECSWorld, tracked::Tracked},
+        geometry::shapes::aabb::AABB,
+        input::Input,
+        physics::voxel_collision::{is_grounded, move_and_slide},
         resource::{Res, ResMut},
-        voxel::util::Morton,
+        voxel::{util::Morton, vox_world::VoxelWorld},
         window::window::Window,
     },
     settings::Settings,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MovementMode {
+    /// Noclip flight through the terrain.
+    Fly,
+    /// Collides with the voxel world and falls with gravity.
+    Walk,
+}
+
 struct PlayerController {
     // In terms of degrees
     euler_angles: Vector3<f32>,
@@ -22,6 +32,37 @@ struct PlayerController {
     walk_speed: f32,
     run_speed: f32,
     paused: bool,
+
+    movement_mode: MovementMode,
+    ground_walk_speed: f32,
+    ground_run_speed: f32,
+    jump_speed: f32,
+    gravity: f32,
+    velocity: Vector3<f32>,
+    grounded: bool,
+    /// The half extents of the collision box, the box's bottom is at the feet.
+    collider_half_extents: Vector3<f32>,
+    /// The height of the camera above the feet.
+    eye_height: f32,
+    /// The highest ledge the player walks up without jumping.
+    step_height: f32,
+    /// The eye position after the latest fixed step while walking.
+    walk_position: Vector3<f32>,
+    /// The eye position before the latest fixed step, the camera is
+    /// interpolated between the two.
+    previous_walk_position: Vector3<f32>,
+    /// Latched each frame so a jump isn't lost on frames without a fixed step.
+    jump_requested: bool,
+}
+
+impl PlayerController {
+    fn collider_aabb(&self, eye_position: Vector3<f32>) -> AABB {
+        let feet = eye_position - Vector3::new(0.0, self.eye_height, 0.0);
+        AABB::new_center_half_extent(
+            Point3::from(feet + Vector3::new(0.0, self.collider_half_extents.y, 0.0)),
+            self.collider_half_extents,
+        )
+    }
 }
 
 pub struct PlayerQuery<'a, Q: Query>(QueryBorrow<'a, With<Q, &'a PlayerTag>>);
@@ -40,45 +81,74 @@ pub fn spawn_player(world: &mut ECSWorld, device: &mut Device) {
     world.spawn((
         PlayerTag,
         Camera::new(device),
-        Transform::new(),
+        Tracked::new(Transform::new()),
         PlayerController {
             euler_angles: Vector3::new(0.0, 0.0, 0.0),
             walk_speed: 30.0,
             run_speed: 200.0,
             paused: true,
+
+            movement_mode: MovementMode::Fly,
+            ground_walk_speed: 6.0,
+            ground_run_speed: 12.0,
+            jump_speed: 10.0,
+            gravity: 30.0,
+            velocity: Vector3::zeros(),
+            grounded: false,
+            collider_half_extents: Vector3::new(0.4, 0.9, 0.4),
+            eye_height: 1.6,
+            step_height: 1.05,
+            walk_position: Vector3::zeros(),
+            previous_walk_position: Vector3::zeros(),
+            jump_requested: false,
         },
     ));
 }
 
 pub fn update_player_controller(
-    ecs_world: Res<ECSWorld>,
+    ecs_world: ResMut<ECSWorld>,
     input: Res<Input>,
     time: Res<Time>,
     mut settings: ResMut<Settings>,
     mut window: ResMut<Window>,
 ) {
-    let mut query = ecs_world.player_query::<(&mut Transform, &mut PlayerController)>();
+    let mut query = ecs_world.player_query::<(&mut Tracked<Transform>, &mut PlayerController)>();
     let (_, (transform, controller)) = query.player();
 
-    if input.is_key_pressed(Key::Tab) {
+    if input.action_pressed("pause") {
         controller.paused = !controller.paused;
 
         window.set_cursor_grabbed(!controller.paused);
         window.set_cursor_visible(controller.paused);
     }
 
-    if input.is_key_down(Key::C) {
-        settings.camera_fov = 10.0f32.to_radians();
+    if input.action_pressed("toggle_movement_mode") {
+        controller.movement_mode = match controller.movement_mode {
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Walk => MovementMode::Fly,
+        };
+        controller.velocity = Vector3::zeros();
+        controller.grounded = false;
+        controller.jump_requested = false;
+        controller.walk_position = transform.isometry.translation.vector;
+        controller.previous_walk_position = controller.walk_position;
+    }
+
+    let camera_fov = if input.action("zoom") {
+        10.0f32.to_radians()
     } else {
-        settings.camera_fov = 90.0f32.to_radians();
+        90.0f32.to_radians()
+    };
+    // Only written when it changes so the cameras aren't recomputed every frame.
+    if settings.camera_fov != camera_fov {
+        settings.camera_fov = camera_fov;
     }
 
-    let mouse_delta = input.mouse_delta();
-    if (mouse_delta.0 != 0.0 || mouse_delta.1 != 0.0) && !controller.paused {
-        controller.euler_angles.y += mouse_delta.0 as f32 * settings.mouse_sensitivity;
-        controller.euler_angles.x = (controller.euler_angles.x
-            + mouse_delta.1 as f32 * settings.mouse_sensitivity)
-            .clamp(-89.95, 89.95);
+    let look = (input.axis("look_x"), input.axis("look_y"));
+    if (look.0 != 0.0 || look.1 != 0.0) && !controller.paused {
+        controller.euler_angles.y += look.0 * settings.mouse_sensitivity;
+        controller.euler_angles.x =
+            (controller.euler_angles.x + look.1 * settings.mouse_sensitivity).clamp(-89.95, 89.95);
 
         transform.isometry.rotation = UnitQuaternion::from_euler_angles(
             controller.euler_angles.x.to_radians(),
@@ -87,16 +157,26 @@ pub fn update_player_controller(
         );
     }
 
+    if controller.movement_mode == MovementMode::Walk {
+        if input.action_pressed("jump") {
+            controller.jump_requested = true;
+        }
+        transform.isometry.translation.vector = controller
+            .previous_walk_position
+            .lerp(&controller.walk_position, time.interpolation_alpha());
+        return;
+    }
+
     let mut delta = Vector3::new(input.horizontal_axis(), 0.0, input.vertical_axis());
-    if input.is_key_down(Key::Space) {
+    if input.action("ascend") {
         delta.y = 1.0;
     }
-    if input.is_key_down(Key::LShift) {
+    if input.action("descend") {
         delta.y = -1.0;
     }
 
     let mut speed = controller.walk_speed;
-    if input.is_key_down(Key::LControl) {
+    if input.action("run") {
         speed = controller.run_speed;
     }
 
@@ -113,5 +193,78 @@ pub fn update_player_controller(
     }
     translation.y += delta.y * speed;
 
-    transform.isometry.translation.vector += translation * time.delta_time().as_secs_f32();
+    if translation != Vector3::zeros() {
+        transform.isometry.translation.vector += translation * time.delta_time().as_secs_f32();
+    }
+}
+
+/// Steps the walking player with the fixed time step so collisions and
+/// jumps don't depend on the frame rate.
+pub fn fixed_update_player_walk(
+    ecs_world: ResMut<ECSWorld>,
+    input: Res<Input>,
+    time: Res<Time>,
+    vox_world: Res<VoxelWorld>,
+) {
+    let mut query = ecs_world.player_query::<&mut PlayerController>();
+    let (_, controller) = query.player();
+    if controller.movement_mode != MovementMode::Walk {
+        return;
+    }
+
+    controller.previous_walk_position = controller.walk_position;
+    walk_player(
+        controller,
+        &input,
+        &vox_world,
+        time.fixed_delta_time().as_secs_f32(),
+    );
+    controller.jump_requested = false;
+}
+
+fn walk_player(
+    controller: &mut PlayerController,
+    input: &Input,
+    vox_world: &VoxelWorld,
+    delta_time: f32,
+) {
+    let mut speed = controller.ground_walk_speed;
+    if input.action("run") {
+        speed = controller.ground_run_speed;
+    }
+
+    let delta = Vector3::new(input.horizontal_axis(), 0.0, input.vertical_axis());
+    let mut xz_velocity = Vector3::zeros();
+    if delta.x != 0.0 || delta.z != 0.0 {
+        xz_velocity =
+            (UnitQuaternion::from_euler_angles(0.0, controller.euler_angles.y.to_radians(), 0.0)
+                * delta)
+                .normalize()
+                * speed;
+    }
+    controller.velocity.x = xz_velocity.x;
+    controller.velocity.z = xz_velocity.z;
+
+    if controller.grounded && controller.jump_requested {
+        controller.velocity.y = controller.jump_speed;
+        controller.grounded = false;
+    }
+    controller.velocity.y -= controller.gravity * delta_time;
+
+    let aabb = controller.collider_aabb(controller.walk_position);
+    let result = move_and_slide(
+        vox_world.dyn_world(),
+        &aabb,
+        controller.velocity * delta_time,
+        controller.step_height,
+        controller.grounded,
+    );
+    controller.walk_position += result.motion;
+
+    if result.collided.y {
+        controller.velocity.y = 0.0;
+    }
+    let aabb = controller.collider_aabb(controller.walk_position);
+    controller.grounded = result.grounded
+        || (controller.velocity.y <= 0.0 && is_grounded(vox_world.dyn_world(), &aabb));
 }
@@ -0,0 +1,48 @@
+use crate::{
+    app::App,
+    engine::{
+        ecs::ecs_world::ECSWorld,
+        graphics::{device::DeviceResource, plugin::RenderPlugin},
+        input::plugin::InputPlugin,
+        plugin::{CorePlugin, Plugin, PluginId},
+        schedule::{IntoSystemConfig, Stage},
+        voxel::{plugin::VoxelWorldPlugin, vox_world::VoxelWorld},
+    },
+};
+
+use super::player::{fixed_update_player_walk, spawn_player, update_player_controller};
+
+/// Spawns the player and keeps the voxel world centered on them.
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        spawn_player(
+            &mut app.resource_bank().get_resource_mut::<ECSWorld>(),
+            &mut app.resource_bank().get_resource_mut::<DeviceResource>(),
+        );
+
+        app.add_system(Stage::FixedUpdate, fixed_update_player_walk)
+            .add_system(
+                Stage::Update,
+                update_player_controller
+                    .label("player_controller")
+                    .before("voxel_streaming"),
+            )
+            .add_system(
+                Stage::Update,
+                VoxelWorld::update_world_position
+                    .after("player_controller")
+                    .before("voxel_streaming"),
+            );
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<CorePlugin>(),
+            PluginId::of::<InputPlugin>(),
+            PluginId::of::<VoxelWorldPlugin>(),
+            PluginId::of::<RenderPlugin>(),
+        ]
+    }
+}
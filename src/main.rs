@@ -1,15 +1,94 @@
+use engine::{
+    input::{
+        recording::{InputRecorder, InputRecording},
+        Input,
+    },
+    resource::ResMut,
+};
+
 pub mod app;
 pub mod constants;
 pub mod engine;
 pub mod game;
-pub mod game_loop;
 pub mod settings;
 pub mod setup;
 
+/// Runs the simulation for a number of ticks without a window with `--headless <ticks>`, records
+/// the session's input to a file with `--record <file>` and plays one back with
+/// `--replay <file>`.
 fn main() {
-    let mut app = app::App::new();
+    let mut headless_ticks = None;
+    let mut record_path = None;
+    let mut replay_path = None;
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {
+                let ticks = args
+                    .next_if(|ticks| !ticks.starts_with("--"))
+                    .map(|ticks| {
+                        ticks
+                            .parse()
+                            .expect("Expected a # of ticks after --headless")
+                    })
+                    .unwrap_or(600);
+                headless_ticks = Some(ticks);
+            }
+            "--record" => {
+                record_path = Some(args.next().expect("Expected a file after --record"));
+            }
+            "--replay" => {
+                replay_path = Some(args.next().expect("Expected a file after --replay"));
+            }
+            _ => panic!("Unknown argument: {}", arg),
+        }
+    }
+    assert!(
+        record_path.is_none() || replay_path.is_none(),
+        "--record and --replay can't be used together"
+    );
+
+    let mut app = match headless_ticks {
+        Some(_) => {
+            let mut app = app::App::new_headless();
+            setup::add_headless_plugins(&mut app);
+            app
+        }
+        None => {
+            let mut app = app::App::new();
+            setup::add_plugins(&mut app);
+            app
+        }
+    };
+
+    app.resource_bank_mut()
+        .resource_scope(|resource_bank, mut recorder: ResMut<InputRecorder>| {
+            let mut input = resource_bank.get_resource_mut::<Input>();
+            if let Some(replay_path) = &replay_path {
+                let recording = InputRecording::load(replay_path)
+                    .unwrap_or_else(|err| panic!("Failed to load {}: {}", replay_path, err));
+                recorder.start_replay(recording, &mut input);
+            }
+            if record_path.is_some() {
+                recorder.start_recording(&mut input);
+            }
+        });
 
-    setup::setup_resources(&mut app);
+    match headless_ticks {
+        Some(ticks) => app.run_ticks(ticks),
+        None => app.run(),
+    }
 
-    app.run();
+    if let Some(record_path) = record_path {
+        let recording = app
+            .resource_bank_mut()
+            .resource_scope(|resource_bank, mut recorder: ResMut<InputRecorder>| {
+                recorder.stop_recording(&mut resource_bank.get_resource_mut::<Input>())
+            })
+            .expect("The recording was stopped before the app finished");
+        recording
+            .save(&record_path)
+            .unwrap_or_else(|err| panic!("Failed to save {}: {}", record_path, err));
+    }
 }
@@ -28,12 +28,19 @@ pub struct Settings {
     /// has not requested it.
     pub chunk_generation_distance: ChunkRadius,
 
+    /// The max # of chunks that can have brick indices at once, every loaded chunk that isn't
+    /// empty takes one block of 512 brick indices.
+    pub brick_index_block_max_size: u32,
+
     /// The max # of bricks that can be stored in the brick data buffer.
     pub brick_data_max_size: u32,
 
     /// The max # of 256 brick palettes that can be stored on the gpu.
     pub brick_palette_max_size: u32,
 
+    /// The max # of voxel objects that can be rendered at once.
+    pub voxel_object_max_count: u32,
+
     /// The max # of bricks that can requested per frame on the gpu.
     pub brick_request_max_size: u32,
 
@@ -42,6 +49,9 @@ pub struct Settings {
 
     /// The real world side length of 1x1x1 voxel.
     pub voxel_unit_length: f32,
+
+    /// The max frames per second, None for no limit.
+    pub fps_limit: Option<u32>,
 }
 
 impl Default for Settings {
@@ -55,12 +65,16 @@ impl Default for Settings {
             chunk_loaded_distance: ChunkRadius::new(32),
             chunk_generation_distance: ChunkRadius::new(4),
 
+            brick_index_block_max_size: 8192,
             brick_data_max_size: 500000,
             brick_palette_max_size: 500000,
+            voxel_object_max_count: 1024,
             brick_request_max_size: 64,
             brick_load_max_size: 128,
 
             voxel_unit_length: 1.0,
+
+            fps_limit: None,
         }
     }
 }